@@ -0,0 +1,78 @@
+//! Benchmarks for the analyzer and the flow engine.
+//!
+//! Tect only builds a binary, so the analysis modules are compiled into the
+//! benchmark directly. Run with `cargo bench`.
+
+#![allow(dead_code)]
+
+#[path = "../src/analyzer.rs"]
+mod analyzer;
+#[path = "../src/engine.rs"]
+mod engine;
+#[path = "../src/models.rs"]
+mod models;
+#[path = "../src/source_manager.rs"]
+mod source_manager;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::fmt::Write;
+use std::path::Path;
+use tower_lsp::lsp_types::Url;
+
+/// Generates a flow of `steps` chained functions sharing one constant.
+fn synthetic(steps: usize) -> String {
+    let mut source = String::from("constant Settings\nvariable V0\nfunction Start\n    > V0\n");
+    for i in 1..=steps {
+        writeln!(
+            source,
+            "variable V{}\nfunction Step{} Settings, V{}\n    > V{}",
+            i,
+            i,
+            i - 1,
+            i
+        )
+        .unwrap();
+    }
+    source.push_str("\nStart\n");
+    for i in 1..=steps {
+        writeln!(source, "Step{}", i).unwrap();
+    }
+    source
+}
+
+fn analyze(uri: &Url, source: &str) -> analyzer::Workspace {
+    let mut workspace = analyzer::Workspace::new();
+    workspace.analyze(uri.clone(), Some(source.to_string()));
+    workspace
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let dsbg = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/dsbg.tect");
+    let dsbg = dsbg.canonicalize().unwrap();
+    let inputs = [
+        (
+            "dsbg",
+            Url::from_file_path(&dsbg).unwrap(),
+            std::fs::read_to_string(&dsbg).unwrap(),
+        ),
+        (
+            "synthetic_500",
+            Url::parse("file:///synthetic.tect").unwrap(),
+            synthetic(500),
+        ),
+    ];
+
+    for (name, uri, source) in &inputs {
+        c.bench_function(&format!("analyze/{}", name), |b| {
+            b.iter(|| analyze(uri, black_box(source)))
+        });
+
+        let workspace = analyze(uri, source);
+        c.bench_function(&format!("simulate/{}", name), |b| {
+            b.iter(|| engine::Flow::new(true).simulate(black_box(&workspace.structure)))
+        });
+    }
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
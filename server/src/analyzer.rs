@@ -10,6 +10,7 @@ use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::Parser;
 use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag, Url};
 
@@ -17,6 +18,88 @@ use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag, Url};
 #[grammar = "tect.pest"]
 pub struct TectParser;
 
+/// Returns the name an identifier stands for.
+///
+/// Quoted identifiers (`"Load Config"`) name the text between the quotes.
+pub fn unquote(ident: &str) -> &str {
+    ident
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(ident)
+}
+
+/// Parses a Tect source file, recovering from syntax errors.
+///
+/// Returns the root pair and, if the source is not valid, the original parse
+/// error. Invalid sources are re-parsed with `recovering_program`, where lines
+/// that do not form a statement become `error_line` pairs, so the remaining
+/// statements can still be analyzed and highlighted.
+pub fn parse_program(content: &str) -> (Option<Pair<'_, Rule>>, Option<pest::error::Error<Rule>>) {
+    match TectParser::parse(Rule::program, content) {
+        Ok(mut pairs) => (pairs.next(), None),
+        Err(e) => {
+            let recovered = TectParser::parse(Rule::recovering_program, content)
+                .ok()
+                .and_then(|mut pairs| pairs.next());
+            (recovered, Some(e))
+        }
+    }
+}
+
+/// Directory containing the file at `uri`, if it is a local file.
+fn directory_of(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+}
+
+/// Finds the project root of a file: the directory of the nearest `tect.toml`
+/// above it, or the directory named by its `root = "..."` key.
+pub fn find_project_root(file: &Path) -> Option<PathBuf> {
+    let dir = file
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("tect.toml").is_file())?;
+    let config = std::fs::read_to_string(dir.join("tect.toml")).unwrap_or_default();
+    let root = config
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim() == "root")
+        .map(|(_, value)| value.trim().trim_matches(|c| c == '"' || c == '\''));
+    Some(normalize_path(&match root {
+        Some(root) => dir.join(root),
+        None => dir.to_path_buf(),
+    }))
+}
+
+/// Resolves `.` and `..` components, then symlinks through the deepest
+/// ancestor that exists on disk, so unsaved files normalize too.
+fn normalize_path(path: &Path) -> PathBuf {
+    let lexical = normalize_lexically(path);
+    for ancestor in lexical.ancestors() {
+        if let Ok(canonical) = std::fs::canonicalize(ancestor) {
+            let rest = lexical.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return canonical.join(rest);
+        }
+    }
+    lexical
+}
+
+/// Resolves `.` and `..` components without touching the file system.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
 /// The orchestrator for the analysis pipeline.
 ///
 /// `Workspace` manages the state of the compiler service, including:
@@ -31,6 +114,14 @@ pub struct Workspace {
     /// Tracks the URI used as the entry point for the current analysis session.
     /// This is used to detect context switches (e.g., when the user switches tabs).
     pub current_root: Option<Url>,
+    /// `.tect` files found in each directory indexed by [`Workspace::index_directory`].
+    directory_index: HashMap<PathBuf, Vec<FileId>>,
+    /// Project root of the current analysis (see [`find_project_root`]).
+    /// Imports resolving outside of it are rejected.
+    pub project_root: Option<PathBuf>,
+    /// Project root for files without a `tect.toml`, e.g. the editor's
+    /// workspace folder. Files outside of it use their own directory.
+    pub default_root: Option<PathBuf>,
 }
 
 impl Default for Workspace {
@@ -46,7 +137,77 @@ impl Workspace {
             source_manager: SourceManager::new(),
             structure: ProgramStructure::default(),
             current_root: None,
+            directory_index: HashMap::new(),
+            project_root: None,
+            default_root: None,
+        }
+    }
+
+    /// Loads every `.tect` file in the directory containing `uri`.
+    ///
+    /// Once indexed, the definitions of these sibling files are visible when
+    /// analyzing any file of the directory, even without an `import`. Each
+    /// directory is only scanned once; files already in memory are kept as-is.
+    pub fn index_directory(&mut self, uri: &Url) {
+        self.index_directory_with_progress(uri, |_, _| {});
+    }
+
+    /// Like [`Workspace::index_directory`], calling `progress(done, total)`
+    /// after each file is loaded so callers can report on large directories.
+    pub fn index_directory_with_progress(
+        &mut self,
+        uri: &Url,
+        mut progress: impl FnMut(usize, usize),
+    ) {
+        let Some(dir) = directory_of(uri) else {
+            return;
+        };
+        if self.directory_index.contains_key(&dir) {
+            return;
+        }
+
+        let siblings: Vec<Url> = walkdir::WalkDir::new(&dir)
+            .max_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry.file_type().is_file()
+                    && entry.path().extension().and_then(|e| e.to_str()) == Some("tect")
+            })
+            .filter_map(|entry| Url::from_file_path(entry.path()).ok())
+            .collect();
+
+        let mut ids = Vec::new();
+        for (i, sibling) in siblings.iter().enumerate() {
+            let id = self.source_manager.get_id(sibling);
+            if self.source_manager.get_content(id).is_some()
+                || self.source_manager.load_file(id, None)
+            {
+                ids.push(id);
+            }
+            progress(i + 1, siblings.len());
         }
+        self.directory_index.insert(dir, ids);
+    }
+
+    /// Whether the directory containing `uri` has yet to be indexed.
+    pub fn needs_indexing(&self, uri: &Url) -> bool {
+        directory_of(uri).is_some_and(|dir| !self.directory_index.contains_key(&dir))
+    }
+
+    /// Indexed files in the directory of `uri` that are not part of `analyzed`.
+    fn sibling_files(&self, uri: &Url, analyzed: &HashSet<FileId>) -> Vec<FileId> {
+        uri.to_file_path()
+            .ok()
+            .and_then(|p| p.parent().and_then(|d| self.directory_index.get(d)))
+            .map(|ids| {
+                ids.iter()
+                    .copied()
+                    .filter(|id| !analyzed.contains(id))
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     /// Entry point: Analyzes the project starting from a root URI.
@@ -63,6 +224,13 @@ impl Workspace {
     pub fn analyze(&mut self, root_uri: Url, root_content: Option<String>) {
         self.current_root = Some(root_uri.clone());
         self.structure = ProgramStructure::default();
+        self.project_root = root_uri.to_file_path().ok().and_then(|path| {
+            find_project_root(&path).or_else(|| {
+                let dir = normalize_path(path.parent()?);
+                let default = self.default_root.as_deref().map(normalize_path);
+                Some(default.filter(|root| dir.starts_with(root)).unwrap_or(dir))
+            })
+        });
 
         // 1. Dependency Discovery
         let root_id = self.source_manager.get_id(&root_uri);
@@ -132,69 +300,98 @@ impl Workspace {
             return;
         }
 
+        // Sibling files only contribute definitions: their flows and
+        // diagnostics belong to their own analysis, and on name clashes the
+        // definitions of the analyzed files win.
+        let siblings = self.sibling_files(&root_uri, &visited_set);
+
         // 3. Multi-Pass Parsing
         // Pass 1: Definitions
-        for file_id in &visited_order {
+        for file_id in visited_order.iter().chain(&siblings) {
             self.pass_definitions(*file_id);
         }
 
         // Pass 2: Resolution & Linking
         for file_id in &visited_order {
-            self.pass_resolution(*file_id);
+            self.pass_resolution(*file_id, true);
+        }
+        for file_id in &siblings {
+            self.pass_resolution(*file_id, false);
         }
 
         // 4. Validation (Unused Symbols)
         self.check_unused_symbols();
+        self.check_functions_outside_flows();
+        self.check_duplicate_contracts();
+        self.structure
+            .diagnostics
+            .retain(|d| !siblings.contains(&d.file_id));
+    }
+
+    /// Records on each function node the URI of the file defining its function.
+    ///
+    /// The engine only sees [`ProgramStructure`], so this is applied to a
+    /// simulated graph before exporting it. Artificial nodes are left untouched.
+    pub fn attach_source_files(&self, graph: &mut Graph) {
+        for node in &mut graph.nodes {
+            node.source_file = self
+                .structure
+                .symbol_table
+                .get(&node.function.uid)
+                .and_then(|meta| self.source_manager.get_uri(meta.definition_span.file_id))
+                .map(|uri| uri.to_string());
+        }
     }
 
     /// Scans a file for import statements to build the dependency graph.
     ///
     /// This does a shallow parse of the file to find `import` statements.
-    /// It resolves relative paths (e.g., `./utils.tect`) against the file's URI
-    /// and ensures the target file exists either in memory or on disk.
+    /// It resolves relative paths (e.g., `./utils.tect`) against the file's URI,
+    /// then against the project root, and ensures the target file exists either
+    /// in memory or on disk. Targets outside the project root are rejected.
     fn scan_imports(&mut self, content: &str, file_id: FileId) -> Vec<(Url, Span)> {
         let mut results = Vec::new();
-        if let Ok(mut pairs) = TectParser::parse(Rule::program, content) {
-            if let Some(root) = pairs.next() {
-                for pair in root.into_inner() {
-                    if let Rule::import_stmt = pair.as_rule() {
-                        let span = self.map_span(&pair, file_id);
-                        let mut inner = pair.into_inner();
-                        let _ = inner.next(); // import kw
-                        let str_lit = inner.next().unwrap().as_str();
-                        let rel_path = &str_lit[1..str_lit.len() - 1]; // strip quotes
-
-                        // Clone base_uri to avoid conflicting borrow
-                        let base_uri = self.source_manager.get_uri(file_id).cloned();
-
-                        if let Some(base_uri) = base_uri {
-                            // Use Url::join to handle relative paths (./, ../) correctly
-                            if let Ok(target_uri) = base_uri.join(rel_path) {
-                                let target_id = self.source_manager.get_id(&target_uri);
-                                let is_in_memory =
-                                    self.source_manager.get_content(target_id).is_some();
-                                let exists_on_disk = target_uri
-                                    .to_file_path()
-                                    .map(|p| p.exists())
-                                    .unwrap_or(false);
-
-                                // Check memory first (unsaved files), then disk
-                                if is_in_memory || exists_on_disk {
-                                    results.push((target_uri, span));
-                                } else {
-                                    self.report_error(
-                                        file_id,
-                                        Some(span),
-                                        format!("Import not found: '{}'", rel_path),
-                                    );
-                                }
-                            } else {
+        if let Some(root) = parse_program(content).0 {
+            for pair in root.into_inner() {
+                if let Rule::import_stmt = pair.as_rule() {
+                    let span = self.map_span(&pair, file_id);
+                    let mut inner = pair.into_inner();
+                    let _ = inner.next(); // import kw
+                    let str_lit = inner.next().unwrap().as_str();
+                    let rel_path = &str_lit[1..str_lit.len() - 1]; // strip quotes
+
+                    // Clone base_uri to avoid conflicting borrow
+                    let base_uri = self.source_manager.get_uri(file_id).cloned();
+
+                    if let Some(base_uri) = base_uri {
+                        let candidates = self.import_candidates(&base_uri, rel_path);
+                        let found = candidates.iter().find(|uri| self.import_exists(uri));
+
+                        if candidates.is_empty() {
+                            self.report_error(
+                                file_id,
+                                Some(span),
+                                format!("Invalid import path: '{}'", rel_path),
+                            );
+                        } else if let Some(target_uri) = found {
+                            if self.is_outside_project(target_uri) {
                                 self.report_error(
                                     file_id,
                                     Some(span),
-                                    format!("Invalid import path: '{}'", rel_path),
+                                    format!(
+                                        "Import '{}' resolves outside the project root",
+                                        rel_path
+                                    ),
                                 );
+                            } else {
+                                results.push((target_uri.clone(), span));
                             }
+                        } else {
+                            self.report_error(
+                                file_id,
+                                Some(span),
+                                format!("Import not found: '{}'", rel_path),
+                            );
                         }
                     }
                 }
@@ -203,6 +400,48 @@ impl Workspace {
         results
     }
 
+    /// Resolves an import path the way [`Workspace::scan_imports`] does: next to
+    /// the importing file first, then at the project root. Returns `None` when
+    /// no candidate exists or the target lies outside the project.
+    pub fn resolve_import(&mut self, base_uri: &Url, rel_path: &str) -> Option<Url> {
+        let found = self
+            .import_candidates(base_uri, rel_path)
+            .into_iter()
+            .find(|uri| self.import_exists(uri))?;
+        (!self.is_outside_project(&found)).then_some(found)
+    }
+
+    /// Targets an import path may refer to, in lookup order.
+    fn import_candidates(&self, base_uri: &Url, rel_path: &str) -> Vec<Url> {
+        // Use Url::join to handle relative paths (./, ../) correctly
+        let root_uri = self
+            .project_root
+            .as_ref()
+            .and_then(|root| Url::from_directory_path(root).ok());
+        [Some(base_uri), root_uri.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|base| base.join(rel_path).ok())
+            .collect()
+    }
+
+    /// Checks memory first (unsaved files), then disk.
+    fn import_exists(&mut self, uri: &Url) -> bool {
+        let target_id = self.source_manager.get_id(uri);
+        self.source_manager.get_content(target_id).is_some()
+            || uri.to_file_path().map(|p| p.exists()).unwrap_or(false)
+    }
+
+    /// Returns true if `uri` is a file outside the project root.
+    ///
+    /// Both paths are canonicalized, so symlinks cannot lead out of the project.
+    fn is_outside_project(&self, uri: &Url) -> bool {
+        match (&self.project_root, uri.to_file_path()) {
+            (Some(root), Ok(path)) => !normalize_path(&path).starts_with(root),
+            _ => false,
+        }
+    }
+
     /// Detects cycles in the dependency graph using Depth-First Search (DFS).
     ///
     /// Returns `Some(String)` containing the cycle path if one is detected, or `None` otherwise.
@@ -275,30 +514,53 @@ impl Workspace {
         };
         let content_owned = content.to_string();
 
-        let parse_res = TectParser::parse(Rule::program, &content_owned);
-        let pairs = match parse_res {
-            Ok(mut p) => p.next().unwrap(),
-            Err(e) => {
-                let start_off = match e.line_col {
-                    pest::error::LineColLocation::Pos((l, c)) => {
-                        self.pos_to_offset(&content_owned, l, c)
-                    }
-                    pest::error::LineColLocation::Span((l, c), _) => {
-                        self.pos_to_offset(&content_owned, l, c)
-                    }
-                };
-                let end_off = start_off;
-                self.report_error(
-                    file_id,
-                    Some(Span::new(file_id, start_off, end_off)),
-                    format!("Syntax Error: {}", e.variant.message()),
-                );
-                return;
-            }
+        let (program, error) = parse_program(&content_owned);
+        let mut error_offset = None;
+        if let Some(e) = error {
+            let start_off = match e.line_col {
+                pest::error::LineColLocation::Pos((l, c)) => {
+                    self.pos_to_offset(&content_owned, l, c)
+                }
+                pest::error::LineColLocation::Span((l, c), _) => {
+                    self.pos_to_offset(&content_owned, l, c)
+                }
+            };
+            error_offset = Some(start_off);
+            self.report_error(
+                file_id,
+                Some(Span::new(file_id, start_off, start_off)),
+                format!("Syntax Error: {}", e.variant.message()),
+            );
+        }
+        let Some(program) = program else {
+            return;
         };
 
-        for pair in pairs.into_inner() {
+        let is_root = self
+            .current_root
+            .as_ref()
+            .is_some_and(|root| self.source_manager.get_uri(file_id) == Some(root));
+
+        for pair in program.into_inner() {
             match pair.as_rule() {
+                // Only the root file describes the diagram
+                Rule::front_matter if is_root => self.read_front_matter(pair.as_str()),
+                Rule::error_line => {
+                    // The region holding the original error is already reported
+                    let span = pair.as_span();
+                    if error_offset.is_some_and(|o| (span.start()..=span.end()).contains(&o)) {
+                        continue;
+                    }
+                    let span = self.map_span(&pair, file_id);
+                    self.report_error(
+                        file_id,
+                        Some(span),
+                        format!(
+                            "Syntax Error: skipped unrecognized input '{}'",
+                            pair.as_str()
+                        ),
+                    );
+                }
                 Rule::const_def => self.define_type(&pair, "constant", file_id),
                 Rule::var_def => self.define_type(&pair, "variable", file_id),
                 Rule::err_def => self.define_type(&pair, "error", file_id),
@@ -317,61 +579,299 @@ impl Workspace {
     /// - Parsing function signatures (inputs/outputs) and linking them to defined artifacts.
     /// - Building the flow sequence and linking flow steps to functions.
     /// - Validating that all referenced symbols exist.
-    fn pass_resolution(&mut self, file_id: FileId) {
+    ///
+    /// Flow steps, flow blocks and assertions are skipped unless `include_flows` is set.
+    fn pass_resolution(&mut self, file_id: FileId, include_flows: bool) {
         let content: &str = match self.source_manager.get_content(file_id) {
             Some(c) => c,
             None => return,
         };
         let content_owned = content.to_string();
 
-        let parse_res = TectParser::parse(Rule::program, &content_owned);
-        let pairs = match parse_res {
-            Ok(mut p) => p.next().unwrap(),
-            Err(_) => return, // Handled in Pass 1
+        // Syntax errors are reported in Pass 1
+        let Some(pairs) = parse_program(&content_owned).0 else {
+            return;
         };
 
         for pair in pairs.into_inner() {
             match pair.as_rule() {
                 Rule::func_def => self.link_function_contracts(&pair, file_id),
+                _ if !include_flows => {}
                 Rule::flow_step => {
-                    let name = pair.as_str().trim();
-                    if !name.is_empty() {
-                        let span = self.map_span(&pair, file_id);
-                        self.structure.flow.push(FlowStep {
-                            function_name: name.to_string(),
-                            span,
-                        });
-
-                        if let Some(func) = self.structure.catalog.get(name) {
-                            self.add_occurrence(func.uid, span);
-                        } else {
-                            self.report_error(
-                                file_id,
-                                Some(span),
-                                format!("Undefined function: '{}'", name),
-                            );
-                        }
+                    if let Some(step) = self.resolve_flow_step(pair, file_id) {
+                        self.structure.flow.push(step);
                     }
                 }
+                Rule::flow_block => self.resolve_flow_block(pair, file_id),
+                Rule::assert_stmt => self.resolve_assertion(pair, file_id),
                 _ => {}
             }
         }
     }
 
+    /// Resolves a named `flow Name { ... }` block into `structure.flows`.
+    fn resolve_flow_block(&mut self, pair: Pair<Rule>, file_id: FileId) {
+        let mut inner = pair.into_inner();
+        let _docs = self.collect_docs(&mut inner);
+        let _kw = inner.next();
+        let Some(name_p) = inner.next() else {
+            return;
+        };
+        let name = unquote(name_p.as_str()).to_string();
+
+        if self.structure.flows.contains_key(&name) {
+            let span = self.map_span(&name_p, file_id);
+            self.report_error(
+                file_id,
+                Some(span),
+                format!("Flow '{}' is already defined.", name),
+            );
+            return;
+        }
+
+        let steps: Vec<FlowStep> = inner
+            .filter(|p| p.as_rule() == Rule::flow_step)
+            .filter_map(|p| self.resolve_flow_step(p, file_id))
+            .collect();
+        self.structure.flow_order.push(name.clone());
+        self.structure.flows.insert(name, steps);
+    }
+
+    /// Resolves an `assert` statement into `structure.assertions`.
+    ///
+    /// Reachability endpoints must be functions and handled errors must be
+    /// errors; otherwise the assertion is reported and dropped.
+    fn resolve_assertion(&mut self, pair: Pair<Rule>, file_id: FileId) {
+        let span = self.map_span(&pair, file_id);
+        let mut inner = pair.into_inner();
+        let _docs = self.collect_docs(&mut inner);
+        let _kw = inner.next();
+        let Some(body) = inner.next() else {
+            return;
+        };
+        let is_reachable = body.as_rule() == Rule::assert_reachable;
+        let names: Vec<Pair<Rule>> = body
+            .into_inner()
+            .filter(|p| p.as_rule() == Rule::ident)
+            .collect();
+
+        let mut valid = true;
+        for name_p in &names {
+            let name = unquote(name_p.as_str());
+            let name_span = self.map_span(name_p, file_id);
+            let uid = if is_reachable {
+                self.structure.catalog.get(name).map(|f| f.uid)
+            } else {
+                match self.structure.artifacts.get(name) {
+                    Some(kind @ Kind::Error(_)) => Some(kind.uid()),
+                    _ => None,
+                }
+            };
+            match uid {
+                Some(uid) => self.add_occurrence(uid, name_span),
+                None => {
+                    valid = false;
+                    let (expected, article) = if is_reachable {
+                        ("function", "a")
+                    } else {
+                        ("error", "an")
+                    };
+                    let label = if self.structure.catalog.contains_key(name) {
+                        Some("function")
+                    } else {
+                        self.describe_non_function(name)
+                    };
+                    let message = match label {
+                        Some(label) => {
+                            format!("'{}' is a {}, not {} {}.", name, label, article, expected)
+                        }
+                        None => format!("Undefined {}: '{}'", expected, name),
+                    };
+                    self.report_error(file_id, Some(name_span), message);
+                }
+            }
+        }
+        if !valid {
+            return;
+        }
+
+        let names: Vec<String> = names
+            .iter()
+            .map(|p| unquote(p.as_str()).to_string())
+            .collect();
+        let kind = match names.as_slice() {
+            [from, to] if is_reachable => AssertionKind::Reachable {
+                from: from.clone(),
+                to: to.clone(),
+            },
+            [error] => AssertionKind::Handled {
+                error: error.clone(),
+            },
+            _ => return,
+        };
+        self.structure.assertions.push(Assertion { kind, span });
+    }
+
+    /// Resolves a flow step and links it to the invoked function.
+    fn resolve_flow_step(&mut self, pair: Pair<Rule>, file_id: FileId) -> Option<FlowStep> {
+        let mut inner = pair.into_inner();
+        let documentation = self.collect_docs(&mut inner);
+        let name_p = inner.next()?;
+        let name = unquote(name_p.as_str().trim());
+        if name.is_empty() {
+            return None;
+        }
+        let span = self.map_span(&name_p, file_id);
+
+        if let Some(func) = self.structure.catalog.get(name) {
+            self.add_occurrence(func.uid, span);
+        } else if let Some(label) = self.describe_non_function(name) {
+            self.report_error(
+                file_id,
+                Some(span),
+                format!(
+                    "'{}' is a {}, not a function. Flow steps must invoke functions.",
+                    name, label
+                ),
+            );
+        } else {
+            self.report_error(
+                file_id,
+                Some(span),
+                format!("Undefined function: '{}'", name),
+            );
+        }
+
+        Some(FlowStep {
+            function_name: name.to_string(),
+            span,
+            documentation,
+        })
+    }
+
     fn check_unused_symbols(&mut self) {
         for meta in self.structure.symbol_table.values() {
-            if meta.occurrences.len() == 1 {
+            if meta.occurrences.len() == 1 + meta.merged_definitions.len() {
+                // Groups are only referenced by their members, so an unused
+                // group is an empty one, often left over from a refactoring
+                let (message, severity, code) = if self.structure.groups.contains_key(&meta.name) {
+                    (
+                        format!("Group '{}' has no member functions.", meta.name),
+                        DiagnosticSeverity::INFORMATION,
+                        "empty-group",
+                    )
+                } else {
+                    let code = if self.structure.catalog.contains_key(&meta.name) {
+                        "dead-function"
+                    } else {
+                        "unused-symbol"
+                    };
+                    (
+                        format!("Unused symbol: '{}'", meta.name),
+                        DiagnosticSeverity::WARNING,
+                        code,
+                    )
+                };
                 self.structure.diagnostics.push(DiagnosticWithContext {
                     file_id: meta.definition_span.file_id,
                     span: Some(meta.definition_span),
-                    message: format!("Unused symbol: '{}'", meta.name),
-                    severity: DiagnosticSeverity::WARNING,
+                    message,
+                    severity,
                     tags: vec![DiagnosticTag::UNNECESSARY],
+                    node_uid: None,
+                    code: Some(code),
                 });
             }
         }
     }
 
+    /// Warns on functions that no flow invokes, although other code refers to
+    /// them (e.g. an `assert`).
+    ///
+    /// Functions nothing refers to are already reported as unused symbols, and
+    /// projects without any flow step are definition libraries, so neither is
+    /// reported here.
+    fn check_functions_outside_flows(&mut self) {
+        let invoked: HashSet<&str> = self
+            .structure
+            .all_steps()
+            .map(|step| step.function_name.as_str())
+            .collect();
+        if invoked.is_empty() {
+            return;
+        }
+
+        let mut spans: Vec<(Span, &str)> = self
+            .structure
+            .catalog
+            .values()
+            .filter(|func| !invoked.contains(func.name.as_str()))
+            .filter_map(|func| self.structure.symbol_table.get(&func.uid))
+            .filter(|meta| meta.occurrences.len() > 1 + meta.merged_definitions.len())
+            .map(|meta| (meta.definition_span, meta.name.as_str()))
+            .collect();
+        spans.sort_by_key(|(span, _)| (span.file_id, span.start));
+
+        let diagnostics: Vec<DiagnosticWithContext> = spans
+            .into_iter()
+            .map(|(span, name)| DiagnosticWithContext {
+                file_id: span.file_id,
+                span: Some(span),
+                message: format!("Function '{}' is never invoked in a flow.", name),
+                severity: DiagnosticSeverity::WARNING,
+                tags: vec![DiagnosticTag::UNNECESSARY],
+                node_uid: None,
+                code: Some("dead-function"),
+            })
+            .collect();
+        self.structure.diagnostics.extend(diagnostics);
+    }
+
+    /// Reports functions sharing the same contract (see [`Function::canonical_signature`]).
+    ///
+    /// These are likely accidental duplicates or candidates for merging.
+    /// Functions with no inputs and no outputs are not compared.
+    fn check_duplicate_contracts(&mut self) {
+        let mut by_signature: HashMap<String, Vec<(Span, String)>> = HashMap::new();
+        for func in self.structure.catalog.values() {
+            if func.consumes.is_empty() && func.produces.is_empty() {
+                continue;
+            }
+            if let Some(meta) = self.structure.symbol_table.get(&func.uid) {
+                by_signature
+                    .entry(func.canonical_signature())
+                    .or_default()
+                    .push((meta.definition_span, func.name.clone()));
+            }
+        }
+
+        let mut duplicates: Vec<_> = by_signature
+            .into_values()
+            .filter(|funcs| funcs.len() > 1)
+            .collect();
+        for funcs in &mut duplicates {
+            funcs.sort_by_key(|(span, _)| (span.file_id, span.start));
+        }
+        duplicates.sort_by_key(|funcs| (funcs[0].0.file_id, funcs[0].0.start));
+
+        for funcs in duplicates {
+            let (span, _) = funcs[0];
+            let names: Vec<String> = funcs.iter().map(|(_, n)| format!("'{}'", n)).collect();
+            self.structure.diagnostics.push(DiagnosticWithContext {
+                file_id: span.file_id,
+                span: Some(span),
+                message: format!(
+                    "Functions {} have identical contracts. Consider merging them.",
+                    names.join(", ")
+                ),
+                severity: DiagnosticSeverity::INFORMATION,
+                tags: vec![],
+                node_uid: None,
+                code: Some("identical-contracts"),
+            });
+        }
+    }
+
     // --- Helpers ---
 
     fn map_span(&self, p: &Pair<Rule>, file_id: FileId) -> Span {
@@ -400,6 +900,24 @@ impl Workspace {
         false
     }
 
+    /// Returns a user-facing label when `name` refers to a non-function symbol.
+    ///
+    /// Used to explain flow steps that reuse a type or group name instead of
+    /// reporting them as plain undefined functions.
+    fn describe_non_function(&self, name: &str) -> Option<&'static str> {
+        if let Some(kind) = self.structure.artifacts.get(name) {
+            Some(match kind {
+                Kind::Constant(_) => "constant",
+                Kind::Variable(_) => "variable",
+                Kind::Error(_) => "error",
+            })
+        } else if self.structure.groups.contains_key(name) {
+            Some("group")
+        } else {
+            None
+        }
+    }
+
     fn report_error(&mut self, file_id: FileId, span: Option<Span>, msg: String) {
         self.structure.diagnostics.push(DiagnosticWithContext {
             file_id,
@@ -407,27 +925,52 @@ impl Workspace {
             message: msg,
             severity: DiagnosticSeverity::ERROR,
             tags: vec![],
+            node_uid: None,
+            code: None,
         });
     }
 
     // --- Definition Logic ---
 
     fn define_type(&mut self, pair: &Pair<Rule>, kw: &str, file_id: FileId) {
+        self.check_ignored_color(pair, kw, file_id);
         let mut inner = pair.clone().into_inner();
         let doc_str = self.collect_docs(&mut inner);
         let _kw = inner.next().unwrap();
         let name_p = inner.next().unwrap();
-        let name = name_p.as_str().to_string();
+        let name = unquote(name_p.as_str()).to_string();
         let span = self.map_span(&name_p, file_id);
 
+        if self.merge_artifact(&name, kw, doc_str.as_deref(), span) {
+            return;
+        }
         if self.check_duplicate(&name, span) {
             return;
         }
 
+        let mut tags: Vec<String> = pair
+            .clone()
+            .into_inner()
+            .filter(|p| p.as_rule() == Rule::tag)
+            .map(|p| p.as_str()[1..].to_string())
+            .collect();
+        tags.sort();
+        tags.dedup();
+
         let kind = match kw {
-            "constant" => Kind::Constant(Arc::new(Constant::new(name.clone(), doc_str))),
-            "variable" => Kind::Variable(Arc::new(Variable::new(name.clone(), doc_str))),
-            _ => Kind::Error(Arc::new(Error::new(name.clone(), doc_str))),
+            "constant" => Kind::Constant(Arc::new(Constant {
+                tags,
+                ..Constant::new(name.clone(), doc_str)
+            })),
+            "variable" => Kind::Variable(Arc::new(Variable {
+                immutable: tags.iter().any(|t| t == "immutable"),
+                tags,
+                ..Variable::new(name.clone(), doc_str)
+            })),
+            _ => Kind::Error(Arc::new(Error {
+                tags,
+                ..Error::new(name.clone(), doc_str)
+            })),
         };
 
         self.structure.symbol_table.insert(
@@ -436,17 +979,72 @@ impl Workspace {
                 name: name.clone(),
                 definition_span: span,
                 occurrences: vec![span],
+                merged_definitions: vec![],
             },
         );
         self.structure.artifacts.insert(name, kind);
     }
 
+    /// Unifies an artifact redefined in another file with its first definition.
+    ///
+    /// Identically named artifacts across files share one UID, so tokens match
+    /// regardless of which file declared them. A warning is reported if the
+    /// definitions disagree on kind or documentation; the first one wins.
+    /// Returns `false` if there is nothing to merge (e.g. same-file duplicates).
+    fn merge_artifact(&mut self, name: &str, kw: &str, docs: Option<&str>, span: Span) -> bool {
+        let Some(existing) = self.structure.artifacts.get(name) else {
+            return false;
+        };
+        let existing_docs = existing.docs().map(str::to_string);
+        let existing_kw = self.describe_non_function(name).unwrap_or_default();
+        let Some(meta) = self.structure.symbol_table.get_mut(&existing.uid()) else {
+            return false;
+        };
+
+        let same_file = std::iter::once(&meta.definition_span)
+            .chain(&meta.merged_definitions)
+            .any(|s| s.file_id == span.file_id);
+        if same_file {
+            return false;
+        }
+
+        meta.occurrences.push(span);
+        meta.merged_definitions.push(span);
+
+        let conflict = if existing_kw != kw {
+            Some(format!(
+                "'{}' is defined as a {} here but as a {} in another file. The first definition is used.",
+                name, kw, existing_kw
+            ))
+        } else if docs.is_some() && existing_docs.is_some() && docs != existing_docs.as_deref() {
+            Some(format!(
+                "'{}' is also defined in another file with different documentation. The first definition is used.",
+                name
+            ))
+        } else {
+            None
+        };
+
+        if let Some(message) = conflict {
+            self.structure.diagnostics.push(DiagnosticWithContext {
+                file_id: span.file_id,
+                span: Some(span),
+                message,
+                severity: DiagnosticSeverity::WARNING,
+                tags: vec![],
+                node_uid: None,
+                code: Some("conflicting-docs"),
+            });
+        }
+        true
+    }
+
     fn define_group(&mut self, pair: &Pair<Rule>, file_id: FileId) {
         let mut inner = pair.clone().into_inner();
         let doc_str = self.collect_docs(&mut inner);
         let _kw = inner.next().unwrap();
         let name_p = inner.next().unwrap();
-        let name = name_p.as_str().to_string();
+        let name = unquote(name_p.as_str()).to_string();
         let span = self.map_span(&name_p, file_id);
 
         if self.check_duplicate(&name, span) {
@@ -460,6 +1058,7 @@ impl Workspace {
                 name: name.clone(),
                 definition_span: span,
                 occurrences: vec![span],
+                merged_definitions: vec![],
             },
         );
         self.structure.groups.insert(name, group);
@@ -473,7 +1072,7 @@ impl Workspace {
         if let Some(p) = inner.peek() {
             if p.as_rule() == Rule::ident {
                 let g_name_p = inner.next().unwrap();
-                let g_name = g_name_p.as_str();
+                let g_name = unquote(g_name_p.as_str());
                 if let Some(g) = self.structure.groups.get(g_name).cloned() {
                     group = Some(g.clone());
                     self.add_occurrence(g.uid, self.map_span(&g_name_p, file_id));
@@ -489,20 +1088,23 @@ impl Workspace {
 
         let _kw = inner.next().unwrap();
         let name_p = inner.next().unwrap();
-        let name = name_p.as_str().to_string();
+        let name = unquote(name_p.as_str()).to_string();
         let span = self.map_span(&name_p, file_id);
 
         if self.check_duplicate(&name, span) {
             return;
         }
 
-        let function = Arc::new(Function::new_skeleton(name.clone(), doc_str, group));
+        let mut function = Function::new_skeleton(name.clone(), doc_str, group);
+        function.color = self.collect_color(pair, file_id);
+        let function = Arc::new(function);
         self.structure.symbol_table.insert(
             function.uid,
             SymbolMetadata {
                 name: name.clone(),
                 definition_span: span,
                 occurrences: vec![span],
+                merged_definitions: vec![],
             },
         );
         self.structure.catalog.insert(name, function);
@@ -525,7 +1127,7 @@ impl Workspace {
         }
         let _kw = inner.next();
         let name_p = inner.next().unwrap();
-        let name = name_p.as_str();
+        let name = unquote(name_p.as_str());
 
         // Stable context seed: Function name
         let func_ctx = name;
@@ -541,8 +1143,16 @@ impl Workspace {
         // Check if optional output block exists
         if let Some(outputs_pair) = inner.next() {
             if outputs_pair.as_rule() == Rule::func_outputs {
-                for (i, line) in outputs_pair.into_inner().enumerate() {
+                let lines = outputs_pair
+                    .into_inner()
+                    .filter(|p| p.as_rule() == Rule::output_line);
+                for (i, line) in lines.enumerate() {
                     let list = line.into_inner().next().unwrap();
+                    if list.as_rule() == Rule::none_output {
+                        // `> None`: an explicit branch producing nothing
+                        produces.push(Vec::new());
+                        continue;
+                    }
                     produces.push(self.resolve_tokens(
                         list,
                         file_id,
@@ -575,18 +1185,20 @@ impl Workspace {
     ) -> Vec<Token> {
         let mut tokens = Vec::new();
         for (i, t_pair) in pair.into_inner().enumerate() {
-            let inner = t_pair.into_inner().next().unwrap();
+            let mut parts = t_pair.into_inner();
+            let inner = parts.next().unwrap();
+            let optional_marker = parts.next();
             let (name, card, span) = match inner.as_rule() {
                 Rule::collection => {
                     let ident_p = inner.into_inner().next().unwrap();
                     (
-                        ident_p.as_str(),
+                        unquote(ident_p.as_str()),
                         Cardinality::Collection,
                         self.map_span(&ident_p, file_id),
                     )
                 }
                 _ => (
-                    inner.as_str(),
+                    unquote(inner.as_str()),
                     Cardinality::Unitary,
                     self.map_span(&inner, file_id),
                 ),
@@ -601,7 +1213,23 @@ impl Workspace {
                 let token_sig = format!("{}:{}:{}:{}", ctx_func, ctx_dir, i, name);
                 let uid = hash_name(&token_sig);
 
-                tokens.push(Token::new(k, card, uid));
+                let mut token = Token::new(k, card, uid);
+                if let Some(marker) = optional_marker {
+                    if ctx_dir == "in" {
+                        token.optional = true;
+                    } else {
+                        let marker_span = self.map_span(&marker, file_id);
+                        self.report_error(
+                            file_id,
+                            Some(marker_span),
+                            format!(
+                                "Output '{}' cannot be optional. Only consumed tokens can be marked with '?'.",
+                                name
+                            ),
+                        );
+                    }
+                }
+                tokens.push(token);
             } else {
                 self.report_error(
                     file_id,
@@ -616,12 +1244,46 @@ impl Workspace {
         tokens
     }
 
+    /// Reads the `title`, `author` and font keys of a front matter block.
+    ///
+    /// Values may be quoted; other keys are ignored.
+    fn read_front_matter(&mut self, block: &str) {
+        for line in block.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            // Only a matching pair of quotes is YAML quoting, e.g. not in `It's`
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|&q| value.strip_prefix(q)?.strip_suffix(q))
+                .unwrap_or(value);
+            let value = (!value.is_empty()).then(|| value.to_string());
+            match key.trim() {
+                "title" => self.structure.title = value,
+                "author" => self.structure.author = value,
+                "font" => self.structure.font = value,
+                "node_font_size" => {
+                    self.structure.node_font_size = value.and_then(|v| v.parse().ok())
+                }
+                "edge_font_size" => {
+                    self.structure.edge_font_size = value.and_then(|v| v.parse().ok())
+                }
+                _ => {}
+            }
+        }
+    }
+
     fn collect_docs(&self, inner: &mut Pairs<Rule>) -> Option<String> {
         let mut docs = Vec::new();
         while let Some(p) = inner.peek() {
             if p.as_rule() == Rule::doc_line {
                 let raw = inner.next().unwrap().as_str();
-                docs.push(raw.trim_start_matches('#').trim().to_string());
+                let line = raw.trim_start_matches('#').trim();
+                // Suppression and color pragmas are not documentation
+                if !line.starts_with("tect:allow") && !line.starts_with("@color") {
+                    docs.push(line.to_string());
+                }
             } else {
                 break;
             }
@@ -633,6 +1295,74 @@ impl Workspace {
         }
     }
 
+    /// Reads a `# @color #rrggbb` pragma from the comment block of a statement.
+    ///
+    /// Short `#rgb` colors are expanded to `#rrggbb`, so exporters only see the
+    /// long form; other values are reported and ignored.
+    fn collect_color(&mut self, pair: &Pair<Rule>, file_id: FileId) -> Option<String> {
+        let mut color = None;
+        for p in pair.clone().into_inner() {
+            if p.as_rule() != Rule::doc_line {
+                break;
+            }
+            let line = p.as_str().trim_start_matches('#').trim();
+            let Some(value) = line.strip_prefix("@color") else {
+                continue;
+            };
+            let value = value.trim();
+            let digits = value.strip_prefix('#').unwrap_or_default();
+            if !matches!(digits.len(), 3 | 6) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                self.structure.diagnostics.push(DiagnosticWithContext {
+                    file_id,
+                    span: Some(self.map_span(&p, file_id)),
+                    message: format!(
+                        "Invalid color '{}'. Expected a hex color such as #ff8800.",
+                        value
+                    ),
+                    severity: DiagnosticSeverity::WARNING,
+                    tags: vec![],
+                    node_uid: None,
+                    code: None,
+                });
+                continue;
+            }
+            let digits = digits.to_ascii_lowercase();
+            color = Some(if digits.len() == 3 {
+                format!(
+                    "#{}",
+                    digits.chars().flat_map(|c| [c, c]).collect::<String>()
+                )
+            } else {
+                format!("#{}", digits)
+            });
+        }
+        color
+    }
+
+    /// Warns about `# @color` pragmas above data definitions, which have no effect.
+    fn check_ignored_color(&mut self, pair: &Pair<Rule>, kw: &str, file_id: FileId) {
+        for p in pair.clone().into_inner() {
+            if p.as_rule() != Rule::doc_line {
+                break;
+            }
+            let line = p.as_str().trim_start_matches('#').trim();
+            if line.starts_with("@color") {
+                self.structure.diagnostics.push(DiagnosticWithContext {
+                    file_id,
+                    span: Some(self.map_span(&p, file_id)),
+                    message: format!(
+                        "'@color' only applies to functions and is ignored on a {}.",
+                        kw
+                    ),
+                    severity: DiagnosticSeverity::WARNING,
+                    tags: vec![],
+                    node_uid: None,
+                    code: Some("ignored-color"),
+                });
+            }
+        }
+    }
+
     fn pos_to_offset(&self, content: &str, line: usize, col: usize) -> usize {
         let mut curr_line = 1;
         let mut curr_col = 1;
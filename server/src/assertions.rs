@@ -0,0 +1,92 @@
+//! # Architecture Assertions
+//!
+//! Checks the invariants declared with `assert` against a simulated [`Graph`].
+//! Failing assertions become ERROR diagnostics at the `assert` statement.
+
+use crate::models::{
+    hash_name, Assertion, AssertionKind, DiagnosticWithContext, EdgeRelation, Graph,
+};
+use std::collections::{HashSet, VecDeque};
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+/// Evaluates each assertion, returning a diagnostic for every one that fails.
+pub fn evaluate_assertions(graph: &Graph, assertions: &[Assertion]) -> Vec<DiagnosticWithContext> {
+    assertions
+        .iter()
+        .filter_map(|assertion| {
+            let (message, node_uid) = failure(graph, &assertion.kind)?;
+            Some(DiagnosticWithContext {
+                file_id: assertion.span.file_id,
+                span: Some(assertion.span),
+                message: format!("Assertion failed: {}", message),
+                severity: DiagnosticSeverity::ERROR,
+                tags: vec![],
+                node_uid,
+                code: None,
+            })
+        })
+        .collect()
+}
+
+/// Describes why an assertion does not hold, or `None` if it does.
+fn failure(graph: &Graph, kind: &AssertionKind) -> Option<(String, Option<u32>)> {
+    match kind {
+        AssertionKind::Reachable { from, to } => {
+            let start = hash_name(from);
+            let target = hash_name(to);
+            for (name, uid) in [(from, start), (to, target)] {
+                if !graph.nodes.iter().any(|n| n.uid == uid) {
+                    return Some((
+                        format!("'{}' is not part of the simulated flow.", name),
+                        None,
+                    ));
+                }
+            }
+            (!reaches(graph, start, target)).then(|| {
+                (
+                    format!("'{}' is not reachable from '{}'.", to, from),
+                    Some(start),
+                )
+            })
+        }
+        AssertionKind::Handled { error } => {
+            let fatal: HashSet<u32> = graph
+                .nodes
+                .iter()
+                .filter(|n| n.is_artificial_error_termination)
+                .map(|n| n.uid)
+                .collect();
+            let edge = graph.edges.iter().find(|e| {
+                e.relation == EdgeRelation::ErrorFlow
+                    && fatal.contains(&e.to_node_uid)
+                    && e.token.kind.name() == error
+            })?;
+            let producer = graph
+                .nodes
+                .iter()
+                .find(|n| n.uid == edge.from_node_uid)
+                .map_or("?", |n| n.function.name.as_str());
+            Some((
+                format!("'{}' from '{}' is never handled.", error, producer),
+                Some(edge.from_node_uid),
+            ))
+        }
+    }
+}
+
+/// Returns true if a path of edges leads from `start` to `target`.
+fn reaches(graph: &Graph, start: u32, target: u32) -> bool {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(uid) = queue.pop_front() {
+        if uid == target {
+            return true;
+        }
+        for edge in graph.edges.iter().filter(|e| e.from_node_uid == uid) {
+            if seen.insert(edge.to_node_uid) {
+                queue.push_back(edge.to_node_uid);
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,81 @@
+//! # Graph Diff
+//!
+//! Compares two simulated [`Graph`]s, e.g. the architecture before and after
+//! a change, to support review.
+//!
+//! Nodes are matched by UID, which is derived from the function name (see the
+//! UID contract on [`Node`]). Edges are matched by source, target, carried
+//! token and relation.
+
+use crate::models::{Edge, EdgeRelation, Graph, Node};
+use std::collections::HashSet;
+
+/// Nodes and edges present in only one of two graphs.
+#[derive(Debug, Clone, Default)]
+pub struct GraphDiff {
+    /// Nodes only present in the new graph.
+    pub added_nodes: Vec<Node>,
+    /// Nodes only present in the old graph.
+    pub removed_nodes: Vec<Node>,
+    /// Edges only present in the new graph.
+    pub added_edges: Vec<Edge>,
+    /// Edges only present in the old graph.
+    pub removed_edges: Vec<Edge>,
+}
+
+impl GraphDiff {
+    /// Returns true if both graphs have the same nodes and edges.
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+/// Identity of an edge when comparing graphs.
+pub fn edge_key(edge: &Edge) -> (u32, u32, &str, &EdgeRelation) {
+    (
+        edge.from_node_uid,
+        edge.to_node_uid,
+        edge.token.kind.name(),
+        &edge.relation,
+    )
+}
+
+/// Computes what changed from `a` (old) to `b` (new).
+///
+/// Results keep the order in which nodes and edges appear in their graph.
+pub fn diff_graphs(a: &Graph, b: &Graph) -> GraphDiff {
+    let nodes_a: HashSet<u32> = a.nodes.iter().map(|n| n.uid).collect();
+    let nodes_b: HashSet<u32> = b.nodes.iter().map(|n| n.uid).collect();
+    let edges_a: HashSet<_> = a.edges.iter().map(edge_key).collect();
+    let edges_b: HashSet<_> = b.edges.iter().map(edge_key).collect();
+
+    GraphDiff {
+        added_nodes: b
+            .nodes
+            .iter()
+            .filter(|n| !nodes_a.contains(&n.uid))
+            .cloned()
+            .collect(),
+        removed_nodes: a
+            .nodes
+            .iter()
+            .filter(|n| !nodes_b.contains(&n.uid))
+            .cloned()
+            .collect(),
+        added_edges: b
+            .edges
+            .iter()
+            .filter(|e| !edges_a.contains(&edge_key(e)))
+            .cloned()
+            .collect(),
+        removed_edges: a
+            .edges
+            .iter()
+            .filter(|e| !edges_b.contains(&edge_key(e)))
+            .cloned()
+            .collect(),
+    }
+}
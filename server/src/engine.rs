@@ -7,14 +7,15 @@
 //! [DiagnosticWithContext] which the LSP layer later resolves to file ranges.
 
 use crate::models::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 use tower_lsp::lsp_types::DiagnosticSeverity;
 
 /// Result of a consumption attempt by a node.
 pub enum Consumed {
-    /// All required tokens were found and consumed. Returns the edges created.
-    AllTokens(Vec<Edge>),
+    /// All required tokens were found and consumed. Returns each satisfied
+    /// requirement with the edge created for it.
+    AllTokens(Vec<(Token, Edge)>),
     /// Some tokens were missing. Returns the list of missing tokens.
     SomeTokens(Vec<Token>),
 }
@@ -43,7 +44,7 @@ pub struct TokenPool {
     pub constants: Vec<Token>,
     pub token_to_origin_node: HashMap<u32, Arc<Node>>,
     pub expanded_nodes: HashSet<u32>,
-    pub constants_used: HashSet<u32>,
+    pub immutables_used: HashSet<u32>,
 }
 
 impl TokenPool {
@@ -70,7 +71,7 @@ impl TokenPool {
             constants,
             token_to_origin_node,
             expanded_nodes: HashSet::new(),
-            constants_used: HashSet::new(),
+            immutables_used: HashSet::new(),
         }
     }
 
@@ -78,13 +79,14 @@ impl TokenPool {
     ///
     /// If the producer node was already expanded (visited), the tokens are marked
     /// with `Cardinality::Collection` to represent that they might be produced multiple times
-    /// (e.g., in a loop).
+    /// (e.g., in a loop). Tokens inherit the group of their producer.
     pub fn produce(&mut self, tokens: Vec<Token>, producer: Arc<Node>) {
         let is_expanded = self.expanded_nodes.contains(&producer.uid);
         for mut token in tokens {
             if is_expanded {
                 token.cardinality = Cardinality::Collection;
             }
+            token.group = producer.function.group.clone();
             self.token_to_origin_node
                 .insert(token.uid, producer.clone());
             match &token.kind {
@@ -99,11 +101,13 @@ impl TokenPool {
     ///
     /// # Logic
     /// - Matches requirements against available tokens by Kind UID.
+    /// - Treats missing optional requirements as satisfied.
     /// - Checks for infinite loops/recursion (Unitary requirement satisfying Collection token).
     /// - Creates data flow edges for consumed tokens.
+    /// - Removes consumed mutable tokens from the pool (see [Kind::is_immutable]).
     ///
     /// # Returns
-    /// - `Consumed::AllTokens` with edges if successful.
+    /// - `Consumed::AllTokens` with `(requirement, edge)` pairs if successful.
     /// - `Consumed::SomeTokens` with missing requirements if failed.
     pub fn try_to_consume(&mut self, requirements: Vec<Token>, destination: Arc<Node>) -> Consumed {
         let mut edges = Vec::new();
@@ -117,37 +121,43 @@ impl TokenPool {
                 Kind::Constant(..) => &self.constants,
             };
 
-            // Match based on Kind (Artifact ID), ignoring the specific Token ID instance
             let matched = pool
                 .iter()
-                .find(|t| t.kind.uid() == req.kind.uid() && !consumed_in_step.contains(t))
+                .find(|t| Self::matches(req, t) && !consumed_in_step.contains(t))
                 .cloned();
 
             if let Some(t) = matched {
                 if let Some(origin) = self.token_to_origin_node.get(&t.uid) {
-                    if req.cardinality == Cardinality::Unitary
-                        && t.cardinality == Cardinality::Collection
-                    {
+                    let fans_out = req.cardinality == Cardinality::Unitary
+                        && t.cardinality == Cardinality::Collection;
+                    if fans_out {
                         trigger_expansion = true;
                     }
-                    edges.push(Edge {
-                        from_node_uid: origin.uid,
-                        to_node_uid: destination.uid,
-                        token: t.clone(),
-                        relation: EdgeRelation::DataFlow,
-                    });
+                    // The edge carries the requirement's optionality for rendering
+                    let mut carried = t.clone();
+                    carried.optional = req.optional;
+                    edges.push((
+                        req.clone(),
+                        Edge {
+                            from_node_uid: origin.uid,
+                            to_node_uid: destination.uid,
+                            token: carried,
+                            relation: if fans_out {
+                                EdgeRelation::FanOut
+                            } else {
+                                EdgeRelation::DataFlow
+                            },
+                        },
+                    ));
                     consumed_in_step.push(t);
                 }
             }
         }
 
+        // Optional requirements never cause starvation
         let missing: Vec<Token> = requirements
             .iter()
-            .filter(|req| {
-                !consumed_in_step
-                    .iter()
-                    .any(|c| c.kind.uid() == req.kind.uid())
-            })
+            .filter(|req| !req.optional && !consumed_in_step.iter().any(|c| Self::matches(req, c)))
             .cloned()
             .collect();
 
@@ -155,34 +165,72 @@ impl TokenPool {
             if trigger_expansion {
                 self.expanded_nodes.insert(destination.uid);
             }
-            for used in &consumed_in_step {
-                if matches!(used.kind, Kind::Constant(..)) {
-                    self.constants_used.insert(used.uid);
-                }
-            }
-            self.variables.retain(|t| !consumed_in_step.contains(t));
-            self.errors.retain(|t| !consumed_in_step.contains(t));
+            // Immutable tokens stay in the pool; only mutable ones are used up
+            let (immutable, mutable): (Vec<Token>, Vec<Token>) = consumed_in_step
+                .into_iter()
+                .partition(|t| t.kind.is_immutable());
+            self.immutables_used.extend(immutable.iter().map(|t| t.uid));
+            self.variables.retain(|t| !mutable.contains(t));
+            self.errors.retain(|t| !mutable.contains(t));
+            self.constants.retain(|t| !mutable.contains(t));
             Consumed::AllTokens(edges)
         } else {
             Consumed::SomeTokens(missing)
         }
     }
 
+    /// Whether `candidate` can satisfy the requirement `req`.
+    ///
+    /// Tokens match by Kind (artifact ID), ignoring the specific token instance,
+    /// its cardinality and its optionality. This is the single place deciding
+    /// what satisfies an input.
+    pub fn matches(req: &Token, candidate: &Token) -> bool {
+        candidate.kind.uid() == req.kind.uid()
+    }
+
     /// Returns a snapshot of unused tokens.
     pub fn get_leftover_tokens(&self) -> Leftovers {
         Leftovers {
-            variables: self.variables.clone(),
+            variables: self
+                .variables
+                .iter()
+                .filter(|v| !self.immutables_used.contains(&v.uid))
+                .cloned()
+                .collect(),
             errors: self.errors.clone(),
             constants: self
                 .constants
                 .iter()
-                .filter(|c| !self.constants_used.contains(&c.uid))
+                .filter(|c| !self.immutables_used.contains(&c.uid))
                 .cloned()
                 .collect(),
         }
     }
 }
 
+/// Options controlling a [Flow] simulation.
+#[derive(Debug, Clone)]
+pub struct FlowConfig {
+    /// Whether to remove duplicate edges in the final graph.
+    pub deduplicate_edges: bool,
+    /// Whether to order steps by data dependencies (see [Flow::simulate_auto]).
+    pub auto_order: bool,
+    /// Whether to keep function nodes left without edges that are not flow
+    /// steps. Declared steps are kept regardless, including starved ones, so
+    /// their diagnostics always point at a node.
+    pub keep_isolated: bool,
+}
+
+impl Default for FlowConfig {
+    fn default() -> Self {
+        Self {
+            deduplicate_edges: true,
+            auto_order: false,
+            keep_isolated: false,
+        }
+    }
+}
+
 /// Manages the full architectural flow simulation.
 pub struct Flow {
     /// Ordered list of nodes derived from the simulation.
@@ -191,25 +239,285 @@ pub struct Flow {
     pub edges: Vec<Edge>,
     /// Active token pools (representing parallel branches of execution).
     pub pools: Vec<TokenPool>,
-    /// Whether to remove duplicate edges in the final graph.
-    pub deduplicate_edges: bool,
+    /// Simulation options.
+    pub config: FlowConfig,
     /// Diagnostics collected during simulation (e.g., flow errors).
     pub diagnostics: Vec<DiagnosticWithContext>,
+    /// Names of the functions whose step fired at least once.
+    pub executed: HashSet<String>,
 }
 
 impl Flow {
+    /// Creates a simulation with default options, except for edge deduplication.
+    ///
+    /// Shorthand kept for tests; other callers use [Flow::with_config].
+    #[cfg(test)]
     pub fn new(deduplicate_edges: bool) -> Self {
+        Self::with_config(FlowConfig {
+            deduplicate_edges,
+            ..FlowConfig::default()
+        })
+    }
+
+    /// Creates a simulation with the given options.
+    pub fn with_config(config: FlowConfig) -> Self {
         Self {
             nodes: Vec::new(),
             edges: Vec::new(),
             pools: Vec::new(),
-            deduplicate_edges,
+            config,
             diagnostics: Vec::new(),
+            executed: HashSet::new(),
         }
     }
 
-    /// Simulates the flow based on the global program structure.
+    /// Names of the functions taking part in the simulated flow: those whose
+    /// step fired, even with an empty contract, or whose node consumed or
+    /// produced at least one token.
+    ///
+    /// Call after one of the `simulate` methods; the complement within the
+    /// catalog is the set of functions the flow never reaches.
+    pub fn reachable_functions(&self) -> HashSet<String> {
+        let connected: HashSet<u32> = self
+            .edges
+            .iter()
+            .flat_map(|e| [e.from_node_uid, e.to_node_uid])
+            .collect();
+        self.nodes
+            .iter()
+            .filter(|n| {
+                !n.is_artificial_graph_start
+                    && !n.is_artificial_graph_end
+                    && !n.is_artificial_error_termination
+                    && connected.contains(&n.uid)
+            })
+            .map(|n| n.function.name.clone())
+            .chain(self.executed.iter().cloned())
+            .collect()
+    }
+
+    /// Simulates the default flow of the program (see [ProgramStructure::default_flow]).
     pub fn simulate(&mut self, structure: &ProgramStructure) -> Graph {
+        if self.config.auto_order {
+            self.simulate_auto(structure)
+        } else {
+            self.simulate_steps(structure, structure.default_flow())
+        }
+    }
+
+    /// Simulates an explicit sequence of steps (e.g. a named flow),
+    /// honoring [FlowConfig::auto_order].
+    pub fn simulate_flow(&mut self, structure: &ProgramStructure, steps: &[FlowStep]) -> Graph {
+        if self.config.auto_order {
+            self.simulate_steps_auto(structure, steps)
+        } else {
+            self.simulate_steps(structure, steps)
+        }
+    }
+
+    /// Simulates the flow after ordering its steps by data dependencies.
+    ///
+    /// Producers run before their consumers regardless of the order in which
+    /// the steps were written. Steps without a dependency between them keep
+    /// their authoring order. A dependency cycle is entered at a step whose
+    /// inputs are already in the initial pool (see [Flow::initial_tokens]);
+    /// only when no step of the cycle can fire is an error reported, and the
+    /// remaining steps are simulated in authoring order.
+    pub fn simulate_auto(&mut self, structure: &ProgramStructure) -> Graph {
+        self.simulate_steps_auto(structure, structure.default_flow())
+    }
+
+    /// Like [Flow::simulate_auto], for an explicit sequence of steps (e.g. a named flow).
+    pub fn simulate_steps_auto(
+        &mut self,
+        structure: &ProgramStructure,
+        steps: &[FlowStep],
+    ) -> Graph {
+        let ordered = self.order_steps(structure, steps);
+        self.simulate_steps(structure, &ordered)
+    }
+
+    /// Topologically sorts the flow steps (Kahn's algorithm, stable on authoring order).
+    fn order_steps(&mut self, structure: &ProgramStructure, steps: &[FlowStep]) -> Vec<FlowStep> {
+        let functions: Vec<Option<&Arc<Function>>> = steps
+            .iter()
+            .map(|step| structure.catalog.get(&step.function_name))
+            .collect();
+
+        let produced: Vec<HashSet<u32>> = functions
+            .iter()
+            .map(|func| {
+                func.map(|f| f.produces.iter().flatten().map(|t| t.kind.uid()).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        // dependencies[i] holds the steps that must run before step i.
+        // Repeated invocations of the same function never depend on each other.
+        let dependencies: Vec<HashSet<usize>> = functions
+            .iter()
+            .map(|func| {
+                let Some(func) = func else {
+                    return HashSet::new();
+                };
+                (0..functions.len())
+                    .filter(|&j| {
+                        functions[j].is_some_and(|other| other.name != func.name)
+                            && func
+                                .consumes
+                                .iter()
+                                .any(|t| produced[j].contains(&t.kind.uid()))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Tokens available before any step runs, used to enter cycles
+        let mut available: HashSet<u32> = Self::initial_tokens(structure, steps)
+            .iter()
+            .map(|t| t.kind.uid())
+            .collect();
+        let can_fire = |i: usize, available: &HashSet<u32>| {
+            functions[i].is_some_and(|func| {
+                func.consumes
+                    .iter()
+                    .all(|t| t.optional || available.contains(&t.kind.uid()))
+            })
+        };
+
+        let mut placed = vec![false; steps.len()];
+        let mut order = Vec::with_capacity(steps.len());
+
+        loop {
+            let ready = (0..steps.len())
+                .find(|&i| !placed[i] && dependencies[i].iter().all(|&j| placed[j]))
+                .or_else(|| (0..steps.len()).find(|&i| !placed[i] && can_fire(i, &available)));
+            let Some(next) = ready else {
+                break;
+            };
+            placed[next] = true;
+            available.extend(&produced[next]);
+            order.push(steps[next].clone());
+        }
+
+        let remaining: Vec<&FlowStep> = steps
+            .iter()
+            .zip(&placed)
+            .filter(|(_, &done)| !done)
+            .map(|(step, _)| step)
+            .collect();
+
+        if let Some(first) = remaining.first() {
+            let mut names: Vec<&str> = remaining
+                .iter()
+                .map(|step| step.function_name.as_str())
+                .collect();
+            names.dedup();
+            self.diagnostics.push(DiagnosticWithContext {
+                file_id: first.span.file_id,
+                span: Some(first.span),
+                message: format!(
+                    "Flow Error: No valid execution order exists. Cyclic dependency between [{}]",
+                    names.join(", ")
+                ),
+                severity: DiagnosticSeverity::ERROR,
+                tags: vec![],
+                node_uid: structure.catalog.get(&first.function_name).map(|f| f.uid),
+                code: None,
+            });
+            order.extend(remaining.into_iter().cloned());
+        }
+
+        order
+    }
+
+    /// Tokens consumed by some function but produced by none: the inputs the
+    /// architecture expects from outside, attributed to the initial node.
+    ///
+    /// Optional inputs and errors are left out, as nothing external provides them.
+    /// Tokens are ordered by consumer name, one per kind and cardinality.
+    pub fn required_external_inputs(structure: &ProgramStructure) -> Vec<Token> {
+        let produced: HashSet<u32> = structure
+            .catalog
+            .values()
+            .flat_map(|f| f.produces.iter().flatten())
+            .map(|t| t.kind.uid())
+            .collect();
+
+        let mut functions: Vec<_> = structure.catalog.values().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut seen = HashSet::new();
+        functions
+            .into_iter()
+            .flat_map(|f| f.consumes.iter())
+            .filter(|t| {
+                !t.optional
+                    && !matches!(t.kind, Kind::Error(_))
+                    && !produced.contains(&t.kind.uid())
+                    && seen.insert((t.kind.uid(), t.cardinality))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Tokens seeded into the initial pool before `steps` run.
+    ///
+    /// External inputs can be needed anywhere in the flow; the inputs of the
+    /// first step of each file are seeded too, as it may close a loop
+    /// (consume what a later step produces).
+    pub fn initial_tokens(structure: &ProgramStructure, steps: &[FlowStep]) -> Vec<Token> {
+        let mut initial_tokens = Self::required_external_inputs(structure);
+        let mut seen_files = HashSet::new();
+        for step in steps {
+            if seen_files.insert(step.span.file_id) {
+                if let Some(func) = structure.catalog.get(&step.function_name) {
+                    for token in func.consumes.iter().filter(|t| !t.optional) {
+                        let seeded = initial_tokens.iter().any(|t| {
+                            t.kind.uid() == token.kind.uid() && t.cardinality == token.cardinality
+                        });
+                        if !seeded {
+                            initial_tokens.push(token.clone());
+                        }
+                    }
+                }
+            }
+        }
+        initial_tokens
+    }
+
+    /// Returns the node of a step, creating it on the function's first invocation.
+    ///
+    /// Repeated invocations of a function share one node (node UIDs are the
+    /// function UID, see [Node]), so their edges meet there. The docs of every
+    /// documented invocation are kept, in flow order, separated by a blank line.
+    fn node_for_step(&mut self, func: &Arc<Function>, step: &FlowStep) -> Arc<Node> {
+        let Some(index) = self.nodes.iter().position(|n| n.uid == func.uid) else {
+            let mut node = Node::new(func.clone());
+            node.documentation = step.documentation.clone();
+            let node = Arc::new(node);
+            self.nodes.push(node.clone());
+            return node;
+        };
+        if let Some(docs) = &step.documentation {
+            let node = Arc::make_mut(&mut self.nodes[index]);
+            match &mut node.documentation {
+                Some(existing) if existing.split("\n\n").any(|d| d == docs) => {}
+                Some(existing) => {
+                    existing.push_str("\n\n");
+                    existing.push_str(docs);
+                }
+                None => node.documentation = Some(docs.clone()),
+            }
+        }
+        self.nodes[index].clone()
+    }
+
+    /// Simulates an explicit sequence of flow steps (e.g. a named flow).
+    ///
+    /// Each step invokes its function once per active token pool; a function
+    /// listed several times is drawn as a single node (see [Flow::node_for_step]).
+    pub fn simulate_steps(&mut self, structure: &ProgramStructure, steps: &[FlowStep]) -> Graph {
         // Prepare artificial nodes (but do not add them to graph yet)
         let initial_node = Arc::new(Node::new_artificial(
             "InitialNode".to_string(),
@@ -233,41 +541,50 @@ impl Flow {
         ));
 
         // 1. Seed Initial Pool
-        let mut initial_tokens = Vec::new();
-        let mut seen_files = HashSet::new();
-
-        for step in &structure.flow {
-            if seen_files.insert(step.span.file_id) {
-                if let Some(func) = structure.catalog.get(&step.function_name) {
-                    initial_tokens.extend(func.consumes.clone());
-                }
-            }
-        }
-
+        let initial_tokens = Self::initial_tokens(structure, steps);
         self.pools
             .push(TokenPool::new(initial_tokens, initial_node.clone()));
 
         // 2. Simulation Loop
-        for step in &structure.flow {
+        let mut has_terminal_flow = false;
+        for step in steps {
             let Some(func) = structure.catalog.get(&step.function_name) else {
                 continue;
             };
-            let node = Arc::new(Node::new(func.clone()));
-            self.nodes.push(node.clone());
+            let node = self.node_for_step(func, step);
 
             let mut next_pools = Vec::new();
             let mut step_executed_at_least_once = false;
-            let mut missing_tokens_examples = HashSet::new();
+            // Sorted so the diagnostic text is identical across runs
+            let mut missing_tokens_examples = BTreeSet::new();
 
             for pool in &mut self.pools {
                 match pool.try_to_consume(func.consumes.clone(), node.clone()) {
-                    Consumed::AllTokens(new_edges) => {
+                    Consumed::AllTokens(consumed) => {
                         step_executed_at_least_once = true;
-                        self.edges.extend(new_edges);
+                        self.edges
+                            .extend(consumed.iter().map(|(_, edge)| edge.clone()));
                         if func.produces.is_empty() {
+                            // Side-effecting sink: the inputs end at this node
+                            // (exporters draw it with a sink shape)
                             next_pools.push(pool.clone());
                         } else {
                             for branch in &func.produces {
+                                if branch.is_empty() {
+                                    // Explicit `> None`: the consumed inputs end the flow here
+                                    for (_, edge) in &consumed {
+                                        if edge.token.kind.is_immutable() {
+                                            continue;
+                                        }
+                                        self.edges.push(Edge {
+                                            from_node_uid: node.uid,
+                                            to_node_uid: final_node.uid,
+                                            token: edge.token.clone(),
+                                            relation: EdgeRelation::TerminalFlow,
+                                        });
+                                        has_terminal_flow = true;
+                                    }
+                                }
                                 let mut branched_pool = pool.clone();
                                 branched_pool.produce(branch.clone(), node.clone());
                                 next_pools.push(branched_pool);
@@ -283,7 +600,9 @@ impl Flow {
                 }
             }
 
-            if !step_executed_at_least_once && !func.consumes.is_empty() {
+            if step_executed_at_least_once {
+                self.executed.insert(func.name.clone());
+            } else if !func.consumes.is_empty() {
                 let missing_list: Vec<String> = missing_tokens_examples.into_iter().collect();
                 let msg = format!(
                     "Flow Error: Function '{}' could not execute. Missing inputs: [{}]",
@@ -297,6 +616,8 @@ impl Flow {
                     message: msg,
                     severity: DiagnosticSeverity::ERROR, // STRICT MODE: Starvation is fatal
                     tags: vec![],
+                    node_uid: Some(func.uid),
+                    code: None,
                 });
             }
 
@@ -304,12 +625,27 @@ impl Flow {
         }
 
         // 3. Process Leftovers and Lazily Add Boundary Nodes
-        let mut has_terminal_flow = false;
         let mut has_error_flow = false;
+        let mut unused_constants = Vec::new();
+
+        // A constant counts as used if any branch consumed it
+        let used_anywhere: HashSet<u32> = self
+            .pools
+            .iter()
+            .flat_map(|p| p.immutables_used.iter().copied())
+            .collect();
 
         for pool in &self.pools {
             let leftovers = pool.get_leftover_tokens();
 
+            for constant in &leftovers.constants {
+                if let Some(origin) = pool.token_to_origin_node.get(&constant.uid) {
+                    if origin.uid != initial_node.uid && !used_anywhere.contains(&constant.uid) {
+                        unused_constants.push((constant.clone(), origin.clone()));
+                    }
+                }
+            }
+
             // Check for valid leftovers (FinalNode)
             for token in leftovers.variables.into_iter().chain(leftovers.constants) {
                 if let Some(origin) = pool.token_to_origin_node.get(&token.uid) {
@@ -323,36 +659,72 @@ impl Flow {
                 }
             }
 
-            // Check for error leftovers (FatalErrors)
+            // Check for error leftovers (FatalErrors), except expected `@terminal` ones
             for err in leftovers.errors {
-                if let Some(origin) = pool.token_to_origin_node.get(&err.uid) {
-                    has_error_flow = true;
+                let Some(origin) = pool.token_to_origin_node.get(&err.uid) else {
+                    continue;
+                };
+                if err.kind.is_terminal_error() {
+                    has_terminal_flow = true;
                     self.edges.push(Edge {
                         from_node_uid: origin.uid,
-                        to_node_uid: fatal_node.uid,
+                        to_node_uid: final_node.uid,
                         token: err.clone(),
-                        relation: EdgeRelation::ErrorFlow,
+                        relation: EdgeRelation::TerminalFlow,
                     });
+                    continue;
+                }
+                has_error_flow = true;
+                self.edges.push(Edge {
+                    from_node_uid: origin.uid,
+                    to_node_uid: fatal_node.uid,
+                    token: err.clone(),
+                    relation: EdgeRelation::ErrorFlow,
+                });
 
-                    // STRICT MODE: Unhandled errors are warnings.
-                    // Locate the function definition that produced this error to attach the warning.
-                    if let Some(meta) = structure.symbol_table.get(&origin.function.uid) {
-                        self.diagnostics.push(DiagnosticWithContext {
-                            file_id: meta.definition_span.file_id,
-                            span: Some(meta.definition_span),
-                            message: format!(
-                                "Unhandled Error: '{}' is produced by '{}' but never consumed (rescued).",
-                                err.kind.name(),
-                                origin.function.name
-                            ),
-                            severity: DiagnosticSeverity::WARNING,
-                            tags: vec![],
-                        });
-                    }
+                // STRICT MODE: Unhandled errors are warnings.
+                // Locate the function definition that produced this error to attach the warning.
+                if let Some(meta) = structure.symbol_table.get(&origin.function.uid) {
+                    self.diagnostics.push(DiagnosticWithContext {
+                        file_id: meta.definition_span.file_id,
+                        span: Some(meta.definition_span),
+                        message: format!(
+                            "Unhandled Error: '{}' is produced by '{}' but never consumed (rescued).",
+                            err.kind.name(),
+                            origin.function.name
+                        ),
+                        severity: DiagnosticSeverity::WARNING,
+                        tags: vec![],
+                        node_uid: Some(origin.uid),
+                        code: Some("unhandled-error"),
+                    });
                 }
             }
         }
 
+        // Constants loaded by a function but never read are likely a mistake
+        let mut reported = HashSet::new();
+        for (constant, origin) in unused_constants {
+            if !reported.insert(constant.uid) {
+                continue;
+            }
+            if let Some(meta) = structure.symbol_table.get(&origin.function.uid) {
+                self.diagnostics.push(DiagnosticWithContext {
+                    file_id: meta.definition_span.file_id,
+                    span: Some(meta.definition_span),
+                    message: format!(
+                        "Unused Constant: '{}' is produced by '{}' but never consumed.",
+                        constant.kind.name(),
+                        origin.function.name
+                    ),
+                    severity: DiagnosticSeverity::INFORMATION,
+                    tags: vec![],
+                    node_uid: Some(origin.uid),
+                    code: Some("unused-constant"),
+                });
+            }
+        }
+
         // 4. Finalize Nodes List
         // Add InitialNode only if it has outgoing edges
         if self
@@ -374,15 +746,44 @@ impl Flow {
             self.nodes.push(fatal_node.clone());
         }
 
-        if self.deduplicate_edges {
+        if self.config.deduplicate_edges {
             let mut seen = HashSet::new();
             self.edges
                 .retain(|e| seen.insert((e.from_node_uid, e.to_node_uid, e.token.uid)));
         }
 
+        // Drop stray boxes: nodes without edges that no step declared
+        if !self.config.keep_isolated {
+            let connected: HashSet<u32> = self
+                .edges
+                .iter()
+                .flat_map(|e| [e.from_node_uid, e.to_node_uid])
+                .collect();
+            let declared: HashSet<u32> = steps
+                .iter()
+                .filter_map(|step| structure.catalog.get(&step.function_name))
+                .map(|func| func.uid)
+                .collect();
+            self.nodes
+                .retain(|n| connected.contains(&n.uid) || declared.contains(&n.uid));
+        }
+
+        // Diagnostics only link to nodes present in the returned graph
+        let present: HashSet<u32> = self.nodes.iter().map(|n| n.uid).collect();
+        for diagnostic in &mut self.diagnostics {
+            if diagnostic
+                .node_uid
+                .is_some_and(|uid| !present.contains(&uid))
+            {
+                diagnostic.node_uid = None;
+            }
+        }
+
         Graph {
             nodes: self.nodes.iter().map(|n| (**n).clone()).collect(),
             edges: self.edges.clone(),
+            title: structure.title.clone(),
+            author: structure.author.clone(),
         }
     }
 }
@@ -0,0 +1,89 @@
+//! # Data Dictionary Exporter
+//!
+//! Lists the vocabulary of the system: every artifact with its kind, its
+//! documentation and the functions producing and consuming it, as a
+//! Markdown table.
+
+use crate::models::{Graph, Kind, ProgramStructure};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+/// Generates a Markdown table of every artifact, sorted by name.
+///
+/// Producers and consumers are read from the simulated graph, so artifacts
+/// only used by functions outside the flow list no functions.
+pub fn export(structure: &ProgramStructure, graph: &Graph) -> String {
+    let names: HashMap<u32, &str> = graph
+        .nodes
+        .iter()
+        .filter(|n| {
+            !n.is_artificial_graph_start
+                && !n.is_artificial_graph_end
+                && !n.is_artificial_error_termination
+        })
+        .map(|n| (n.uid, n.function.name.as_str()))
+        .collect();
+
+    // Artifact UID -> (producers, consumers)
+    let mut usage: HashMap<u32, (BTreeSet<&str>, BTreeSet<&str>)> = HashMap::new();
+    for edge in &graph.edges {
+        let entry = usage.entry(edge.token.kind.uid()).or_default();
+        if let Some(name) = names.get(&edge.from_node_uid) {
+            entry.0.insert(name);
+        }
+        if let Some(name) = names.get(&edge.to_node_uid) {
+            entry.1.insert(name);
+        }
+    }
+
+    let mut out = String::new();
+    match &graph.title {
+        Some(title) => writeln!(out, "# Data Dictionary: {}\n", escape_cell(title)).unwrap(),
+        None => writeln!(out, "# Data Dictionary\n").unwrap(),
+    }
+    writeln!(
+        out,
+        "| Name | Kind | Description | Produced by | Consumed by |"
+    )
+    .unwrap();
+    writeln!(out, "|---|---|---|---|---|").unwrap();
+
+    let mut artifacts: Vec<&Kind> = structure.artifacts.values().collect();
+    artifacts.sort_by(|a, b| a.name().cmp(b.name()));
+    for kind in artifacts {
+        let keyword = match kind {
+            Kind::Constant(_) => "constant",
+            Kind::Variable(_) => "variable",
+            Kind::Error(_) => "error",
+        };
+        let (producers, consumers) = usage.remove(&kind.uid()).unwrap_or_default();
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            escape_cell(kind.name()),
+            keyword,
+            kind.docs().map(escape_cell).unwrap_or_default(),
+            list(&producers),
+            list(&consumers)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Comma-separated function names, or a dash when there are none.
+fn list(names: &BTreeSet<&str>) -> String {
+    if names.is_empty() {
+        return "—".to_string();
+    }
+    names
+        .iter()
+        .map(|n| escape_cell(n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Keeps text inside its table cell: pipes are escaped and line breaks become `<br>`.
+fn escape_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', "<br>")
+}
@@ -1,26 +1,59 @@
 //! # Graphviz (DOT) Exporter
 
-use super::theme::{Shape, Theme};
-use crate::models::{EdgeRelation, Graph};
-use std::collections::HashMap;
+use super::theme::{self, Shape, Theme};
+use super::{sanitize_id, ErrorLayout, ExportOptions};
+use crate::models::{EdgeRelation, Function, Graph, Kind, ProgramStructure};
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
+use std::sync::Arc;
+
+/// Graphviz graph attributes constraining the layout, e.g. to fit a page.
+///
+/// Unset attributes are not written, leaving Graphviz defaults in place.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct DotLayout {
+    /// Aspect ratio of the drawing: a number (height / width), or fill, compress, expand, auto
+    #[arg(long, value_name = "RATIO")]
+    pub ratio: Option<String>,
+
+    /// Maximum drawing size in inches, as "W,H" (e.g. "6.5,9" for a letter page)
+    #[arg(long, value_name = "W,H")]
+    pub size: Option<String>,
+
+    /// Minimum space between nodes of the same rank, in inches
+    #[arg(long, value_name = "INCHES")]
+    pub nodesep: Option<f32>,
+
+    /// Minimum space between ranks, in inches
+    #[arg(long, value_name = "INCHES")]
+    pub ranksep: Option<f32>,
+}
+
+/// Label typography used unless [`super::theme::ThemeConfig`] sets it.
+const DEFAULT_FONT: &str = "Helvetica";
+const DEFAULT_NODE_FONT_SIZE: u32 = 10;
+const DEFAULT_EDGE_FONT_SIZE: u32 = 9;
 
 /// Generates a Graphviz DOT representation of the graph.
 ///
 /// This produces a standard `.dot` string with clusters for groups, styled nodes for functions,
 /// and edges for data/control flow.
-pub fn export(graph: &Graph) -> String {
+pub fn export(graph: &Graph, options: &ExportOptions) -> String {
     let mut out = String::new();
+    let degrees = options.degrees(graph);
+    let highlighted = options.highlighted(graph);
 
-    writeln!(out, "digraph Tect {{").unwrap();
-    writeln!(out, "    layout=dot;").unwrap();
-    writeln!(out, "    rankdir=TD;").unwrap();
-    writeln!(out, "    node [fontname=\"Helvetica\", fontsize=10];").unwrap();
-    writeln!(out, "    edge [fontname=\"Helvetica\", fontsize=9];").unwrap();
+    write_header(&mut out, options, &options.caption(graph));
 
     let mut groups: HashMap<Option<String>, Vec<&crate::models::Node>> = HashMap::new();
     for node in &graph.nodes {
-        let group_name = node.function.group.as_ref().map(|g| g.name.clone());
+        // A collapsed group is its own box, not a cluster
+        let group_name = node
+            .function
+            .group
+            .as_ref()
+            .filter(|_| node.members.is_empty())
+            .map(|g| g.name.clone());
         groups.entry(group_name).or_default().push(node);
     }
 
@@ -36,14 +69,31 @@ pub fn export(graph: &Graph) -> String {
         }
 
         for node in nodes {
-            let style = Theme::get_node_style(node);
+            let mut style = Theme::get_node_style(node);
+            if highlighted
+                .as_ref()
+                .is_some_and(|h| !h.nodes.contains(&node.uid))
+            {
+                style.dim();
+            }
             let shape_str = match style.shape {
                 Shape::Box => "box",
                 Shape::Rounded => "rect, style=\"rounded,filled\"",
                 Shape::Diamond => "diamond",
+                Shape::Sink => "box, peripheries=2",
             };
 
-            let label = format!("<<B>{}</B>>", escape_html(&node.function.name));
+            // Collapsed groups list their member functions below the name
+            let members: String = node
+                .members
+                .iter()
+                .map(|m| format!("<BR/><FONT POINT-SIZE=\"8\">{}</FONT>", escape_html(m)))
+                .collect();
+            let label = format!(
+                "<<B>{}</B>{}>",
+                escape_html(&options.node_label(node, degrees.as_ref())),
+                members
+            );
 
             let style_attr = if shape_str.contains("style=") {
                 ""
@@ -51,10 +101,24 @@ pub fn export(graph: &Graph) -> String {
                 ", style=filled"
             };
 
+            // Step docs, then function docs, then the full name of a truncated label
+            // become hover tooltips in SVG output
+            let tooltip = node
+                .documentation
+                .clone()
+                .or_else(|| node.function.documentation.clone())
+                .or_else(|| {
+                    let full = &node.function.name;
+                    (options.label(full) != *full).then(|| full.clone())
+                });
+            let tooltip_attr = tooltip
+                .map(|text| format!(", tooltip=\"{}\"", escape_dot_string(&text)))
+                .unwrap_or_default();
+
             // Apply style from theme
             writeln!(
                 out,
-                "        N_{} [label={}, shape={}, fillcolor=\"{}\", color=\"{}\", penwidth={}, fontcolor=\"{}\"{}];",
+                "        N_{} [label={}, shape={}, fillcolor=\"{}\", color=\"{}\", penwidth={}, fontcolor=\"{}\"{}{}];",
                 node.uid,
                 label,
                 shape_str,
@@ -62,7 +126,8 @@ pub fn export(graph: &Graph) -> String {
                 style.border, // Group Color
                 style.stroke_width, // Thick stroke for groups
                 style.text,
-                style_attr
+                style_attr,
+                tooltip_attr
             )
             .unwrap();
         }
@@ -72,35 +137,190 @@ pub fn export(graph: &Graph) -> String {
         }
     }
 
-    for edge in &graph.edges {
-        let (color, _) = Theme::get_token_color(&edge.token.kind);
+    for (i, edge) in graph.edges.iter().enumerate() {
+        let (mut color, _) = Theme::get_edge_color(&edge.token, options.color_edges_by_group);
+        // Highlighted edges are thick and red, the others greyed out
+        let mut emphasis = "";
+        if let Some(h) = &highlighted {
+            if h.edges.contains(&i) {
+                color = theme::HIGHLIGHT_COLOR.into();
+                emphasis = ", penwidth=2.5";
+            } else {
+                color = theme::DIMMED_COLOR.into();
+            }
+        }
         let style = match edge.relation {
             EdgeRelation::ControlFlow => "dashed",
             EdgeRelation::ErrorFlow => "dotted",
+            _ if edge.token.optional => "dashed",
             _ => "solid",
         };
 
+        // Fan-out edges get a double arrowhead to mark the "for each";
+        // returning errors point back at their producer
+        let arrowhead = match edge.relation {
+            EdgeRelation::FanOut => ", arrowhead=\"normalnormal\"",
+            EdgeRelation::ErrorFlow if options.error_layout == ErrorLayout::Return => {
+                ", dir=\"back\", arrowtail=\"inv\", constraint=false"
+            }
+            _ => "",
+        };
+
+        let name = edge.token.kind.name();
+        let label = options.label(name);
+        // Token docs, or the full name of a truncated label
+        let tooltip = edge
+            .token
+            .kind
+            .docs()
+            .or((label != name).then_some(name))
+            .map(|text| format!(", edgetooltip=\"{}\"", escape_dot_string(text)))
+            .unwrap_or_default();
+
         writeln!(
             out,
-            "    N_{} -> N_{} [label=\"{}\", color=\"{}\", style=\"{}\"];",
+            "    N_{} -> N_{} [label=\"{}\", color=\"{}\", style=\"{}\"{}{}{}];",
             edge.from_node_uid,
             edge.to_node_uid,
-            edge.token.kind.name(),
+            escape_dot_string(&label),
             color,
-            style
+            style,
+            emphasis,
+            arrowhead,
+            tooltip
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Generates the data-type dependency graph in DOT: one node per artifact,
+/// and an edge from each consumed artifact to each artifact the consuming
+/// function produces, labeled with that function.
+///
+/// Contracts come from the definitions, so functions missing from the flow
+/// still contribute their edges. Edges leading to errors are dotted.
+pub fn export_types(structure: &ProgramStructure, options: &ExportOptions) -> String {
+    let mut out = String::new();
+    write_header(
+        &mut out,
+        options,
+        &options.caption_for(structure.title.as_deref(), structure.author.as_deref()),
+    );
+
+    let mut artifacts: Vec<&Kind> = structure.artifacts.values().collect();
+    artifacts.sort_by(|a, b| a.name().cmp(b.name()));
+    for kind in artifacts {
+        let tooltip = kind
+            .docs()
+            .map(|text| format!(", tooltip=\"{}\"", escape_dot_string(text)))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "    T_{} [label=<<B>{}</B>>, shape=box, style=filled, fillcolor=\"{}\", color=\"{}\", fontcolor=\"#ffffff\"{}];",
+            kind.uid(),
+            escape_html(&options.label(kind.name())),
+            Theme::get_token_color(kind).0,
+            theme::FUNCTION_BORDER,
+            tooltip
         )
         .unwrap();
     }
 
+    let mut functions: Vec<&Arc<Function>> = structure.catalog.values().collect();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut seen = HashSet::new();
+    for function in functions {
+        let color = match &function.group {
+            Some(group) if options.color_edges_by_group => Theme::get_group_color(&group.name).0,
+            _ => theme::VARIABLE_COLOR.to_string(),
+        };
+        let tooltip = function
+            .documentation
+            .as_deref()
+            .map(|text| format!(", edgetooltip=\"{}\"", escape_dot_string(text)))
+            .unwrap_or_default();
+        for input in &function.consumes {
+            for output in function.produces.iter().flatten() {
+                let (from, to) = (input.kind.uid(), output.kind.uid());
+                if !seen.insert((from, to, function.uid)) {
+                    continue;
+                }
+                let style = match output.kind {
+                    Kind::Error(_) => "dotted",
+                    _ => "solid",
+                };
+                writeln!(
+                    out,
+                    "    T_{} -> T_{} [label=\"{}\", color=\"{}\", style=\"{}\"{}];",
+                    from,
+                    to,
+                    escape_dot_string(&options.label(&function.name)),
+                    color,
+                    style,
+                    tooltip
+                )
+                .unwrap();
+            }
+        }
+    }
+
     writeln!(out, "}}").unwrap();
     out
 }
 
-/// Sanitize string for use as a DOT identifier.
-fn sanitize_id(s: &str) -> String {
-    s.chars()
-        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
-        .collect()
+/// Opens the digraph and writes the graph-wide attributes: fonts, layout
+/// constraints and the caption.
+fn write_header(out: &mut String, options: &ExportOptions, caption: &[String]) {
+    writeln!(out, "digraph Tect {{").unwrap();
+    writeln!(out, "    layout=dot;").unwrap();
+    writeln!(out, "    rankdir=TD;").unwrap();
+    let theme = &options.theme;
+    let font = escape_dot_string(theme.font_family.as_deref().unwrap_or(DEFAULT_FONT));
+    // Cluster and caption labels only change font when one is configured
+    if theme.font_family.is_some() {
+        writeln!(out, "    fontname=\"{}\";", font).unwrap();
+    }
+    writeln!(
+        out,
+        "    node [fontname=\"{}\", fontsize={}];",
+        font,
+        theme.node_font_size.unwrap_or(DEFAULT_NODE_FONT_SIZE)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    edge [fontname=\"{}\", fontsize={}];",
+        font,
+        theme.edge_font_size.unwrap_or(DEFAULT_EDGE_FONT_SIZE)
+    )
+    .unwrap();
+
+    let layout = &options.dot_layout;
+    if let Some(ratio) = &layout.ratio {
+        writeln!(out, "    ratio=\"{}\";", escape_dot_string(ratio)).unwrap();
+    }
+    if let Some(size) = &layout.size {
+        writeln!(out, "    size=\"{}\";", escape_dot_string(size)).unwrap();
+    }
+    if let Some(nodesep) = layout.nodesep {
+        writeln!(out, "    nodesep={};", nodesep).unwrap();
+    }
+    if let Some(ranksep) = layout.ranksep {
+        writeln!(out, "    ranksep={};", ranksep).unwrap();
+    }
+
+    if !caption.is_empty() {
+        writeln!(
+            out,
+            "    label=\"{}\";",
+            escape_dot_string(&caption.join("\n"))
+        )
+        .unwrap();
+        writeln!(out, "    labelloc=t;").unwrap();
+    }
 }
 
 /// Escapes HTML special characters for HTML-like labels in DOT.
@@ -109,3 +329,10 @@ fn escape_html(s: &str) -> String {
         .replace('<', "&lt;")
         .replace('>', "&gt;")
 }
+
+/// Escapes a string for use inside a quoted DOT attribute.
+fn escape_dot_string(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
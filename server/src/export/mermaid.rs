@@ -1,48 +1,89 @@
 //! # Mermaid.js Exporter
 
-use super::theme::{Shape, Theme, GROUP_PALETTE};
-use crate::models::{EdgeRelation, Graph};
-use std::collections::HashMap;
+use super::theme::{self, Shape, Theme, GROUP_PALETTE};
+use super::{sanitize_id, ExportOptions};
+use crate::diff::{diff_graphs, edge_key};
+use crate::models::{Cardinality, Edge, EdgeRelation, Graph};
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 
-pub fn export(graph: &Graph) -> String {
+pub fn export(graph: &Graph, options: &ExportOptions) -> String {
     let mut out = String::new();
 
+    // Mermaid titles are one line, so the author is left out
+    let caption: Vec<String> = graph
+        .title
+        .iter()
+        .cloned()
+        .chain(
+            options
+                .timestamp
+                .iter()
+                .map(|ts| format!("Generated {}", ts)),
+        )
+        .collect();
+    let theme = &options.theme;
+    let configured = theme.font_family.is_some() || theme.node_font_size.is_some();
+    if !caption.is_empty() || configured {
+        writeln!(out, "---").unwrap();
+    }
+    if !caption.is_empty() {
+        let title = caption.join(" · ").replace('"', "#quot;");
+        writeln!(out, "title: \"{}\"", title).unwrap();
+    }
+    if configured {
+        writeln!(out, "config:\n  themeVariables:").unwrap();
+        if let Some(family) = &theme.font_family {
+            writeln!(out, "    fontFamily: \"{}\"", family.replace('"', "")).unwrap();
+        }
+        // Mermaid has a single font size; edge labels use the node size
+        if let Some(size) = theme.node_font_size {
+            writeln!(out, "    fontSize: \"{}px\"", size).unwrap();
+        }
+    }
+    if !caption.is_empty() || configured {
+        writeln!(out, "---").unwrap();
+    }
     writeln!(out, "flowchart TD").unwrap();
 
-    // Define Base Classes
-    writeln!(
-        out,
-        "    classDef default fill:#1e293b,stroke:#334155,color:#fff;"
-    )
-    .unwrap();
-    writeln!(
-        out,
-        "    classDef startend fill:#059669,stroke:#047857,color:#fff;"
-    )
-    .unwrap();
-    writeln!(
-        out,
-        "    classDef error fill:#dc2626,stroke:#b91c1c,color:#fff;"
-    )
-    .unwrap();
-    writeln!(
-        out,
-        "    classDef function fill:#2563eb,stroke:#1d4ed8,color:#fff;"
-    )
-    .unwrap();
-
-    // Generate Dynamic Classes for Groups
-    // Fill is Blue (#2563eb), Border is Group Color, Thick Stroke
-    for (i, hex) in GROUP_PALETTE.iter().enumerate() {
-        writeln!(
-            out,
-            "    classDef group{} fill:#2563eb,stroke:{},stroke-width:3px,color:#fff;",
-            i, hex
-        )
-        .unwrap();
+    if !options.mermaid_compact {
+        for (name, css) in BASE_CLASSES {
+            writeln!(out, "    classDef {} {};", name, css).unwrap();
+        }
+        // Group classes: blue fill with a thick border in the group color
+        for i in 0..GROUP_PALETTE.len() {
+            writeln!(
+                out,
+                "    classDef group{} {};",
+                i,
+                class_css(&format!("group{}", i))
+            )
+            .unwrap();
+        }
     }
 
+    // Compact mode numbers nodes instead of using their (long) uids
+    let ids: HashMap<u32, String> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let id = if options.mermaid_compact {
+                format!("n{}", i)
+            } else {
+                format!("N_{}", node.uid)
+            };
+            (node.uid, id)
+        })
+        .collect();
+    // Dangling edge endpoints keep their uid, as in the other exporters
+    let id_of = |uid: u32| {
+        ids.get(&uid)
+            .cloned()
+            .unwrap_or_else(|| format!("N_{}", uid))
+    };
+    let highlighted = options.highlighted(graph);
+
     // Group nodes
     let mut groups: HashMap<Option<String>, Vec<&crate::models::Node>> = HashMap::new();
     for node in &graph.nodes {
@@ -54,8 +95,22 @@ pub fn export(graph: &Graph) -> String {
         let is_cluster = group_opt.is_some();
 
         if let Some(group_name) = group_opt {
-            writeln!(out, "    subgraph {}", sanitize_id(&group_name)).unwrap();
-            writeln!(out, "        direction TB").unwrap();
+            let id = sanitize_id(&group_name);
+            if id == group_name {
+                writeln!(out, "    subgraph {}", id).unwrap();
+            } else {
+                // Keep the original name as the title
+                writeln!(
+                    out,
+                    "    subgraph {} [\"{}\"]",
+                    id,
+                    group_name.replace('"', "#quot;")
+                )
+                .unwrap();
+            }
+            if !options.mermaid_compact {
+                writeln!(out, "        direction TB").unwrap();
+            }
         }
 
         for node in nodes {
@@ -64,18 +119,24 @@ pub fn export(graph: &Graph) -> String {
                 Shape::Box => "[",
                 Shape::Rounded => "(",
                 Shape::Diamond => "{",
+                Shape::Sink => "[/",
             };
             let shape_close = match style.shape {
                 Shape::Box => "]",
                 Shape::Rounded => ")",
                 Shape::Diamond => "}",
+                Shape::Sink => "\\]",
             };
 
             // Node Definition
+            let id = &ids[&node.uid];
             writeln!(
                 out,
-                "        N_{}{}\"{}\"{}",
-                node.uid, shape_open, node.function.name, shape_close
+                "        {}{}\"{}\"{}",
+                id,
+                shape_open,
+                options.label(&node.function.name),
+                shape_close
             )
             .unwrap();
 
@@ -91,7 +152,19 @@ pub fn export(graph: &Graph) -> String {
                 "function".to_string()
             };
 
-            writeln!(out, "        class N_{} {}", node.uid, class_name).unwrap();
+            let dimmed = highlighted
+                .as_ref()
+                .is_some_and(|h| !h.nodes.contains(&node.uid));
+            if dimmed {
+                writeln!(out, "        style {} {}", id, DIMMED_CSS).unwrap();
+            } else if options.mermaid_compact {
+                writeln!(out, "        style {} {}", id, class_css(&class_name)).unwrap();
+            } else {
+                writeln!(out, "        class {} {}", id, class_name).unwrap();
+            }
+            if let Some(hex) = node.function.color.as_ref().filter(|_| !dimmed) {
+                writeln!(out, "        style {} fill:{}", id, hex).unwrap();
+            }
         }
 
         if is_cluster {
@@ -100,29 +173,147 @@ pub fn export(graph: &Graph) -> String {
     }
 
     // Edges
-    for edge in &graph.edges {
+    for (i, edge) in graph.edges.iter().enumerate() {
         let arrow = match edge.relation {
             EdgeRelation::ErrorFlow => "-.->",
             EdgeRelation::ControlFlow => "-.->",
+            EdgeRelation::FanOut => "==>",
+            _ if edge.token.optional => "-.->",
+            _ if edge.token.cardinality == Cardinality::Collection => "==>",
             _ => "-->",
         };
 
+        writeln!(
+            out,
+            "    {} {}|{}| {}",
+            id_of(edge.from_node_uid),
+            arrow,
+            edge_label(edge, options),
+            id_of(edge.to_node_uid)
+        )
+        .unwrap();
+        if let Some(h) = &highlighted {
+            let (color, width) = if h.edges.contains(&i) {
+                (theme::HIGHLIGHT_COLOR, 3)
+            } else {
+                (theme::DIMMED_COLOR, 1)
+            };
+            writeln!(
+                out,
+                "    linkStyle {} stroke:{},stroke-width:{}px;",
+                i, color, width
+            )
+            .unwrap();
+        }
+    }
+
+    out
+}
+
+/// Node classes shared by all diagrams (group classes are generated from the palette).
+const BASE_CLASSES: &[(&str, &str)] = &[
+    ("default", "fill:#1e293b,stroke:#334155,color:#fff"),
+    ("startend", "fill:#059669,stroke:#047857,color:#fff"),
+    ("error", "fill:#dc2626,stroke:#b91c1c,color:#fff"),
+    ("function", "fill:#2563eb,stroke:#1d4ed8,color:#fff"),
+];
+
+/// Styling of nodes outside a highlighted path.
+const DIMMED_CSS: &str = "fill:#334155,stroke:#64748b,color:#64748b";
+
+/// Styling of a node class, as written in its `classDef` or inline `style`.
+fn class_css(class_name: &str) -> String {
+    if let Some(hex) = class_name
+        .strip_prefix("group")
+        .and_then(|i| i.parse::<usize>().ok())
+        .and_then(|i| GROUP_PALETTE.get(i))
+    {
+        return format!("fill:#2563eb,stroke:{},stroke-width:3px,color:#fff", hex);
+    }
+    BASE_CLASSES
+        .iter()
+        .find(|(name, _)| *name == class_name)
+        .map_or(BASE_CLASSES[0].1, |(_, css)| css)
+        .to_string()
+}
+
+/// Renders the union of two graphs, highlighting what changed from `old` to `new`.
+///
+/// Added nodes and edges are green, removed ones are red and dashed,
+/// unchanged ones keep the default style.
+pub fn export_diff(old: &Graph, new: &Graph, options: &ExportOptions) -> String {
+    let diff = diff_graphs(old, new);
+    let added_nodes: HashSet<u32> = diff.added_nodes.iter().map(|n| n.uid).collect();
+    let added_edges: HashSet<_> = diff.added_edges.iter().map(edge_key).collect();
+
+    let mut out = String::new();
+    writeln!(out, "flowchart TD").unwrap();
+    writeln!(
+        out,
+        "    classDef default fill:#1e293b,stroke:#334155,color:#fff;"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    classDef added fill:#059669,stroke:#047857,color:#fff;"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    classDef removed fill:#dc2626,stroke:#b91c1c,color:#fff,stroke-dasharray:5 5;"
+    )
+    .unwrap();
+
+    let nodes = new
+        .nodes
+        .iter()
+        .map(|n| (n, added_nodes.contains(&n.uid).then_some("added")))
+        .chain(diff.removed_nodes.iter().map(|n| (n, Some("removed"))));
+    for (node, class) in nodes {
+        writeln!(
+            out,
+            "    N_{}[\"{}\"]",
+            node.uid,
+            options.label(&node.function.name)
+        )
+        .unwrap();
+        if let Some(class) = class {
+            writeln!(out, "    class N_{} {}", node.uid, class).unwrap();
+        }
+    }
+
+    let edges = new
+        .edges
+        .iter()
+        .map(|e| (e, added_edges.contains(&edge_key(e)), false))
+        .chain(diff.removed_edges.iter().map(|e| (e, false, true)));
+    for (i, (edge, added, removed)) in edges.enumerate() {
+        let arrow = if removed { "-.->" } else { "-->" };
         writeln!(
             out,
             "    N_{} {}|{}| N_{}",
             edge.from_node_uid,
             arrow,
-            edge.token.kind.name(),
+            edge_label(edge, options),
             edge.to_node_uid
         )
         .unwrap();
+        if added {
+            writeln!(out, "    linkStyle {} stroke:#16a34a,stroke-width:2px;", i).unwrap();
+        } else if removed {
+            writeln!(out, "    linkStyle {} stroke:#dc2626,stroke-width:2px;", i).unwrap();
+        }
     }
 
     out
 }
 
-fn sanitize_id(s: &str) -> String {
-    s.chars()
-        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
-        .collect()
+/// Edge label; collections are bracketed (and quoted, as Mermaid reserves brackets).
+fn edge_label(edge: &Edge, options: &ExportOptions) -> String {
+    let label = options.label(edge.token.kind.name());
+    if edge.token.cardinality == Cardinality::Collection {
+        format!("\"[{}]\"", label.replace('"', "#quot;"))
+    } else {
+        label
+    }
 }
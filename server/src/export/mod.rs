@@ -2,8 +2,133 @@
 //!
 //! Contains implementations for exporting the internal [Graph] to various external formats.
 
+pub mod dictionary;
 pub mod dot;
 pub mod mermaid;
 pub mod theme;
 pub mod tikz;
 pub mod vis_js;
+
+use crate::metrics;
+use crate::models::{hash_name, Graph, Node};
+use std::collections::HashMap;
+
+/// Rendering options shared by all exporters.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Color edges by the group of the function that produced their token.
+    pub color_edges_by_group: bool,
+    /// Maximum number of characters in node and edge labels (unlimited if `None`).
+    pub max_label_len: Option<usize>,
+    /// Append fan-in and fan-out counts to function labels, e.g. `Render (3→1)`.
+    pub show_degree: bool,
+    /// How error edges are drawn (HTML and DOT output).
+    pub error_layout: ErrorLayout,
+    /// Generation time shown in the diagram caption (omitted if `None`).
+    pub timestamp: Option<String>,
+    /// Number documented edge labels and list their docs below the diagram (TikZ output).
+    pub token_notes: bool,
+    /// Write each node's documentation as a caption below it (TikZ output).
+    pub node_captions: bool,
+    /// Graphviz layout attributes (DOT output).
+    pub dot_layout: dot::DotLayout,
+    /// Font family and sizes.
+    pub theme: theme::ThemeConfig,
+    /// Inline node styles, short node ids and no subgraph directions, for
+    /// renderers with limited Mermaid support such as GitHub (Mermaid output).
+    pub mermaid_compact: bool,
+    /// Force-directed layout parameters (HTML output).
+    pub physics: vis_js::PhysicsOptions,
+    /// Emphasize part of the graph and dim the rest (DOT, Mermaid and HTML output).
+    pub highlight: Option<Highlight>,
+}
+
+/// Part of the graph emphasized by [`ExportOptions::highlight`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Highlight {
+    /// Every path leading to a fatal error, see [`crate::metrics::error_paths`].
+    Errors,
+}
+
+/// Visual treatment of [`crate::models::EdgeRelation::ErrorFlow`] edges.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorLayout {
+    /// Errors flow forward like any other token.
+    #[default]
+    Forward,
+    /// Errors are drawn as curved edges returning to their producer,
+    /// so the unhappy path reads differently from the happy path.
+    Return,
+}
+
+impl ExportOptions {
+    /// Applies the label length limit to a node or edge label.
+    pub fn label(&self, text: &str) -> String {
+        match self.max_label_len {
+            Some(max) => theme::truncate_label(text, max),
+            None => text.to_string(),
+        }
+    }
+
+    /// Builds a node label, appending its degree when available.
+    ///
+    /// `degrees` comes from [`crate::metrics::degree_map`], computed only
+    /// when [`ExportOptions::show_degree`] is set.
+    pub fn node_label(
+        &self,
+        node: &Node,
+        degrees: Option<&HashMap<u32, (usize, usize)>>,
+    ) -> String {
+        let label = self.label(&node.function.name);
+        match degrees.and_then(|d| d.get(&node.uid)) {
+            Some((fan_in, fan_out)) => format!("{} ({}→{})", label, fan_in, fan_out),
+            None => label,
+        }
+    }
+
+    /// Caption lines for a diagram: title, author and generation time, when known.
+    pub fn caption(&self, graph: &Graph) -> Vec<String> {
+        self.caption_for(graph.title.as_deref(), graph.author.as_deref())
+    }
+
+    /// Caption lines for a diagram with the given title and author.
+    pub fn caption_for(&self, title: Option<&str>, author: Option<&str>) -> Vec<String> {
+        let mut lines: Vec<String> = [title, author]
+            .into_iter()
+            .flatten()
+            .map(str::to_string)
+            .collect();
+        if let Some(timestamp) = &self.timestamp {
+            lines.push(format!("Generated {}", timestamp));
+        }
+        lines
+    }
+
+    /// Computes node degrees if [`ExportOptions::show_degree`] is set.
+    pub fn degrees(&self, graph: &Graph) -> Option<HashMap<u32, (usize, usize)>> {
+        self.show_degree.then(|| metrics::degree_map(graph))
+    }
+
+    /// Computes the highlighted nodes and edges if [`ExportOptions::highlight`] is set.
+    pub fn highlighted(&self, graph: &Graph) -> Option<metrics::ErrorPaths> {
+        self.highlight.map(|highlight| match highlight {
+            Highlight::Errors => metrics::error_paths(graph),
+        })
+    }
+}
+
+/// Turns a name into an identifier for DOT clusters and Mermaid subgraphs.
+///
+/// ASCII alphanumeric names are kept. Otherwise every other character becomes
+/// `_` and a hash of the name is appended, so distinct names such as
+/// `Data Layer`, `Data_Layer` and `Dätä` never share an identifier.
+pub fn sanitize_id(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return s.to_string();
+    }
+    let stripped: String = s
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}_{:08x}", stripped, hash_name(s))
+}
@@ -3,7 +3,7 @@
 //! Centralized styling definitions for all static export formats (DOT, Mermaid, TikZ).
 //! Ensures visual consistency across different outputs.
 
-use crate::models::{Kind, Node};
+use crate::models::{Kind, Node, ProgramStructure, Token};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
@@ -23,6 +23,57 @@ pub const GROUP_PALETTE: &[&str] = &[
     "#ca8a04", // Yellow 600
 ];
 
+/// Body of function nodes (Slate 800).
+pub const FUNCTION_FILL: &str = "#1e293b";
+/// Border of ungrouped function nodes (Slate 600).
+pub const FUNCTION_BORDER: &str = "#475569";
+/// Start and end nodes (Emerald 600).
+pub const ENDPOINT_FILL: &str = "#059669";
+pub const ENDPOINT_BORDER: &str = "#047857";
+/// Fatal error terminations (Red 600).
+pub const FATAL_FILL: &str = "#dc2626";
+pub const FATAL_BORDER: &str = "#b91c1c";
+/// Edge colors per token kind (Purple, Slate 400, Red).
+pub const CONSTANT_COLOR: &str = "#a855f7";
+pub const VARIABLE_COLOR: &str = "#94a3b8";
+pub const ERROR_COLOR: &str = "#ef4444";
+/// Elements outside a highlighted path (Slate 700 and 500).
+pub const DIMMED_FILL: &str = "#334155";
+pub const DIMMED_COLOR: &str = "#64748b";
+/// Edges on a highlighted path (Red 500).
+pub const HIGHLIGHT_COLOR: &str = "#ef4444";
+/// Border width emphasizing group affiliation.
+pub const GROUP_STROKE_WIDTH: u32 = 3;
+/// Typography shared by all exporters.
+///
+/// Unset fields fall back to the front matter (`font`, `node_font_size`,
+/// `edge_font_size`). Fields left unset there too keep each exporter's own
+/// default typography, so unconfigured output does not change.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ThemeConfig {
+    /// Font family for node and edge labels
+    #[arg(long = "font", value_name = "FAMILY")]
+    pub font_family: Option<String>,
+
+    /// Font size of node labels, in points
+    #[arg(long, value_name = "SIZE")]
+    pub node_font_size: Option<u32>,
+
+    /// Font size of edge labels, in points
+    #[arg(long, value_name = "SIZE")]
+    pub edge_font_size: Option<u32>,
+}
+
+impl ThemeConfig {
+    /// Fills unset fields from the front matter of the analyzed program.
+    pub fn or_front_matter(mut self, structure: &ProgramStructure) -> Self {
+        self.font_family = self.font_family.or_else(|| structure.font.clone());
+        self.node_font_size = self.node_font_size.or(structure.node_font_size);
+        self.edge_font_size = self.edge_font_size.or(structure.edge_font_size);
+        self
+    }
+}
+
 pub struct Style {
     pub fill: String,
     pub border: String,
@@ -33,10 +84,21 @@ pub struct Style {
     pub stroke_width: u32,
 }
 
+impl Style {
+    /// Greys the node out, for nodes outside a highlighted path.
+    pub fn dim(&mut self) {
+        self.fill = DIMMED_FILL.into();
+        self.border = DIMMED_COLOR.into();
+        self.text = DIMMED_COLOR.into();
+    }
+}
+
 pub enum Shape {
     Box,
     Rounded,
     Diamond,
+    /// A function that produces nothing: its inputs terminate there.
+    Sink,
 }
 
 pub struct Theme;
@@ -50,10 +112,21 @@ impl Theme {
 
     /// Returns the style for a given node based on its Kind and properties.
     pub fn get_node_style(node: &Node) -> Style {
+        let mut style = Self::theme_node_style(node);
+        // An explicit `@color` replaces the body color; groups keep their border
+        if let Some(hex) = &node.function.color {
+            style.fill = hex.clone();
+            style.latex_fill = latex_rgb(hex);
+        }
+        style
+    }
+
+    /// Node style derived from the node role and group alone.
+    fn theme_node_style(node: &Node) -> Style {
         if node.is_artificial_error_termination {
             return Style {
-                fill: "#dc2626".into(), // Red 600
-                border: "#b91c1c".into(),
+                fill: FATAL_FILL.into(),
+                border: FATAL_BORDER.into(),
                 text: "#ffffff".into(),
                 shape: Shape::Diamond,
                 latex_fill: "TectRed".into(),
@@ -64,8 +137,8 @@ impl Theme {
 
         if node.is_artificial_graph_start || node.is_artificial_graph_end {
             return Style {
-                fill: "#059669".into(), // Emerald 600
-                border: "#047857".into(),
+                fill: ENDPOINT_FILL.into(),
+                border: ENDPOINT_BORDER.into(),
                 text: "#ffffff".into(),
                 shape: Shape::Rounded,
                 latex_fill: "TectGreen".into(),
@@ -75,26 +148,33 @@ impl Theme {
         }
 
         // Logic for Standard Functions
+        // Side-effecting functions (no outputs) are drawn as sinks
+        let shape = if node.function.produces.is_empty() && node.members.is_empty() {
+            Shape::Sink
+        } else {
+            Shape::Box
+        };
+
         if let Some(group) = &node.function.group {
             // Grouped Node: Blue Body, Group-Colored Border
             let (group_hex, group_latex) = Self::get_group_color(&group.name);
 
             Style {
-                fill: "#1e293b".into(), // Slate 800 (Darker body for contrast)
-                border: group_hex,      // Dynamic Group Color
+                fill: FUNCTION_FILL.into(),
+                border: group_hex, // Dynamic Group Color
                 text: "#ffffff".into(),
-                shape: Shape::Box,
+                shape,
                 latex_fill: "TectBlue".into(),
                 latex_border: group_latex,
-                stroke_width: 3, // Thick border to emphasize affiliation
+                stroke_width: GROUP_STROKE_WIDTH,
             }
         } else {
             // Ungrouped Node: Blue Body, Dark Blue Border
             Style {
-                fill: "#1e293b".into(),
-                border: "#475569".into(), // Slate 600
+                fill: FUNCTION_FILL.into(),
+                border: FUNCTION_BORDER.into(),
                 text: "#ffffff".into(),
-                shape: Shape::Box,
+                shape,
                 latex_fill: "TectBlue".into(),
                 latex_border: "TectBlueDark".into(),
                 stroke_width: 1,
@@ -102,15 +182,55 @@ impl Theme {
         }
     }
 
+    /// Returns the (Hex, LatexName) color for an edge carrying `token`.
+    ///
+    /// With `by_group`, tokens produced inside a group take the group's color;
+    /// all other tokens fall back to their kind color.
+    pub fn get_edge_color(token: &Token, by_group: bool) -> (String, String) {
+        match &token.group {
+            Some(group) if by_group => Self::get_group_color(&group.name),
+            _ => {
+                let (hex, latex) = Self::get_token_color(&token.kind);
+                (hex.to_string(), latex.to_string())
+            }
+        }
+    }
+
     pub fn get_token_color(kind: &Kind) -> (&'static str, &'static str) {
         match kind {
-            Kind::Constant(_) => ("#a855f7", "TectPurple"), // Purple
-            Kind::Variable(_) => ("#94a3b8", "TectGray"),   // Slate 400
-            Kind::Error(_) => ("#ef4444", "TectRed"),       // Red
+            Kind::Constant(_) => (CONSTANT_COLOR, "TectPurple"),
+            Kind::Variable(_) => (VARIABLE_COLOR, "TectGray"),
+            Kind::Error(_) => (ERROR_COLOR, "TectRed"),
         }
     }
 }
 
+/// Converts a `#rrggbb` color to an inline xcolor specification.
+fn latex_rgb(hex: &str) -> String {
+    let digits = hex.trim_start_matches('#');
+    let channel = |i: usize| {
+        digits
+            .get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .unwrap_or(0)
+    };
+    format!(
+        "{{rgb,255:red,{};green,{};blue,{}}}",
+        channel(0),
+        channel(2),
+        channel(4)
+    )
+}
+
+/// Shortens `s` to at most `max` characters, ending in an ellipsis when cut.
+pub fn truncate_label(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let kept: String = s.chars().take(max.saturating_sub(1)).collect();
+    format!("{}\u{2026}", kept)
+}
+
 fn get_palette_index(s: &str) -> usize {
     let mut hasher = DefaultHasher::new();
     s.hash(&mut hasher);
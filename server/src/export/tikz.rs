@@ -1,11 +1,12 @@
 //! # TikZ (LaTeX) Exporter
 
 use super::theme::{Shape, Theme, GROUP_PALETTE};
+use super::ExportOptions;
 use crate::models::{EdgeRelation, Graph};
 use std::collections::HashMap;
 use std::fmt::Write;
 
-pub fn export(graph: &Graph) -> String {
+pub fn export(graph: &Graph, options: &ExportOptions) -> String {
     let mut out = String::new();
 
     writeln!(out, "% Tect Architecture Export").unwrap();
@@ -17,6 +18,15 @@ pub fn export(graph: &Graph) -> String {
     )
     .unwrap();
     writeln!(out, "\\usegdlibrary{{force}}").unwrap();
+    // A configured font goes through fontspec; otherwise the class default stays
+    if let Some(family) = &options.theme.font_family {
+        writeln!(
+            out,
+            "\\usepackage{{fontspec}}\n\\setsansfont{{{}}}",
+            escape_latex(family)
+        )
+        .unwrap();
+    }
     writeln!(out).unwrap();
     writeln!(out, "% Tect Color Palette").unwrap();
     writeln!(out, "\\definecolor{{TectBlue}}{{HTML}}{{2563eb}}").unwrap();
@@ -41,8 +51,21 @@ pub fn export(graph: &Graph) -> String {
     writeln!(out, "\\begin{{document}}").unwrap();
     writeln!(out).unwrap();
     writeln!(out, "\\begin{{tikzpicture}}[").unwrap();
-    writeln!(out, "  tect_node/.style={{draw=none, text=white, font=\\sffamily\\small, inner sep=6pt, rounded corners=2pt}},").unwrap();
-    writeln!(out, "  tect_edge/.style={{draw=gray!50, thick, ->, >=stealth, font=\\sffamily\\tiny, align=center}}").unwrap();
+    writeln!(out, "  tect_node/.style={{draw=none, text=white, font={}, inner sep=6pt, rounded corners=2pt}},", font(options.theme.node_font_size, "\\small")).unwrap();
+    writeln!(
+        out,
+        "  tect_edge/.style={{draw=gray!50, thick, ->, >=stealth, font={}, align=center}}",
+        font(options.theme.edge_font_size, "\\tiny")
+    )
+    .unwrap();
+    if options.node_captions {
+        writeln!(
+            out,
+            "  tect_caption/.style={{text=TectGray, font={}, align=center, text width=4cm}},",
+            font(options.theme.edge_font_size, "\\tiny")
+        )
+        .unwrap();
+    }
     writeln!(out, "]").unwrap();
     writeln!(out).unwrap();
     writeln!(
@@ -70,6 +93,7 @@ pub fn export(graph: &Graph) -> String {
                 Shape::Box => "rectangle",
                 Shape::Rounded => "rectangle, rounded corners=5pt",
                 Shape::Diamond => "diamond",
+                Shape::Sink => "rectangle, double",
             };
 
             // Apply fill and stroke colors from theme
@@ -83,10 +107,27 @@ pub fn export(graph: &Graph) -> String {
                 "".to_string()
             };
 
+            // Step docs, then function docs, one caption line per doc line
+            let caption = node
+                .documentation
+                .as_deref()
+                .or(node.function.documentation.as_deref())
+                .filter(|_| options.node_captions)
+                .map(|docs| {
+                    let lines: Vec<String> = docs.lines().map(escape_latex).collect();
+                    format!(", label={{[tect_caption]below:{{{}}}}}", lines.join("\\\\"))
+                })
+                .unwrap_or_default();
+
             writeln!(
                 out,
-                "  N_{} [as=\"{}\", tect_node, shape={}, fill={}{}];",
-                node.uid, node.function.name, shape_tikz, style.latex_fill, draw_opts
+                "  N_{} [as=\"{}\", tect_node, shape={}, fill={}{}{}];",
+                node.uid,
+                escape_latex(&options.label(&node.function.name)),
+                shape_tikz,
+                style.latex_fill,
+                draw_opts,
+                caption
             )
             .unwrap();
         }
@@ -94,28 +135,105 @@ pub fn export(graph: &Graph) -> String {
 
     writeln!(out).unwrap();
 
+    // Documented tokens are numbered in order of first appearance
+    let mut notes: Vec<(&str, &str)> = Vec::new();
+
     for edge in &graph.edges {
-        let (_, color_name) = Theme::get_token_color(&edge.token.kind);
+        let (_, color_name) = Theme::get_edge_color(&edge.token, options.color_edges_by_group);
         let style_extra = match edge.relation {
             EdgeRelation::ControlFlow | EdgeRelation::ErrorFlow => ", dashed",
+            EdgeRelation::FanOut => ", ->>",
+            _ if edge.token.optional => ", dashed",
             _ => "",
         };
 
+        let name = edge.token.kind.name();
+        let mut label = escape_latex(&options.label(name));
+        if let Some(docs) = edge.token.kind.docs().filter(|_| options.token_notes) {
+            let index = match notes.iter().position(|(n, _)| *n == name) {
+                Some(i) => i,
+                None => {
+                    notes.push((name, docs));
+                    notes.len() - 1
+                }
+            };
+            write!(label, "$^{{{}}}$", index + 1).unwrap();
+        }
+
         writeln!(
             out,
             "  N_{} -> [tect_edge, color={}, edge label=\"{}\"{}] N_{};",
-            edge.from_node_uid,
-            color_name,
-            edge.token.kind.name(),
-            style_extra,
-            edge.to_node_uid
+            edge.from_node_uid, color_name, label, style_extra, edge.to_node_uid
         )
         .unwrap();
     }
 
     writeln!(out, "}};").unwrap();
+
+    let caption = options.caption(graph);
+    if !caption.is_empty() {
+        let lines: Vec<String> = caption.iter().map(|l| escape_latex(l)).collect();
+        writeln!(
+            out,
+            "\\node[anchor=south, align=center, font=\\sffamily\\small] at (current bounding box.north) {{{}}};",
+            lines.join("\\\\")
+        )
+        .unwrap();
+    }
+
+    if !notes.is_empty() {
+        let lines: Vec<String> = notes
+            .iter()
+            .enumerate()
+            .map(|(i, (name, docs))| {
+                format!(
+                    "$^{{{}}}$ \\textbf{{{}}}: {}",
+                    i + 1,
+                    escape_latex(name),
+                    escape_latex(docs)
+                )
+            })
+            .collect();
+        writeln!(
+            out,
+            "\\node[anchor=north, align=left, font=\\sffamily\\scriptsize] at (current bounding box.south) {{{}}};",
+            lines.join("\\\\")
+        )
+        .unwrap();
+    }
     writeln!(out, "\\end{{tikzpicture}}").unwrap();
     writeln!(out, "\\end{{document}}").unwrap();
 
     out
 }
+
+/// Sans-serif font selection at `size` points with 1.2 line spacing, or at
+/// the relative `default` size (e.g. `\\small`) when no size is configured.
+fn font(size: Option<u32>, default: &str) -> String {
+    match size {
+        Some(size) => format!(
+            "\\sffamily\\fontsize{{{}}}{{{}}}\\selectfont",
+            size,
+            size * 6 / 5
+        ),
+        None => format!("\\sffamily{}", default),
+    }
+}
+
+/// Escapes LaTeX special characters in free text.
+fn escape_latex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
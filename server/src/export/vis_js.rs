@@ -2,8 +2,9 @@
 //!
 //! Note: This module uses the centralized `theme.rs` to ensure visual consistency.
 
-use super::theme::{Shape, Theme};
-use crate::models::{Cardinality, Graph, Kind};
+use super::theme::{self, Shape, Theme};
+use super::{ErrorLayout, ExportOptions};
+use crate::models::{Cardinality, DiagnosticReport, Edge, EdgeRelation, Graph, Kind};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
@@ -41,6 +42,12 @@ pub struct VisFont {
     pub stroke_width: u32,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VisShapeProperties {
+    pub border_dashes: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct VisNode {
@@ -51,7 +58,13 @@ pub struct VisNode {
     pub cluster_group: Option<String>,
     pub color: VisColor,
     pub border_width: u32,
+    /// Outline options; only set for sinks, which get a dashed border.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shape_properties: Option<VisShapeProperties>,
     pub font: VisFont,
+    /// Tooltip shown on hover (step documentation, or the full name of a truncated label).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -64,15 +77,135 @@ pub struct VisEdge {
     pub color: String,
     pub width: f32,
     pub dashes: bool,
-    pub arrows: String,
+    pub arrows: VisArrows,
     pub font: VisFont,
+    /// Tooltip with the full token name when the label is truncated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Curve settings, used to draw returning error edges.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smooth: Option<VisSmooth>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VisSmooth {
+    pub enabled: bool,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub roundness: f32,
+}
+
+/// Physics defaults, shared by the command line and [`PhysicsOptions::default`].
+const DEFAULT_GRAVITY: f32 = -100.0;
+const DEFAULT_SPRING_LENGTH: f32 = 10.0;
+const DEFAULT_AVOID_OVERLAP: f32 = 1.0;
+const DEFAULT_DAMPING: f32 = 0.75;
+
+/// Force-directed layout of the interactive diagram (vis.js `forceAtlas2Based` solver).
+///
+/// Large graphs usually need a stronger repulsion than small ones.
+#[derive(Debug, Clone, Serialize, clap::Args)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicsOptions {
+    /// Attraction between nodes; more negative values push them further apart (HTML output)
+    #[arg(long = "gravity", value_name = "G", default_value_t = DEFAULT_GRAVITY, allow_negative_numbers = true)]
+    pub gravitational_constant: f32,
+
+    /// Rest length of edges (HTML output)
+    #[arg(long, value_name = "LENGTH", default_value_t = DEFAULT_SPRING_LENGTH)]
+    pub spring_length: f32,
+
+    /// How strongly nodes avoid overlapping, from 0 to 1 (HTML output)
+    #[arg(long, value_name = "FACTOR", default_value_t = DEFAULT_AVOID_OVERLAP, value_parser = parse_fraction)]
+    pub avoid_overlap: f32,
+
+    /// Fraction of velocity lost per step, from 0 to 1 (HTML output)
+    #[arg(long, value_name = "FACTOR", default_value_t = DEFAULT_DAMPING, value_parser = parse_fraction)]
+    pub damping: f32,
+}
+
+impl Default for PhysicsOptions {
+    fn default() -> Self {
+        Self {
+            gravitational_constant: DEFAULT_GRAVITY,
+            spring_length: DEFAULT_SPRING_LENGTH,
+            avoid_overlap: DEFAULT_AVOID_OVERLAP,
+            damping: DEFAULT_DAMPING,
+        }
+    }
+}
+
+/// Parses a number between 0 and 1, inclusive.
+fn parse_fraction(value: &str) -> Result<f32, String> {
+    let number: f32 = value.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=1.0).contains(&number) {
+        Ok(number)
+    } else {
+        Err(format!("{} is not between 0 and 1", number))
+    }
+}
+
+/// Width of edges carrying collections.
+const COLLECTION_EDGE_WIDTH: f32 = 5.0;
+/// Width of edges carrying single items.
+const EDGE_WIDTH: f32 = 1.5;
+/// Minimum width of edges on a highlighted path.
+const HIGHLIGHT_EDGE_WIDTH: f32 = 3.0;
+/// Minimum border width of sinks, so their dashes show.
+const SINK_BORDER_WIDTH: u32 = 2;
+/// Label sizes used unless [`super::theme::ThemeConfig`] sets them.
+const DEFAULT_NODE_FONT_SIZE: u32 = 14;
+const DEFAULT_EDGE_FONT_SIZE: u32 = 11;
+
+/// Arrowheads of an edge; absent ends are not drawn.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VisArrows {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<VisArrowHead>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub middle: Option<VisArrowHead>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<VisArrowHead>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VisArrowHead {
+    pub enabled: bool,
+    /// vis.js arrow type (`arrow`, `bar`, `circle`, `crow`, ...).
+    #[serde(rename = "type")]
+    pub kind: String,
 }
 
-pub fn produce_vis_data(graph: &Graph) -> VisData {
+impl VisArrowHead {
+    fn new(kind: &str) -> Option<Self> {
+        Some(Self {
+            enabled: true,
+            kind: kind.into(),
+        })
+    }
+}
+
+/// Arrowhead telling edge roles apart: errors end in a circle, terminal
+/// flows in a bar and collections in a crow's foot.
+fn arrow_type(edge: &Edge) -> &'static str {
+    match edge.relation {
+        EdgeRelation::ErrorFlow => "circle",
+        EdgeRelation::TerminalFlow => "bar",
+        _ if edge.token.cardinality == Cardinality::Collection => "crow",
+        _ => "arrow",
+    }
+}
+
+pub fn produce_vis_data(graph: &Graph, options: &ExportOptions) -> VisData {
     let mut vis_nodes = Vec::new();
     let mut vis_edges = Vec::new();
     let mut groups = HashSet::new();
     let mut group_colors = HashMap::new();
+    let degrees = options.degrees(graph);
+    let highlighted = options.highlighted(graph);
 
     for n in &graph.nodes {
         let group_name = n.function.group.as_ref().map(|g| g.name.clone());
@@ -84,17 +217,31 @@ pub fn produce_vis_data(graph: &Graph) -> VisData {
             }
         }
 
-        let style = Theme::get_node_style(n);
+        let mut style = Theme::get_node_style(n);
+        if highlighted
+            .as_ref()
+            .is_some_and(|h| !h.nodes.contains(&n.uid))
+        {
+            style.dim();
+        }
 
         let vis_shape = match style.shape {
             Shape::Box => "box",
             Shape::Rounded => "box",
             Shape::Diamond => "box",
+            Shape::Sink => "box",
+        };
+        // Sinks keep the box shape but get a thicker, dashed outline
+        let is_sink = matches!(style.shape, Shape::Sink);
+        let border_width = if is_sink {
+            style.stroke_width.max(SINK_BORDER_WIDTH)
+        } else {
+            style.stroke_width
         };
 
         vis_nodes.push(VisNode {
             id: n.uid,
-            label: format!(" {} ", n.function.name),
+            label: format!(" {} ", options.node_label(n, degrees.as_ref())),
             shape: vis_shape.into(),
             margin: 10,
             cluster_group: group_name.clone(),
@@ -106,17 +253,27 @@ pub fn produce_vis_data(graph: &Graph) -> VisData {
                     border: "#ffffff".into(),
                 },
             },
-            border_width: style.stroke_width,
+            border_width,
+            shape_properties: is_sink.then_some(VisShapeProperties {
+                border_dashes: true,
+            }),
             font: VisFont {
                 color: style.text,
-                size: 14,
-                face: "sans-serif".into(),
+                size: options
+                    .theme
+                    .node_font_size
+                    .unwrap_or(DEFAULT_NODE_FONT_SIZE),
+                face: font_face(options, "sans-serif"),
                 stroke_width: 0,
             },
+            title: n.documentation.clone().or_else(|| {
+                let full = &n.function.name;
+                (options.label(full) != *full).then(|| full.clone())
+            }),
         });
     }
 
-    for e in &graph.edges {
+    for (i, e) in graph.edges.iter().enumerate() {
         let is_many = e.token.cardinality == Cardinality::Collection;
         let t_name = match &e.token.kind {
             Kind::Constant(c) => &c.name,
@@ -124,30 +281,73 @@ pub fn produce_vis_data(graph: &Graph) -> VisData {
             Kind::Error(er) => &er.name,
         };
 
-        let (color, _) = Theme::get_token_color(&e.token.kind);
+        let (mut color, _) = Theme::get_edge_color(&e.token, options.color_edges_by_group);
+        let mut width = if is_many {
+            COLLECTION_EDGE_WIDTH
+        } else {
+            EDGE_WIDTH
+        };
+        if let Some(h) = &highlighted {
+            if h.edges.contains(&i) {
+                color = theme::HIGHLIGHT_COLOR.into();
+                width = width.max(HIGHLIGHT_EDGE_WIDTH);
+            } else {
+                color = theme::DIMMED_COLOR.into();
+            }
+        }
+        let label = options.label(t_name);
+        let title = (label != *t_name).then(|| t_name.clone());
 
         // Deterministic Edge ID: "FromUID-ToUID-TokenUID"
         let edge_id = format!("{}-{}-{}", e.from_node_uid, e.to_node_uid, e.token.uid);
 
+        let returns_error =
+            e.relation == EdgeRelation::ErrorFlow && options.error_layout == ErrorLayout::Return;
+
         vis_edges.push(VisEdge {
             id: edge_id,
             from: e.from_node_uid,
             to: e.to_node_uid,
             label: if is_many {
-                format!("[{}]", t_name)
+                format!("[{}]", label)
+            } else {
+                label
+            },
+            color,
+            width,
+            dashes: e.token.optional || matches!(e.token.kind, Kind::Constant(_)),
+            arrows: if e.relation == EdgeRelation::FanOut {
+                VisArrows {
+                    to: VisArrowHead::new("arrow"),
+                    middle: VisArrowHead::new("crow"),
+                    ..VisArrows::default()
+                }
+            } else if returns_error {
+                VisArrows {
+                    from: VisArrowHead::new(arrow_type(e)),
+                    ..VisArrows::default()
+                }
             } else {
-                t_name.clone()
+                VisArrows {
+                    to: VisArrowHead::new(arrow_type(e)),
+                    ..VisArrows::default()
+                }
             },
-            color: color.into(),
-            width: if is_many { 5.0 } else { 1.5 },
-            dashes: matches!(e.token.kind, Kind::Constant(_)),
-            arrows: "to".into(),
             font: VisFont {
                 color: "#ffffff".into(),
-                size: 11,
-                face: "monospace".into(),
+                size: options
+                    .theme
+                    .edge_font_size
+                    .unwrap_or(DEFAULT_EDGE_FONT_SIZE),
+                face: font_face(options, "monospace"),
                 stroke_width: 0,
             },
+            title,
+            smooth: returns_error.then(|| VisSmooth {
+                enabled: true,
+                kind: "curvedCCW".into(),
+                roundness: 0.4,
+            }),
         });
     }
 
@@ -159,22 +359,125 @@ pub fn produce_vis_data(graph: &Graph) -> VisData {
     }
 }
 
-pub fn generate_interactive_html(graph: &Graph) -> String {
-    let data = produce_vis_data(graph);
+pub fn generate_interactive_html(graph: &Graph, options: &ExportOptions) -> String {
+    render_page(graph, options, "")
+}
+
+/// Generates the interactive diagram with a list of diagnostics below it.
+///
+/// Clicking a diagnostic related to a function focuses its node.
+pub fn generate_report_html(
+    graph: &Graph,
+    options: &ExportOptions,
+    diagnostics: &[DiagnosticReport],
+) -> String {
+    let items: String = diagnostics
+        .iter()
+        .map(|d| {
+            let icon = match d.severity.as_str() {
+                "error" => "\u{2716}",
+                "warning" => "\u{26a0}",
+                "information" => "\u{2139}",
+                _ => "\u{2022}",
+            };
+            let location = match (&d.file, d.line, d.column) {
+                (Some(file), Some(line), Some(column)) => {
+                    let name = std::path::Path::new(file)
+                        .file_name()
+                        .map_or(file.clone(), |n| n.to_string_lossy().into_owned());
+                    format!("{}:{}:{}", name, line, column)
+                }
+                _ => "global".to_string(),
+            };
+            let node_attr = d
+                .node_uid
+                .map(|uid| format!(" data-node=\"{}\"", uid))
+                .unwrap_or_default();
+            format!(
+                "<li class=\"diag-{}\"{}><span class=\"icon\">{}</span><span class=\"loc\">[{}]</span> {}</li>",
+                d.severity,
+                node_attr,
+                icon,
+                escape_html(&location),
+                escape_html(&d.message)
+            )
+        })
+        .collect();
+    let body = if diagnostics.is_empty() {
+        "<p>No issues found.</p>".to_string()
+    } else {
+        format!("<ul>{}</ul>", items)
+    };
+    let panel = format!(
+        "<div id=\"diagnostics\"><h3>Diagnostics ({})</h3>{}</div>",
+        diagnostics.len(),
+        body
+    );
+    render_page(graph, options, &panel)
+}
+
+/// CSS font stack for the configured family, falling back to sans-serif,
+/// or `default` when no family is configured.
+fn font_face(options: &ExportOptions, default: &str) -> String {
+    match &options.theme.font_family {
+        Some(family) => format!(
+            "'{}', sans-serif",
+            family.replace(['\'', '"', '<', '>'], "")
+        ),
+        None => default.to_string(),
+    }
+}
+
+/// Renders the HTML page; `diagnostics_panel` is placed below the diagram.
+fn render_page(graph: &Graph, options: &ExportOptions, diagnostics_panel: &str) -> String {
+    let data = produce_vis_data(graph, options);
     let nodes_json = serde_json::to_string(&data.nodes).unwrap();
     let edges_json = serde_json::to_string(&data.edges).unwrap();
     let groups_json = serde_json::to_string(&data.groups).unwrap();
     let color_map_json = serde_json::to_string(&data.group_colors).unwrap();
+    let title = escape_html(graph.title.as_deref().unwrap_or("Tect Architecture"));
+    let caption: String = options
+        .caption(graph)
+        .iter()
+        .map(|line| format!("<div>{}</div>", escape_html(line)))
+        .collect();
+    let legend = legend_html(&data);
+    let font = font_face(options, "sans-serif");
+    let cluster_font = match options.theme.font_family {
+        Some(_) => serde_json::to_string(&font).unwrap(),
+        None => "'sans-serif'".to_string(),
+    };
+    let cluster_font_size = options
+        .theme
+        .node_font_size
+        .unwrap_or(DEFAULT_NODE_FONT_SIZE)
+        + 2;
+    let physics_json = serde_json::to_string(&options.physics).unwrap();
 
     format!(
         r#"<!DOCTYPE html>
 <html style="color-scheme: dark;">
 <head>
     <meta charset="utf-8">
+    <title>{title}</title>
     <script type="text/javascript" src="https://unpkg.com/vis-network/standalone/umd/vis-network.min.js"></script>
     <style type="text/css">
-        body {{ background-color: #0b0e14; color: #e0e0e0; margin: 0; display: flex; font-family: sans-serif; height: 100vh; overflow: hidden; }}
-        #mynetwork {{ flex-grow: 1; height: 100vh; }}
+        body {{ background-color: #0b0e14; color: #e0e0e0; margin: 0; display: flex; font-family: {font}; height: 100vh; overflow: hidden; }}
+        #main {{ flex-grow: 1; display: flex; flex-direction: column; height: 100vh; min-width: 0; }}
+        #mynetwork {{ flex-grow: 1; min-height: 0; }}
+        #diagnostics {{ max-height: 30vh; overflow-y: auto; background: #0d1117; border-top: 2px solid #30363d; padding: 0 15px 10px; font-size: 13px; }}
+        #diagnostics h3 {{ font-size: 14px; color: #58a6ff; }}
+        #diagnostics ul {{ list-style: none; margin: 0; padding: 0; }}
+        #diagnostics li {{ padding: 3px 0; }}
+        #diagnostics li[data-node] {{ cursor: pointer; }}
+        #diagnostics li[data-node]:hover {{ background: #161b22; }}
+        #diagnostics .icon {{ display: inline-block; width: 1.5em; }}
+        #diagnostics .loc {{ color: #8b949e; }}
+        .diag-error .icon {{ color: #f85149; }}
+        .diag-warning .icon {{ color: #d29922; }}
+        .diag-information .icon {{ color: #58a6ff; }}
+        #caption {{ position: absolute; top: 10px; left: 14px; z-index: 5; pointer-events: none; font-size: 13px; color: #8b949e; }}
+        #caption div:first-child {{ font-size: 16px; font-weight: bold; color: #e0e0e0; }}
         #resizer {{ width: 6px; cursor: col-resize; background-color: #30363d; transition: background 0.2s; z-index: 10; }}
         #resizer:hover {{ background-color: #58a6ff; }}
         #config {{ width: 350px; min-width: 250px; height: 100vh; overflow-y: auto; background: #161b22; flex-shrink: 0; display: flex; flex-direction: column; }}
@@ -189,12 +492,23 @@ pub fn generate_interactive_html(graph: &Graph) -> String {
         #options-code {{ background: #161b22; padding: 10px; border-radius: 4px; font-family: monospace; font-size: 11px; max-height: 200px; overflow: auto; white-space: pre-wrap; border: 1px solid #30363d; color: #8b949e; }}
         #copy-btn {{ margin-top: 10px; width: 100%; padding: 8px; background: #238636; color: white; border: none; border-radius: 4px; cursor: pointer; font-weight: bold; }}
         #copy-btn:hover {{ background: #2ea043; }}
+        #legend {{ padding: 10px 15px; border-bottom: 2px solid #30363d; font-size: 12px; }}
+        #legend summary {{ color: #58a6ff; font-weight: bold; cursor: pointer; }}
+        #legend ul {{ list-style: none; margin: 8px 0 0; padding: 0; }}
+        #legend li {{ display: flex; align-items: center; gap: 8px; margin: 4px 0; }}
+        .legend-node {{ width: 22px; height: 12px; border-radius: 2px; box-sizing: border-box; flex-shrink: 0; }}
+        .legend-edge {{ width: 22px; height: 0; flex-shrink: 0; }}
     </style>
 </head>
 <body>
-<div id="mynetwork"></div>
+<div id="caption">{caption}</div>
+<div id="main">
+    <div id="mynetwork"></div>
+    {diagnostics_panel}
+</div>
 <div id="resizer"></div>
 <div id="config">
+    {legend}
     <div id="config-controls"></div>
     <div id="options-export">
         <h3>Current Options (JSON)</h3>
@@ -227,7 +541,7 @@ pub fn generate_interactive_html(graph: &Graph) -> String {
         .observe(configControls, {{ childList: true, subtree: true }});
     const data = {{ nodes, edges }};
     const options = {{
-        physics: {{ enabled: true, solver: 'forceAtlas2Based', forceAtlas2Based: {{ gravitationalConstant: -100, springLength: 10, avoidOverlap: 1, damping: 0.75 }} }},
+        physics: {{ enabled: true, solver: 'forceAtlas2Based', forceAtlas2Based: {physics_json} }},
         interaction: {{ navigationButtons: true, keyboard: true, hover: true }},
         configure: {{ enabled: true, container: configControls, showButton: false }}
     }};
@@ -235,7 +549,7 @@ pub fn generate_interactive_html(graph: &Graph) -> String {
     network.on("configChange", (params) => {{ optionsCode.innerText = JSON.stringify(params, null, 2); }});
     const clusterBy = (g) => ({{
         joinCondition: (n) => n.clusterGroup === g,
-        clusterNodeProperties: {{ id: 'c:'+g, label: g, shape: 'box', margin: 10, color: {{ background: groupColors[g] || '#fbbf24', border: '#fff' }}, font: {{ color: '#fff', size: 16, face: 'sans-serif', strokeWidth: 0 }} }}
+        clusterNodeProperties: {{ id: 'c:'+g, label: g, shape: 'box', margin: 10, color: {{ background: groupColors[g] || '#fbbf24', border: '#fff' }}, font: {{ color: '#fff', size: {cluster_font_size}, face: {cluster_font}, strokeWidth: 0 }} }}
     }});
     groups.forEach(g => network.cluster(clusterBy(g)));
     network.on("click", (p) => {{
@@ -245,6 +559,16 @@ pub fn generate_interactive_html(graph: &Graph) -> String {
             else {{ let d = nodes.get(id); if (d && d.clusterGroup) network.cluster(clusterBy(d.clusterGroup)); }}
         }}
     }});
+    document.querySelectorAll('#diagnostics li[data-node]').forEach(item => {{
+        item.addEventListener('click', () => {{
+            const id = Number(item.dataset.node);
+            const path = network.findNode(id);
+            if (path.length === 0) return;
+            if (path.length > 1) network.openCluster(path[0]);
+            network.selectNodes([id]);
+            network.focus(id, {{ scale: 1.2, animation: true }});
+        }});
+    }});
     copyBtn.addEventListener('click', () => {{
         navigator.clipboard.writeText(optionsCode.innerText).then(() => {{
             const originalText = copyBtn.innerText;
@@ -257,3 +581,65 @@ pub fn generate_interactive_html(graph: &Graph) -> String {
 </html>"#
     )
 }
+
+/// Builds the collapsible legend explaining the color and shape scheme.
+///
+/// Colors and widths come from the same constants used to draw the graph.
+fn legend_html(data: &VisData) -> String {
+    let node = |fill: &str, border: &str, width: u32, label: &str| {
+        format!(
+            "<li><span class=\"legend-node\" style=\"background:{};border:{}px solid {}\"></span>{}</li>",
+            fill, width, border, label
+        )
+    };
+    let edge = |color: &str, width: f32, style: &str, label: &str| {
+        format!(
+            "<li><span class=\"legend-edge\" style=\"border-top:{}px {} {}\"></span>{}</li>",
+            width, style, color, label
+        )
+    };
+    let mut items = vec![
+        node(theme::FUNCTION_FILL, theme::FUNCTION_BORDER, 1, "Function"),
+        node(
+            theme::ENDPOINT_FILL,
+            theme::ENDPOINT_BORDER,
+            1,
+            "Start / End",
+        ),
+        node(theme::FATAL_FILL, theme::FATAL_BORDER, 1, "Fatal error"),
+    ];
+    let mut groups = data.groups.clone();
+    groups.sort();
+    for group in &groups {
+        let label = format!("Group: {}", escape_html(group));
+        items.push(node(
+            theme::FUNCTION_FILL,
+            &data.group_colors[group],
+            theme::GROUP_STROKE_WIDTH,
+            &label,
+        ));
+    }
+    items.extend([
+        edge(theme::VARIABLE_COLOR, EDGE_WIDTH, "solid", "Variable"),
+        edge(theme::CONSTANT_COLOR, EDGE_WIDTH, "dashed", "Constant"),
+        edge(theme::ERROR_COLOR, EDGE_WIDTH, "solid", "Error"),
+        edge(
+            theme::VARIABLE_COLOR,
+            COLLECTION_EDGE_WIDTH,
+            "solid",
+            "Collection",
+        ),
+    ]);
+
+    format!(
+        "<details id=\"legend\" open><summary>Legend</summary><ul>{}</ul></details>",
+        items.concat()
+    )
+}
+
+/// Escapes text for use inside HTML elements.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
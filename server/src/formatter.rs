@@ -5,6 +5,45 @@
 
 use crate::analyzer::{Rule, TectParser};
 use pest::Parser;
+use std::collections::HashMap;
+
+/// Options controlling the layout produced by [format_tect_source].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Number of spaces used for one indentation level (ignored when `use_tabs` is set).
+    pub indent: usize,
+    /// Indent with a single tab character instead of spaces.
+    pub use_tabs: bool,
+    /// Align the token lists of a function's output lines into columns.
+    pub align_outputs: bool,
+    /// Sort the items of every token list (constants, variables, errors, then by name).
+    pub sort_tokens: bool,
+    /// Move all imports into one sorted block above the first statement, followed by a blank line.
+    pub group_imports: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            use_tabs: false,
+            align_outputs: false,
+            sort_tokens: false,
+            group_imports: true,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Returns the string used for one level of indentation.
+    fn indent_unit(&self) -> String {
+        if self.use_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.indent)
+        }
+    }
+}
 
 /// Represents a formatted block of code.
 ///
@@ -17,6 +56,10 @@ struct Block {
     start_pos: usize,
     /// The end byte position in the original source.
     end_pos: usize,
+    /// Forces a blank line between this block and the next one.
+    blank_line_after: bool,
+    /// Comment or front matter, i.e. not a statement of its own.
+    is_comment: bool,
 }
 
 /// Formats the given Tect source code string.
@@ -27,14 +70,22 @@ struct Block {
 ///
 /// # Returns
 /// `Some(String)` containing the formatted code if parsing succeeds, or `None` if parsing fails.
-pub fn format_tect_source(content: &str) -> Option<String> {
+pub fn format_tect_source(content: &str, options: &FormatOptions) -> Option<String> {
     let mut blocks = Vec::new();
+    let mut import_indices = Vec::new();
 
     let parsed = match TectParser::parse(Rule::program, content) {
         Ok(mut p) => p.next().unwrap(),
         Err(_) => return None,
     };
 
+    // Token sorting needs to know the kind of each artifact defined in this file
+    let kind_ranks = if options.sort_tokens {
+        Some(collect_kind_ranks(parsed.clone()))
+    } else {
+        None
+    };
+
     // 1. Blockify: Convert AST pairs into styled Blocks
     for pair in parsed.into_inner() {
         if pair.as_rule() == Rule::EOI {
@@ -42,22 +93,38 @@ pub fn format_tect_source(content: &str) -> Option<String> {
         }
 
         let span = pair.as_span();
+        let is_import = pair.as_rule() == Rule::import_stmt;
+        let is_comment = matches!(pair.as_rule(), Rule::comment | Rule::front_matter);
         let formatted_content = match pair.as_rule() {
-            Rule::func_def => format_function(pair),
+            Rule::func_def => format_function(pair, options, kind_ranks.as_ref()),
+            Rule::flow_block => format_flow_block(pair, options),
+            Rule::assert_stmt => format_assertion(pair),
             Rule::import_stmt => pair.as_str().trim().to_string(),
             Rule::comment | Rule::flow_step => pair.as_str().trim().to_string(),
             _ => pair.as_str().trim().to_string(), // Constants, vars, etc.
         };
 
         if !formatted_content.is_empty() {
+            if is_import {
+                import_indices.push(blocks.len());
+            }
+            // Some rules (e.g. functions without outputs) swallow trailing newlines.
+            // Measure the block without them so the glue step sees the real gap.
+            let end_pos = span.start() + span.as_str().trim_end().len();
             blocks.push(Block {
                 content: formatted_content,
                 start_pos: span.start(),
-                end_pos: span.end(),
+                end_pos,
+                blank_line_after: false,
+                is_comment,
             });
         }
     }
 
+    if options.group_imports {
+        group_import_blocks(&mut blocks, &import_indices, content);
+    }
+
     if blocks.is_empty() {
         return Some(String::new());
     }
@@ -76,7 +143,11 @@ pub fn format_tect_source(content: &str) -> Option<String> {
 
         // If gap has >= 2 newlines (a blank line), force \n\n.
         // Else use \n (this keeps comments attached to code if they were adjacent).
-        let separator = if newline_count >= 2 { "\n\n" } else { "\n" };
+        let separator = if newline_count >= 2 || prev.blank_line_after {
+            "\n\n"
+        } else {
+            "\n"
+        };
 
         result.push_str(separator);
         result.push_str(&curr.content);
@@ -86,24 +157,243 @@ pub fn format_tect_source(content: &str) -> Option<String> {
     Some(result)
 }
 
+/// Formats an `assert` statement with single spaces between its words.
+fn format_assertion(pair: pest::iterators::Pair<Rule>) -> String {
+    let mut lines = Vec::new();
+    let mut words = Vec::new();
+    for child in pair.into_inner() {
+        match child.as_rule() {
+            Rule::doc_line => lines.push(child.as_str().trim().to_string()),
+            Rule::kw_assert => words.push(child.as_str().to_string()),
+            Rule::assert_reachable => {
+                let parts: Vec<&str> = child.into_inner().map(|p| p.as_str()).collect();
+                words.push(parts[..2].join(" -> "));
+                words.extend(parts[2..].iter().map(|p| p.to_string()));
+            }
+            _ => words.extend(child.into_inner().map(|p| p.as_str().to_string())),
+        }
+    }
+    lines.push(words.join(" "));
+    lines.join("\n")
+}
+
+/// Formats a named flow block, indenting its steps and comments.
+///
+/// Blank lines between steps are preserved (collapsed to one).
+fn format_flow_block(pair: pest::iterators::Pair<Rule>, options: &FormatOptions) -> String {
+    let indent = options.indent_unit();
+    let source = pair.as_str();
+    let base = pair.as_span().start();
+
+    let mut lines = Vec::new();
+    let mut header = Vec::new();
+    let mut prev_end = None;
+
+    for child in pair.into_inner() {
+        match child.as_rule() {
+            Rule::doc_line => lines.push(child.as_str().trim().to_string()),
+            Rule::kw_flow => header.push(child.as_str().trim().to_string()),
+            Rule::ident => {
+                header.push(child.as_str().trim().to_string());
+                lines.push(format!("{} {{", header.join(" ")));
+            }
+            _ => {
+                let start = child.as_span().start() - base;
+                if let Some(end) = prev_end {
+                    if source[end..start].matches('\n').count() >= 2 {
+                        lines.push(String::new());
+                    }
+                }
+                let text = child.as_str().trim();
+                for line in text.lines() {
+                    lines.push(format!("{}{}", indent, line.trim()));
+                }
+                prev_end = Some(start + text.len());
+            }
+        }
+    }
+
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Moves all imports into a single sorted block above the first statement.
+///
+/// Comments directly above an import move with it, except the file's leading
+/// comments and front matter, which stay on top. The merged block is always
+/// followed by a blank line.
+fn group_import_blocks(blocks: &mut Vec<Block>, import_indices: &[usize], content: &str) {
+    let Some(&first) = import_indices.first() else {
+        return;
+    };
+
+    let header_len = blocks.iter().take_while(|b| b.is_comment).count();
+    let mut moved = vec![false; blocks.len()];
+    let mut first_start = blocks[first].start_pos;
+
+    // Each import with its attached comments, keyed by the statement itself
+    let mut imports: Vec<(String, String)> = Vec::new();
+    for &i in import_indices {
+        let mut start = i;
+        while start > header_len
+            && blocks[start - 1].is_comment
+            && !moved[start - 1]
+            && content[blocks[start - 1].end_pos..blocks[start].start_pos]
+                .matches('\n')
+                .count()
+                < 2
+        {
+            start -= 1;
+        }
+        if i == first {
+            first_start = blocks[start].start_pos;
+        }
+        let lines: Vec<&str> = blocks[start..=i]
+            .iter()
+            .map(|b| b.content.as_str())
+            .collect();
+        imports.push((blocks[i].content.clone(), lines.join("\n")));
+        moved[start..=i].fill(true);
+    }
+    imports.sort_by(|a, b| a.0.cmp(&b.0));
+    imports.dedup_by(|a, b| a.0 == b.0);
+
+    let mut kept: Vec<Block> = std::mem::take(blocks)
+        .into_iter()
+        .zip(moved)
+        .filter_map(|(block, moved)| (!moved).then_some(block))
+        .collect();
+
+    let at = kept
+        .iter()
+        .position(|b| !b.is_comment)
+        .unwrap_or(kept.len());
+    let prev_end = at.checked_sub(1).map_or(0, |i| kept[i].end_pos);
+    let next_start = kept.get(at).map_or(content.len(), |b| b.start_pos);
+    // Keep the original spacing when the first import already sat here
+    let start_pos = if (prev_end..=next_start).contains(&first_start) {
+        first_start
+    } else {
+        next_start
+    };
+
+    let merged: Vec<String> = imports.into_iter().map(|(_, text)| text).collect();
+    kept.insert(
+        at,
+        Block {
+            content: merged.join("\n"),
+            start_pos,
+            end_pos: start_pos,
+            blank_line_after: true,
+            is_comment: false,
+        },
+    );
+    *blocks = kept;
+}
+
 /// Formats a list of tokens (e.g., in a function signature) into a standard string representation.
 ///
 /// Example: converts `[A, B, C]` or `A, B` into a comma-separated string `A, B, C`.
-fn format_token_list(pair: pest::iterators::Pair<Rule>) -> String {
-    pair.into_inner()
+fn format_token_list(
+    pair: pest::iterators::Pair<Rule>,
+    kind_ranks: Option<&HashMap<String, u8>>,
+) -> String {
+    format_tokens(pair, kind_ranks).join(", ")
+}
+
+/// Normalizes each token of a token list individually.
+///
+/// When `kind_ranks` is provided, the tokens are also sorted canonically.
+fn format_tokens(
+    pair: pest::iterators::Pair<Rule>,
+    kind_ranks: Option<&HashMap<String, u8>>,
+) -> Vec<String> {
+    let mut tokens: Vec<String> = pair
+        .into_inner()
         .map(|t| {
-            // t is Rule::token -> inner is Rule::collection or Rule::unitary
-            let inner = t.into_inner().next().unwrap();
+            // t is Rule::token -> inner is Rule::collection or Rule::unitary,
+            // optionally followed by Rule::optional
+            let mut parts = t.into_inner();
+            let inner = parts.next().unwrap();
+            let marker = if parts.next().is_some() { "?" } else { "" };
             match inner.as_rule() {
                 Rule::collection => {
                     let name = inner.into_inner().next().unwrap().as_str().trim();
-                    format!("[{}]", name)
+                    format!("[{}]{}", name, marker)
                 }
-                _ => inner.as_str().trim().to_string(),
+                _ => format!("{}{}", inner.as_str().trim(), marker),
             }
         })
-        .collect::<Vec<_>>()
-        .join(", ")
+        .collect();
+
+    if let Some(ranks) = kind_ranks {
+        tokens.sort_by_cached_key(|t| {
+            let name = t
+                .trim_end_matches('?')
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string();
+            (ranks.get(&name).copied().unwrap_or(UNKNOWN_KIND_RANK), name)
+        });
+    }
+    tokens
+}
+
+/// Sort rank for artifacts not defined in the formatted file (e.g. imported ones).
+const UNKNOWN_KIND_RANK: u8 = 3;
+
+/// Maps every artifact defined in the program to its sort rank:
+/// constants first, then variables, then errors.
+fn collect_kind_ranks(program: pest::iterators::Pair<Rule>) -> HashMap<String, u8> {
+    let mut ranks = HashMap::new();
+    for pair in program.into_inner() {
+        let rank = match pair.as_rule() {
+            Rule::const_def => 0,
+            Rule::var_def => 1,
+            Rule::err_def => 2,
+            _ => continue,
+        };
+        if let Some(name) = pair.into_inner().find(|p| p.as_rule() == Rule::ident) {
+            ranks.insert(name.as_str().to_string(), rank);
+        }
+    }
+    ranks
+}
+
+/// Renders output lines so that tokens at the same position share a column.
+///
+/// Every token except the last one of a line is padded to the widest token
+/// found at that position, keeping lines free of trailing whitespace.
+fn align_output_lines(outputs: &[(&str, Vec<String>)]) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for (_, tokens) in outputs {
+        for (i, token) in tokens.iter().enumerate() {
+            if i + 1 == tokens.len() {
+                break;
+            }
+            let len = token.chars().count() + 1; // Include the trailing comma
+            match widths.get_mut(i) {
+                Some(w) => *w = (*w).max(len),
+                None => widths.push(len),
+            }
+        }
+    }
+
+    outputs
+        .iter()
+        .map(|(symbol, tokens)| {
+            let mut line = format!("{} ", symbol);
+            for (i, token) in tokens.iter().enumerate() {
+                if i + 1 == tokens.len() {
+                    line.push_str(token);
+                } else {
+                    let cell = format!("{},", token);
+                    line.push_str(&format!("{:<width$} ", cell, width = widths[i]));
+                }
+            }
+            line
+        })
+        .collect()
 }
 
 /// Formats a function definition node.
@@ -111,8 +401,13 @@ fn format_token_list(pair: pest::iterators::Pair<Rule>) -> String {
 /// This rebuilds the function definition from its parts:
 /// 1. Documentation comments.
 /// 2. Header (Group, Function Keyword, Name, Input tokens).
-/// 3. Output sections (indented).
-fn format_function(pair: pest::iterators::Pair<Rule>) -> String {
+/// 3. Output sections (indented according to `options`).
+fn format_function(
+    pair: pest::iterators::Pair<Rule>,
+    options: &FormatOptions,
+    kind_ranks: Option<&HashMap<String, u8>>,
+) -> String {
+    let indent = options.indent_unit();
     let mut inner = pair.clone().into_inner();
     let mut parts = Vec::new();
     let mut last_inner_pos = None;
@@ -135,7 +430,7 @@ fn format_function(pair: pest::iterators::Pair<Rule>) -> String {
     let mut header = Vec::new();
     while let Some(p) = inner.peek() {
         if p.as_rule() == Rule::token_list {
-            header.push(format_token_list(inner.next().unwrap()));
+            header.push(format_token_list(inner.next().unwrap(), kind_ranks));
         } else if matches!(p.as_rule(), Rule::ident | Rule::kw_function) {
             header.push(inner.next().unwrap().as_str().trim().to_string());
         } else {
@@ -146,22 +441,43 @@ fn format_function(pair: pest::iterators::Pair<Rule>) -> String {
         parts.push(header.join(" "));
     }
 
-    // 3. Extract Outputs (Indented)
+    // 3. Extract Outputs (Indented), keeping comments between branches
+    // as (index of the following output, text)
+    let mut outputs = Vec::new();
+    let mut comments = Vec::new();
     if let Some(p) = inner.next() {
         for child in p.into_inner() {
-            if child.as_rule() == Rule::output_line {
+            if child.as_rule() == Rule::comment {
+                comments.push((outputs.len(), child.as_str().trim().to_string()));
+            } else if child.as_rule() == Rule::output_line {
                 let raw = child.as_str().trim();
                 let symbol = if raw.starts_with('>') { ">" } else { "|" };
                 let mut output_parts = child.into_inner();
                 // Token list
-                let tokens = output_parts
-                    .next()
-                    .map(format_token_list)
-                    .unwrap_or_default();
-                parts.push(format!("    {} {}", symbol, tokens));
+                let tokens = match output_parts.next() {
+                    Some(none) if none.as_rule() == Rule::none_output => vec!["None".to_string()],
+                    Some(list) => format_tokens(list, kind_ranks),
+                    None => Vec::new(),
+                };
+                outputs.push((symbol, tokens));
             }
         }
     }
 
+    let lines: Vec<String> = if options.align_outputs {
+        align_output_lines(&outputs)
+    } else {
+        outputs
+            .iter()
+            .map(|(symbol, tokens)| format!("{} {}", symbol, tokens.join(", ")))
+            .collect()
+    };
+    for (i, line) in lines.iter().enumerate() {
+        for (_, comment) in comments.iter().filter(|(at, _)| *at == i) {
+            parts.push(format!("{}{}", indent, comment));
+        }
+        parts.push(format!("{}{}", indent, line));
+    }
+
     parts.join("\n")
 }
@@ -5,18 +5,23 @@
 //!
 //! Acts as the controller for the [Workspace], [Analyzer], and [Engine].
 
-use crate::analyzer::Workspace;
-use crate::engine::Flow;
+use crate::analyzer::{parse_program, Rule, Workspace};
+use crate::assertions::evaluate_assertions;
+use crate::engine::{Flow, FlowConfig};
+use crate::export::theme::ThemeConfig;
 use crate::export::vis_js::VisData;
-use crate::export::{dot, mermaid, tikz, vis_js};
-use crate::formatter::format_tect_source;
-use crate::models::{Cardinality, Function, Graph, Kind, ProgramStructure, SymbolMetadata, Token};
+use crate::export::{dot, mermaid, tikz, vis_js, ExportOptions};
+use crate::formatter::{format_tect_source, FormatOptions};
+use crate::models::{Function, Graph, Kind, ProgramStructure, SymbolMetadata};
+use pest::iterators::Pair;
 use regex::Regex;
 use serde_json::Value;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
 use tower_lsp::jsonrpc::{Error as LspError, Result as LspResult};
 use tower_lsp::lsp_types::notification::Notification;
 use tower_lsp::lsp_types::*;
@@ -29,30 +34,102 @@ impl Notification for AnalysisFinished {
     const METHOD: &'static str = "tect/analysisFinished";
 }
 
+/// Quiet period after an edit before the document is re-analyzed.
+/// Edits arriving within it supersede the pending analysis.
+pub const DIAGNOSTICS_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Token types advertised in the semantic tokens legend.
+/// The position of each entry is the index sent to the client.
+pub const SEMANTIC_TOKEN_TYPES: [SemanticTokenType; 7] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::STRING,
+    SemanticTokenType::DECORATOR,
+];
+
+const TOKEN_KEYWORD: u32 = 0;
+const TOKEN_FUNCTION: u32 = 1;
+const TOKEN_TYPE: u32 = 2;
+const TOKEN_NAMESPACE: u32 = 3;
+const TOKEN_COMMENT: u32 = 4;
+const TOKEN_STRING: u32 = 5;
+const TOKEN_DECORATOR: u32 = 6;
+
+/// Semantic tokens sent for a file, keyed by their result id.
+type SemanticSnapshot = (u64, Vec<SemanticToken>);
+
 /// The Backend holds the workspace state protected by a Mutex.
+///
+/// Cloning is cheap and shares the state, so background tasks can own a handle.
+#[derive(Clone)]
 pub struct Backend {
     /// The LSP client handle for sending notifications and requests.
     pub client: Client,
     /// The shared workspace state, protected by a mutex for thread safety.
-    pub workspace: Mutex<Workspace>,
-    /// Tracks which files are currently open in the editor (managed by didOpen/didClose).
-    pub open_documents: Mutex<HashSet<Url>>,
+    pub workspace: Arc<Mutex<Workspace>>,
+    /// Tracks which files are currently open in the editor and their latest version
+    /// (managed by didOpen/didChange/didClose).
+    pub open_documents: Arc<Mutex<HashMap<Url, i32>>>,
     /// Caches the hash of the last successfully simulated graph per file.
     /// Used to suppress unnecessary UI updates unless the graph structure actually changes.
-    pub graph_cache: Mutex<HashMap<Url, u64>>,
+    pub graph_cache: Arc<Mutex<HashMap<Url, u64>>>,
+    /// Last semantic tokens sent per file, with their result id,
+    /// so `semanticTokens/full/delta` can answer with edits only.
+    pub semantic_cache: Arc<Mutex<HashMap<Url, SemanticSnapshot>>>,
+    /// Debounced analyses waiting to run after an edit, one per file.
+    pub pending_analyses: Arc<Mutex<HashMap<Url, JoinHandle<()>>>>,
 }
 
 impl Backend {
+    pub fn new(client: Client) -> Self {
+        Backend {
+            client,
+            workspace: Arc::new(Mutex::new(Workspace::new())),
+            open_documents: Arc::default(),
+            graph_cache: Arc::default(),
+            semantic_cache: Arc::default(),
+            pending_analyses: Arc::default(),
+        }
+    }
+
     fn compute_graph_hash(graph: &Graph) -> u64 {
         let mut s = DefaultHasher::new();
         graph.hash(&mut s);
         s.finish()
     }
+
+    /// Returns the latest version reported by the editor for an open document.
+    fn document_version(&self, uri: &Url) -> Option<i32> {
+        self.open_documents.lock().unwrap().get(uri).copied()
+    }
+
+    /// Fails with `ContentModified` if the document changed after `version` was read,
+    /// so the client drops results computed from stale content.
+    fn ensure_current(&self, uri: &Url, version: Option<i32>) -> LspResult<()> {
+        if self.document_version(uri) == version {
+            Ok(())
+        } else {
+            Err(LspError::content_modified())
+        }
+    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> LspResult<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> LspResult<InitializeResult> {
+        // Files without a `tect.toml` are contained in the opened folder
+        #[allow(deprecated)]
+        let folder = params
+            .workspace_folders
+            .and_then(|folders| folders.into_iter().next())
+            .map(|folder| folder.uri)
+            .or(params.root_uri);
+        self.workspace.lock().unwrap().default_root =
+            folder.and_then(|uri| uri.to_file_path().ok());
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 // We support full text sync to ensure the server always has the complete file content.
@@ -63,6 +140,11 @@ impl LanguageServer for Backend {
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 // Go to definition
                 definition_provider: Some(OneOf::Left(true)),
+                // Clickable import paths
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(false),
+                    work_done_progress_options: Default::default(),
+                }),
                 // Formatting
                 document_formatting_provider: Some(OneOf::Left(true)),
                 // Outline/Structure
@@ -83,6 +165,19 @@ impl LanguageServer for Backend {
                 }),
                 // Inlay hints (type annotations)
                 inlay_hint_provider: Some(OneOf::Left(true)),
+                // Syntax highlighting
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: SemanticTokensLegend {
+                                token_types: SEMANTIC_TOKEN_TYPES.to_vec(),
+                                token_modifiers: vec![],
+                            },
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                            ..Default::default()
+                        },
+                    ),
+                ),
                 ..Default::default()
             },
             ..Default::default()
@@ -92,8 +187,10 @@ impl LanguageServer for Backend {
     async fn did_open(&self, p: DidOpenTextDocumentParams) {
         {
             let mut docs = self.open_documents.lock().unwrap();
-            docs.insert(p.text_document.uri.clone());
+            docs.insert(p.text_document.uri.clone(), p.text_document.version);
         }
+        // Make definitions from sibling files resolvable without opening them
+        self.index_directory(&p.text_document.uri).await;
         self.process_change(p.text_document.uri, Some(p.text_document.text))
             .await;
     }
@@ -103,17 +200,53 @@ impl LanguageServer for Backend {
             let mut docs = self.open_documents.lock().unwrap();
             docs.remove(&p.text_document.uri);
         }
+        if let Some(task) = self
+            .pending_analyses
+            .lock()
+            .unwrap()
+            .remove(&p.text_document.uri)
+        {
+            task.abort();
+        }
+        self.semantic_cache
+            .lock()
+            .unwrap()
+            .remove(&p.text_document.uri);
     }
 
     async fn did_change(&self, p: DidChangeTextDocumentParams) {
-        if let Some(c) = p.content_changes.into_iter().last() {
-            self.process_change(p.text_document.uri, Some(c.text)).await;
+        let uri = p.text_document.uri;
+        let version = p.text_document.version;
+        {
+            let mut docs = self.open_documents.lock().unwrap();
+            docs.insert(uri.clone(), version);
+        }
+        let Some(c) = p.content_changes.into_iter().last() else {
+            return;
+        };
+        // Requests see the new text right away; only the full analysis waits
+        {
+            let mut ws = self.workspace.lock().unwrap();
+            let id = ws.source_manager.get_id(&uri);
+            ws.source_manager.load_file(id, Some(c.text));
+        }
+
+        // The analysis runs in the background; a later edit aborts it
+        let backend = self.clone();
+        let target = uri.clone();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(DIAGNOSTICS_DEBOUNCE).await;
+            backend.process_change(target, None).await;
+        });
+        if let Some(previous) = self.pending_analyses.lock().unwrap().insert(uri, task) {
+            previous.abort();
         }
     }
 
     async fn hover(&self, p: HoverParams) -> LspResult<Option<Hover>> {
         let uri = p.text_document_position_params.text_document.uri;
         let pos = p.text_document_position_params.position;
+        let version = self.document_version(&uri);
 
         let mut ws = self.workspace.lock().unwrap();
 
@@ -144,6 +277,12 @@ impl LanguageServer for Backend {
                     "Defines an architectural contract with specific inputs and result branches.",
                 ),
                 "import" => Some("Imports definitions from another Tect file."),
+                "flow" => {
+                    Some("Defines a named flow: an entry point with its own sequence of steps.")
+                }
+                "assert" => Some(
+                    "Declares an architectural invariant, checked against the simulated graph.",
+                ),
                 _ => None,
             };
 
@@ -159,14 +298,22 @@ impl LanguageServer for Backend {
 
             // Lookup symbol documentation
             let markdown = if let Some(kind) = ws.structure.artifacts.get(&word) {
+                let tags = if kind.tags().is_empty() {
+                    String::new()
+                } else {
+                    let tags: Vec<String> =
+                        kind.tags().iter().map(|t| format!("`@{}`", t)).collect();
+                    format!("**Tags**: {}\n\n", tags.join(" "))
+                };
                 format!(
-                    "### {}: `{}`\n\n---\n\n{}",
+                    "### {}: `{}`\n\n{}---\n\n{}",
                     match kind {
                         Kind::Constant(_) => "Constant",
                         Kind::Variable(_) => "Variable",
                         Kind::Error(_) => "Error",
                     },
                     word,
+                    tags,
                     kind.docs().unwrap_or("*No documentation.*")
                 )
             } else if let Some(f) = ws.structure.catalog.get(&word) {
@@ -175,7 +322,7 @@ impl LanguageServer for Backend {
                     .as_ref()
                     .map(|g| format!("**Group**: `{}`\n\n", g.name))
                     .unwrap_or_default();
-                let signature = format!("**Signature**: `{}`\n\n", Self::format_signature(f));
+                let signature = format!("**Signature**: `{}`\n\n", f.signature());
                 format!(
                     "### Function: `{}`\n\n{}{}---\n\n{}",
                     word,
@@ -183,18 +330,13 @@ impl LanguageServer for Backend {
                     signature,
                     f.documentation.as_deref().unwrap_or("*No documentation.*")
                 )
-            } else if let Some(g) = ws.structure.groups.get(&word) {
-                format!(
-                    "### Group: `{}`\n\n---\n\n{}",
-                    word,
-                    g.documentation
-                        .as_deref()
-                        .unwrap_or("*Architectural group.*")
-                )
+            } else if let Some(markdown) = Self::group_hover(&word, &ws.structure) {
+                markdown
             } else {
                 return Ok(None);
             };
 
+            self.ensure_current(&uri, version)?;
             return Ok(Some(Hover {
                 contents: HoverContents::Markup(MarkupContent {
                     kind: MarkupKind::Markdown,
@@ -219,21 +361,21 @@ impl LanguageServer for Backend {
             ws_guard.analyze(uri.clone(), None);
         }
 
-        let Workspace {
-            ref structure,
-            ref mut source_manager,
-            ..
-        } = *ws_guard;
-
-        let file_id = source_manager.get_id(&uri);
-        source_manager.load_file(file_id, None);
+        let file_id = ws_guard.source_manager.get_id(&uri);
+        ws_guard.source_manager.load_file(file_id, None);
 
-        let Some(content) = source_manager.get_content(file_id).map(|s| s.to_string()) else {
+        let Some(content) = ws_guard
+            .source_manager
+            .get_content(file_id)
+            .map(|s| s.to_string())
+        else {
             return Ok(None);
         };
 
         // Check for import path first
-        if let Some((target_uri, origin_range)) = Self::check_import_at(&content, pos, &uri) {
+        if let Some((target_uri, origin_range)) =
+            Self::check_import_at(&mut ws_guard, &content, pos, &uri)
+        {
             let target_range = Range::new(Position::new(0, 0), Position::new(0, 0));
             return Ok(Some(GotoDefinitionResponse::Link(vec![LocationLink {
                 origin_selection_range: Some(origin_range),
@@ -243,6 +385,12 @@ impl LanguageServer for Backend {
             }])));
         }
 
+        let Workspace {
+            ref structure,
+            ref mut source_manager,
+            ..
+        } = *ws_guard;
+
         // Check for symbols
         if let Some((word, _)) = Self::get_word_at(&content, pos) {
             if let Some(meta) = self.find_meta(&word, structure) {
@@ -260,6 +408,23 @@ impl LanguageServer for Backend {
         Ok(None)
     }
 
+    async fn document_link(&self, p: DocumentLinkParams) -> LspResult<Option<Vec<DocumentLink>>> {
+        let mut ws = self.workspace.lock().unwrap();
+        let uri = p.text_document.uri;
+        let file_id = ws.source_manager.get_id(&uri);
+
+        ws.source_manager.load_file(file_id, None);
+
+        let Some(content) = ws
+            .source_manager
+            .get_content(file_id)
+            .map(|s| s.to_string())
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self::import_links(&mut ws, &content, &uri)))
+    }
+
     async fn document_symbol(
         &self,
         p: DocumentSymbolParams,
@@ -447,7 +612,7 @@ impl LanguageServer for Backend {
 
         if let Some((word, _)) = Self::get_word_at(&content, pos) {
             if let Some(f) = ws.structure.catalog.get(&word) {
-                let sig = format!("{}: {}", f.name, Self::format_signature(f));
+                let sig = format!("{}: {}", f.name, f.signature());
                 return Ok(Some(SignatureHelp {
                     signatures: vec![SignatureInformation {
                         label: sig,
@@ -465,6 +630,7 @@ impl LanguageServer for Backend {
 
     async fn inlay_hint(&self, p: InlayHintParams) -> LspResult<Option<Vec<InlayHint>>> {
         let uri = p.text_document.uri;
+        let version = self.document_version(&uri);
 
         let mut ws_guard = self.workspace.lock().unwrap();
 
@@ -482,11 +648,11 @@ impl LanguageServer for Backend {
         source_manager.load_file(file_id, None);
 
         let mut hints = Vec::new();
-        for step in &structure.flow {
+        for step in structure.all_steps() {
             if step.span.file_id == file_id {
                 if let Some(f) = structure.catalog.get(&step.function_name) {
                     let range = source_manager.resolve_range(step.span);
-                    let signature = Self::format_signature(f);
+                    let signature = f.signature();
                     let label = if let Some(ref g) = f.group {
                         format!("{} {}", g.name, signature)
                     } else {
@@ -506,9 +672,63 @@ impl LanguageServer for Backend {
                 }
             }
         }
+        self.ensure_current(&uri, version)?;
         Ok(Some(hints))
     }
 
+    async fn semantic_tokens_full(
+        &self,
+        p: SemanticTokensParams,
+    ) -> LspResult<Option<SemanticTokensResult>> {
+        let uri = p.text_document.uri;
+        let version = self.document_version(&uri);
+
+        let Some(data) = self.current_semantic_tokens(&uri) else {
+            return Ok(None);
+        };
+
+        self.ensure_current(&uri, version)?;
+        let result_id = self.cache_semantic_tokens(&uri, data.clone());
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: Some(result_id),
+            data,
+        })))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        p: SemanticTokensDeltaParams,
+    ) -> LspResult<Option<SemanticTokensFullDeltaResult>> {
+        let uri = p.text_document.uri;
+        let version = self.document_version(&uri);
+
+        let Some(data) = self.current_semantic_tokens(&uri) else {
+            return Ok(None);
+        };
+        self.ensure_current(&uri, version)?;
+
+        let previous = {
+            let cache = self.semantic_cache.lock().unwrap();
+            cache
+                .get(&uri)
+                .filter(|(id, _)| id.to_string() == p.previous_result_id)
+                .map(|(_, tokens)| tokens.clone())
+        };
+        let result_id = self.cache_semantic_tokens(&uri, data.clone());
+
+        // Unknown previous result: the client gets the full set instead
+        Ok(Some(match previous {
+            Some(previous) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: Some(result_id),
+                edits: Self::semantic_tokens_edits(&previous, &data),
+            }),
+            None => SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+                result_id: Some(result_id),
+                data,
+            }),
+        }))
+    }
+
     async fn formatting(&self, p: DocumentFormattingParams) -> LspResult<Option<Vec<TextEdit>>> {
         let mut ws = self.workspace.lock().unwrap();
         let uri = p.text_document.uri;
@@ -516,8 +736,15 @@ impl LanguageServer for Backend {
 
         ws.source_manager.load_file(file_id, None);
 
+        // Honor the editor's indentation preferences
+        let options = FormatOptions {
+            indent: p.options.tab_size as usize,
+            use_tabs: !p.options.insert_spaces,
+            ..FormatOptions::default()
+        };
+
         if let Some(content) = ws.source_manager.get_content(file_id) {
-            if let Some(formatted) = format_tect_source(content) {
+            if let Some(formatted) = format_tect_source(content, &options) {
                 let full_range = Range::new(Position::new(0, 0), Position::new(u32::MAX, u32::MAX));
                 return Ok(Some(vec![TextEdit::new(full_range, formatted)]));
             }
@@ -544,10 +771,14 @@ impl Backend {
         let mut ws = self.workspace.lock().unwrap();
         ws.analyze(uri, None);
 
-        let mut flow = Flow::new(true);
+        let mut flow = Flow::with_config(FlowConfig::default());
         let graph = flow.simulate(&ws.structure);
 
-        Ok(vis_js::produce_vis_data(&graph))
+        let options = ExportOptions {
+            theme: ThemeConfig::default().or_front_matter(&ws.structure),
+            ..ExportOptions::default()
+        };
+        Ok(vis_js::produce_vis_data(&graph, &options))
     }
 
     /// Handler for `tect/exportGraph`. Returns the graph in various string formats.
@@ -568,30 +799,63 @@ impl Backend {
         let mut ws = self.workspace.lock().unwrap();
         ws.analyze(uri, None);
 
-        let mut flow = Flow::new(true);
-        let graph = flow.simulate(&ws.structure);
+        let mut flow = Flow::with_config(FlowConfig::default());
+        let mut graph = flow.simulate(&ws.structure);
+        ws.attach_source_files(&mut graph);
+
+        let options = ExportOptions {
+            theme: ThemeConfig::default().or_front_matter(&ws.structure),
+            ..ExportOptions::default()
+        };
 
         match format {
-            "dot" => Ok(dot::export(&graph)),
-            "mermaid" => Ok(mermaid::export(&graph)),
-            "tex" => Ok(tikz::export(&graph)),
+            "dot" => Ok(dot::export(&graph, &options)),
+            "mermaid" => Ok(mermaid::export(&graph, &options)),
+            "tex" => Ok(tikz::export(&graph, &options)),
             "json" => Ok(
                 serde_json::to_string_pretty(&graph).map_err(|_e| LspError::internal_error())?
             ),
-            "html" => Ok(vis_js::generate_interactive_html(&graph)),
+            "html" => Ok(vis_js::generate_interactive_html(&graph, &options)),
             _ => Err(LspError::invalid_params("Unknown format")),
         }
     }
 
     async fn process_change(&self, changed_uri: Url, content: Option<String>) {
-        let open_docs: Vec<Url> = {
+        let (diagnostics, graph_to_notify) = self.analyze_documents(&changed_uri, content);
+        for params in diagnostics {
+            self.client
+                .publish_diagnostics(params.uri, params.diagnostics, params.version)
+                .await;
+        }
+
+        if let Some(uri) = graph_to_notify {
+            self.client
+                .send_notification::<AnalysisFinished>(
+                    serde_json::json!({ "uri": uri.to_string() }),
+                )
+                .await;
+        }
+    }
+
+    /// Re-analyzes every open document after `changed_uri` changed.
+    ///
+    /// Returns the diagnostics grouped per file, each with the version it was
+    /// computed from, and the changed document if its graph changed.
+    pub fn analyze_documents(
+        &self,
+        changed_uri: &Url,
+        content: Option<String>,
+    ) -> (Vec<PublishDiagnosticsParams>, Option<Url>) {
+        // Snapshot the versions being analyzed so the editor can discard
+        // diagnostics that arrive after a newer edit.
+        let (open_docs, versions): (Vec<Url>, HashMap<Url, i32>) = {
             let docs = self.open_documents.lock().unwrap();
-            let mut list: Vec<Url> = docs.iter().cloned().collect();
-            if let Some(pos) = list.iter().position(|u| u == &changed_uri) {
+            let mut list: Vec<Url> = docs.keys().cloned().collect();
+            if let Some(pos) = list.iter().position(|u| u == changed_uri) {
                 list.remove(pos);
             }
             list.push(changed_uri.clone());
-            list
+            (list, docs.clone())
         };
 
         let mut all_file_diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
@@ -605,7 +869,7 @@ impl Backend {
             let mut ws_guard = self.workspace.lock().unwrap();
 
             if let Some(c) = content {
-                let id = ws_guard.source_manager.get_id(&changed_uri);
+                let id = ws_guard.source_manager.get_id(changed_uri);
                 ws_guard.source_manager.load_file(id, Some(c));
             }
 
@@ -619,14 +883,16 @@ impl Backend {
                     .any(|d| d.severity == DiagnosticSeverity::ERROR);
 
                 if !has_errors {
-                    let mut flow = Flow::new(true);
+                    let mut flow = Flow::with_config(FlowConfig::default());
                     let graph = flow.simulate(&ws_guard.structure);
                     ws_guard.structure.diagnostics.extend(flow.diagnostics);
+                    let failed = evaluate_assertions(&graph, &ws_guard.structure.assertions);
+                    ws_guard.structure.diagnostics.extend(failed);
 
                     // --- Differential Graph Check ---
                     // Only for the actively edited file do we care about notifying the graph
                     // This mechanism prevents re-rendering the UI graph if logic hasn't changed.
-                    if doc_uri == changed_uri {
+                    if doc_uri == *changed_uri {
                         let new_hash = Self::compute_graph_hash(&graph);
                         let mut cache = self.graph_cache.lock().unwrap();
                         let last_hash = cache.get(&doc_uri).cloned().unwrap_or(0);
@@ -641,6 +907,9 @@ impl Backend {
                 let current_diagnostics = ws_guard.structure.diagnostics.clone();
 
                 for diag_ctx in current_diagnostics {
+                    if ws_guard.source_manager.is_suppressed(&diag_ctx) {
+                        continue;
+                    }
                     if let Some(uri) = ws_guard.source_manager.get_uri(diag_ctx.file_id).cloned() {
                         let range = if let Some(span) = diag_ctx.span {
                             ws_guard.source_manager.resolve_range(span)
@@ -653,6 +922,7 @@ impl Backend {
                             severity: Some(diag_ctx.severity),
                             message: diag_ctx.message.clone(),
                             source: Some("tect".into()),
+                            code: diag_ctx.code.map(|c| NumberOrString::String(c.into())),
                             tags: if diag_ctx.tags.is_empty() {
                                 None
                             } else {
@@ -670,17 +940,71 @@ impl Backend {
             }
         }
 
-        for (furi, diags) in all_file_diagnostics {
-            self.client.publish_diagnostics(furi, diags, None).await;
-        }
+        // Diagnostics are grouped per file; files that are not open (e.g. imports) have no version.
+        let mut diagnostics: Vec<PublishDiagnosticsParams> = all_file_diagnostics
+            .into_iter()
+            .map(|(uri, diags)| {
+                let version = versions.get(&uri).copied();
+                PublishDiagnosticsParams::new(uri, diags, version)
+            })
+            .collect();
+        diagnostics.sort_by(|a, b| a.uri.cmp(&b.uri));
+        (diagnostics, graph_to_notify)
+    }
 
-        if let Some(uri) = graph_to_notify {
-            self.client
-                .send_notification::<AnalysisFinished>(
-                    serde_json::json!({ "uri": uri.to_string() }),
-                )
-                .await;
+    /// Indexes the directory of `uri`, reporting `$/progress` to the client.
+    ///
+    /// Progress is forwarded by a separate task while the workspace is locked,
+    /// and skipped if the client does not accept a progress token.
+    async fn index_directory(&self, uri: &Url) {
+        if !self.workspace.lock().unwrap().needs_indexing(uri) {
+            return;
+        }
+        let token = NumberOrString::String(format!("tect/index/{}", uri));
+        let created = self
+            .client
+            .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .is_ok();
+        if !created {
+            self.workspace.lock().unwrap().index_directory(uri);
+            return;
         }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let client = self.client.clone();
+        let progress_token = token.clone();
+        let forwarder = tokio::spawn(async move {
+            while let Some(progress) = rx.recv().await {
+                client
+                    .send_notification::<notification::Progress>(ProgressParams {
+                        token: progress_token.clone(),
+                        value: ProgressParamsValue::WorkDone(progress),
+                    })
+                    .await;
+            }
+        });
+
+        let _ = tx.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: "Indexing Tect files".to_string(),
+            percentage: Some(0),
+            ..Default::default()
+        }));
+        self.workspace
+            .lock()
+            .unwrap()
+            .index_directory_with_progress(uri, |done, total| {
+                let _ = tx.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+                    message: Some(format!("{}/{}", done, total)),
+                    percentage: Some((done * 100 / total) as u32),
+                    ..Default::default()
+                }));
+            });
+        let _ = tx.send(WorkDoneProgress::End(WorkDoneProgressEnd::default()));
+        drop(tx);
+        let _ = forwarder.await;
     }
 
     fn find_meta<'a>(
@@ -713,57 +1037,60 @@ impl Backend {
         }
     }
 
-    fn check_import_at(content: &str, pos: Position, base_uri: &Url) -> Option<(Url, Range)> {
-        let line_str = content.lines().nth(pos.line as usize)?;
-
-        // Regex to match: import "path"
-        // Captures: 1 = quotes+path, 2 = path
-        let re = Regex::new(r#"^\s*import\s+("([^"]+)")"#).ok()?;
-
-        if let Some(cap) = re.captures(line_str) {
-            let full_match = cap.get(1)?; // "path"
-            let path_match = cap.get(2)?; // path
-
-            let match_start = full_match.start();
-            let match_end = full_match.end();
-
-            // Calculate byte offset of cursor in line
-            let mut byte_offset = 0;
-            for (i, c) in line_str.chars().enumerate() {
-                if i == pos.character as usize {
-                    break;
-                }
-                byte_offset += c.len_utf8();
-            }
-
-            // Check if cursor is strictly inside quotes
-            if byte_offset > match_start && byte_offset < match_end {
-                let rel_path = path_match.as_str();
-
-                // Compute Range of the inner string
-                let inner_start_byte = path_match.start();
-                let inner_end_byte = path_match.end();
+    fn check_import_at(
+        ws: &mut Workspace,
+        content: &str,
+        pos: Position,
+        base_uri: &Url,
+    ) -> Option<(Url, Range)> {
+        Self::import_links(ws, content, base_uri)
+            .into_iter()
+            .find(|link| {
+                link.range.start.line == pos.line
+                    && (link.range.start.character..=link.range.end.character)
+                        .contains(&pos.character)
+            })
+            .and_then(|link| Some((link.target?, link.range)))
+    }
 
-                // Convert byte offsets to UTF-16 character columns for LSP
-                let start_col = line_str[..inner_start_byte].encode_utf16().count() as u32;
-                let end_col = line_str[..inner_end_byte].encode_utf16().count() as u32;
+    /// Links the path of every `import "path"` statement to the imported file,
+    /// resolved like the analyzer does (see [`Workspace::resolve_import`]).
+    /// Imports that do not resolve get no link.
+    pub(crate) fn import_links(
+        ws: &mut Workspace,
+        content: &str,
+        base_uri: &Url,
+    ) -> Vec<DocumentLink> {
+        // Captures: 1 = path
+        let re = Regex::new(r#"^\s*import\s+"([^"]+)""#).unwrap();
+
+        let mut links = Vec::new();
+        for (line, line_str) in content.lines().enumerate() {
+            let Some(path_match) = re.captures(line_str).and_then(|cap| cap.get(1)) else {
+                continue;
+            };
+            let Some(target) = ws.resolve_import(base_uri, path_match.as_str()) else {
+                continue;
+            };
 
-                let range = Range::new(
-                    Position::new(pos.line, start_col),
-                    Position::new(pos.line, end_col),
-                );
+            // Convert byte offsets to UTF-16 character columns for LSP
+            let start_col = line_str[..path_match.start()].encode_utf16().count() as u32;
+            let end_col = line_str[..path_match.end()].encode_utf16().count() as u32;
 
-                // Resolve relative to base_uri
-                // The LSP client will handle the "goto" effectively if we provide a valid link
-                if let Ok(target) = base_uri.join(rel_path) {
-                    return Some((target, range));
-                }
-            }
+            links.push(DocumentLink {
+                range: Range::new(
+                    Position::new(line as u32, start_col),
+                    Position::new(line as u32, end_col),
+                ),
+                target: Some(target),
+                tooltip: Some(format!("Open {}", path_match.as_str())),
+                data: None,
+            });
         }
-        None
+        links
     }
 
-    fn get_word_at(content: &str, pos: Position) -> Option<(String, Range)> {
+    pub(crate) fn get_word_at(content: &str, pos: Position) -> Option<(String, Range)> {
         let line_str = content.lines().nth(pos.line as usize)?;
         let mut u16_off = 0;
         let mut b_off = 0;
@@ -774,13 +1101,16 @@ impl Backend {
             u16_off += c.len_utf16();
             b_off += c.len_utf8();
         }
-        let re = Regex::new(r"([a-zA-Z0-9_]+)").unwrap();
-        for cap in re.find_iter(line_str) {
+        // Quoted names (`"Load Config"`) are one word, without their quotes
+        let re = Regex::new(r#""([^"]+)"|([\p{L}\p{N}_]+)"#).unwrap();
+        for caps in re.captures_iter(line_str) {
+            let cap = caps.get(0)?;
             if b_off >= cap.start() && b_off <= cap.end() {
                 let s_u16 = line_str[..cap.start()].encode_utf16().count() as u32;
                 let e_u16 = line_str[..cap.end()].encode_utf16().count() as u32;
+                let word = caps.get(1).or(caps.get(2))?;
                 return Some((
-                    cap.as_str().to_string(),
+                    word.as_str().to_string(),
                     Range::new(
                         Position::new(pos.line, s_u16),
                         Position::new(pos.line, e_u16),
@@ -791,38 +1121,210 @@ impl Backend {
         None
     }
 
-    fn format_signature(f: &Function) -> String {
-        let mut inputs = f
-            .consumes
-            .iter()
-            .map(Self::format_token)
-            .collect::<Vec<_>>()
-            .join(", ");
-        let mut outputs = f
-            .produces
-            .iter()
-            .map(|branch| {
-                branch
-                    .iter()
-                    .map(Self::format_token)
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            })
-            .collect::<Vec<_>>()
-            .join(" | ");
-        if inputs.is_empty() {
-            inputs = "()".to_string();
+    /// Classifies the source into delta-encoded semantic tokens.
+    ///
+    /// Statements around syntax errors are still classified.
+    pub(crate) fn semantic_tokens(content: &str) -> Option<Vec<SemanticToken>> {
+        let program = parse_program(content).0?;
+        let mut spans = Vec::new();
+        Self::classify_pair(program, &mut spans);
+        spans.sort_by_key(|(start, _, _)| *start);
+
+        let mut tokens = Vec::with_capacity(spans.len());
+        let (mut prev_line, mut prev_col) = (0, 0);
+        for (start, end, token_type) in spans {
+            let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line = content[..start].matches('\n').count() as u32;
+            let col = content[line_start..start].encode_utf16().count() as u32;
+            let length = content[start..end].encode_utf16().count() as u32;
+
+            let delta_start = if line == prev_line {
+                col - prev_col
+            } else {
+                col
+            };
+            tokens.push(SemanticToken {
+                delta_line: line - prev_line,
+                delta_start,
+                length,
+                token_type,
+                token_modifiers_bitset: 0,
+            });
+            (prev_line, prev_col) = (line, col);
         }
-        if outputs.is_empty() {
-            outputs = "()".to_string();
+        Some(tokens)
+    }
+
+    /// Computes the semantic tokens of a file from its current content.
+    fn current_semantic_tokens(&self, uri: &Url) -> Option<Vec<SemanticToken>> {
+        let mut ws = self.workspace.lock().unwrap();
+        let file_id = ws.source_manager.get_id(uri);
+        ws.source_manager.load_file(file_id, None);
+        ws.source_manager
+            .get_content(file_id)
+            .and_then(Self::semantic_tokens)
+    }
+
+    /// Stores the tokens sent for a file and returns their new result id.
+    fn cache_semantic_tokens(&self, uri: &Url, data: Vec<SemanticToken>) -> String {
+        let mut cache = self.semantic_cache.lock().unwrap();
+        let id = cache.get(uri).map_or(1, |(id, _)| id + 1);
+        cache.insert(uri.clone(), (id, data));
+        id.to_string()
+    }
+
+    /// Describes the change from `old` to `new` as a single edit replacing
+    /// everything between their common prefix and suffix.
+    ///
+    /// Edit offsets count integers, five per token, as the protocol requires.
+    pub(crate) fn semantic_tokens_edits(
+        old: &[SemanticToken],
+        new: &[SemanticToken],
+    ) -> Vec<SemanticTokensEdit> {
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let removed = old.len() - prefix - suffix;
+        let inserted = &new[prefix..new.len() - suffix];
+        if removed == 0 && inserted.is_empty() {
+            return vec![];
         }
-        format!("{} -> {}", inputs, outputs)
+        vec![SemanticTokensEdit {
+            start: (prefix * 5) as u32,
+            delete_count: (removed * 5) as u32,
+            data: (!inserted.is_empty()).then(|| inserted.to_vec()),
+        }]
     }
 
-    fn format_token(t: &Token) -> String {
-        match t.cardinality {
-            Cardinality::Collection => format!("[{}]", t.kind.name()),
-            Cardinality::Unitary => t.kind.name().to_string(),
+    /// Collects `(start, end, token_type)` byte spans for a parse tree node.
+    fn classify_pair(pair: Pair<Rule>, out: &mut Vec<(usize, usize, u32)>) {
+        let span = pair.as_span();
+        let mut push = |token_type| out.push((span.start(), span.end(), token_type));
+        match pair.as_rule() {
+            Rule::kw_constant
+            | Rule::kw_variable
+            | Rule::kw_error
+            | Rule::kw_group
+            | Rule::kw_function
+            | Rule::kw_import
+            | Rule::kw_flow
+            | Rule::kw_assert
+            | Rule::reachable
+            | Rule::no
+            | Rule::unhandled
+            | Rule::none_output => push(TOKEN_KEYWORD),
+            Rule::comment => push(TOKEN_COMMENT),
+            Rule::string_literal => push(TOKEN_STRING),
+            Rule::tag => push(TOKEN_DECORATOR),
+            Rule::flow_step | Rule::assert_reachable => {
+                for child in pair.into_inner() {
+                    if child.as_rule() == Rule::ident {
+                        let s = child.as_span();
+                        out.push((s.start(), s.end(), TOKEN_FUNCTION));
+                    } else {
+                        Self::classify_pair(child, out);
+                    }
+                }
+            }
+            Rule::unitary => push(TOKEN_TYPE),
+            Rule::assert_handled => {
+                for child in pair.into_inner() {
+                    if child.as_rule() == Rule::ident {
+                        let s = child.as_span();
+                        out.push((s.start(), s.end(), TOKEN_TYPE));
+                    } else {
+                        Self::classify_pair(child, out);
+                    }
+                }
+            }
+            Rule::collection => {
+                for ident in pair.into_inner() {
+                    let s = ident.as_span();
+                    out.push((s.start(), s.end(), TOKEN_TYPE));
+                }
+            }
+            Rule::const_def
+            | Rule::var_def
+            | Rule::err_def
+            | Rule::group_def
+            | Rule::flow_block => {
+                let ident_type = if matches!(pair.as_rule(), Rule::group_def | Rule::flow_block) {
+                    TOKEN_NAMESPACE
+                } else {
+                    TOKEN_TYPE
+                };
+                for child in pair.into_inner() {
+                    if child.as_rule() == Rule::ident {
+                        let s = child.as_span();
+                        out.push((s.start(), s.end(), ident_type));
+                    } else {
+                        Self::classify_pair(child, out);
+                    }
+                }
+            }
+            Rule::func_def => {
+                // The identifier before `function` is the group, the one after is the name
+                let mut seen_keyword = false;
+                for child in pair.into_inner() {
+                    match child.as_rule() {
+                        Rule::ident => {
+                            let s = child.as_span();
+                            let ident_type = if seen_keyword {
+                                TOKEN_FUNCTION
+                            } else {
+                                TOKEN_NAMESPACE
+                            };
+                            out.push((s.start(), s.end(), ident_type));
+                        }
+                        Rule::kw_function => {
+                            seen_keyword = true;
+                            Self::classify_pair(child, out);
+                        }
+                        _ => Self::classify_pair(child, out),
+                    }
+                }
+            }
+            _ => {
+                for child in pair.into_inner() {
+                    Self::classify_pair(child, out);
+                }
+            }
         }
     }
+
+    /// Builds the hover for a group: its docs followed by its member functions and their contracts.
+    pub(crate) fn group_hover(name: &str, structure: &ProgramStructure) -> Option<String> {
+        let group = structure.groups.get(name)?;
+
+        let mut members: Vec<&Arc<Function>> = structure
+            .catalog
+            .values()
+            .filter(|f| f.group.as_ref().is_some_and(|g| g.uid == group.uid))
+            .collect();
+        members.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let members = if members.is_empty() {
+            "*No member functions.*".to_string()
+        } else {
+            members
+                .iter()
+                .map(|f| format!("- `{}`: `{}`", f.name, f.signature()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        Some(format!(
+            "### Group: `{}`\n\n---\n\n{}\n\n**Members**\n\n{}",
+            name,
+            group
+                .documentation
+                .as_deref()
+                .unwrap_or("*Architectural group.*"),
+            members
+        ))
+    }
 }
@@ -1,20 +1,25 @@
 use anyhow::{Context, Result};
-use clap::{Parser as ClapParser, Subcommand};
+use clap::{Args, Parser as ClapParser, Subcommand};
 use colored::Colorize;
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
 use tower_lsp::{LspService, Server};
 
 use crate::export::vis_js;
 
 mod analyzer;
+mod assertions;
+mod diff;
 mod engine;
 mod export;
 mod formatter;
 mod lsp;
+mod metrics;
 mod models;
 mod source_manager;
 
@@ -59,13 +64,267 @@ struct Cli {
     /// Force stdio mode (internal use for LSP communication)
     #[arg(long, global = true, hide = true)]
     stdio: bool,
+
+    /// Suppress status messages; only the requested output is printed
+    #[arg(long, short, global = true)]
+    quiet: bool,
+}
+
+/// Set by `--quiet`.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints a status message to stderr, so stdout only carries the requested output.
+fn status(message: std::fmt::Arguments) {
+    if !QUIET.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    }
+}
+
+/// One-line indicator of the file being analyzed in a multi-file run.
+///
+/// Drawn on stderr only when it is a terminal, so logs and CI output stay clean.
+struct Progress {
+    total: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        let enabled =
+            total > 1 && std::io::stderr().is_terminal() && !QUIET.load(Ordering::Relaxed);
+        Self { total, enabled }
+    }
+
+    /// Shows `file`, the `index`-th (0-based) of the run, replacing the previous line.
+    fn update(&self, index: usize, file: &Path) {
+        if self.enabled {
+            eprint!("\r\x1b[2K[{}/{}] {}", index + 1, self.total, file.display());
+        }
+    }
+
+    /// Erases the indicator, e.g. before printing diagnostics.
+    fn clear(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[2K");
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Options controlling the flow simulation, shared by analysis commands.
+#[derive(Args)]
+struct FlowArgs {
+    /// Order flow steps by data dependencies instead of authoring order
+    #[arg(long)]
+    auto_order: bool,
+
+    /// Keep every individual token flow, including repeated edges from loop expansion.
+    ///
+    /// By default, edges carrying the same token between the same nodes are merged,
+    /// which keeps diagrams readable. Disable this to see the raw simulation.
+    #[arg(long)]
+    no_dedup: bool,
+
+    /// Keep function nodes without edges that are not flow steps (steps are always kept)
+    #[arg(long)]
+    keep_isolated: bool,
+
+    /// Simulate this named flow instead of the default one (when checking a directory, in the files that define it)
+    #[arg(long, value_name = "NAME")]
+    flow: Option<String>,
+}
+
+impl FlowArgs {
+    /// Runs the flow engine over the analyzed structure.
+    fn simulate(
+        &self,
+        structure: &models::ProgramStructure,
+    ) -> Result<(engine::Flow, models::Graph)> {
+        let mut flow = engine::Flow::with_config(engine::FlowConfig {
+            deduplicate_edges: !self.no_dedup,
+            auto_order: self.auto_order,
+            keep_isolated: self.keep_isolated,
+        });
+        let graph = match &self.flow {
+            Some(name) => {
+                let steps = structure
+                    .named_flow(name)
+                    .with_context(|| format!("Flow '{}' is not defined", name))?;
+                flow.simulate_flow(structure, steps)
+            }
+            None => flow.simulate(structure),
+        };
+        // An engine regression should not block exports; surface it with the flow diagnostics
+        let file_id = structure.all_steps().next().map_or(0, |s| s.span.file_id);
+        for issue in graph.validate() {
+            flow.diagnostics.push(models::DiagnosticWithContext {
+                file_id,
+                span: None,
+                message: format!("Simulation produced a malformed graph: {}", issue),
+                severity: DiagnosticSeverity::WARNING,
+                tags: vec![],
+                node_uid: None,
+                code: None,
+            });
+        }
+        Ok((flow, graph))
+    }
+}
+
+/// Where and in which form `build` writes its output.
+#[derive(Args)]
+struct BuildTarget {
+    /// Output file path, or `-` for stdout
+    #[arg(short, long, value_name = "OUTPUT")]
+    output: PathBuf,
+
+    /// Output format (inferred from the output extension by default; JSON for stdout)
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    format: Option<OutputFormat>,
+
+    /// List the diagnostics below the diagram, each linking to its node (HTML output)
+    #[arg(long)]
+    report: bool,
+
+    /// Only list diagnostics at or above this severity
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        value_name = "SEVERITY",
+        requires = "report"
+    )]
+    min_severity: models::MinSeverity,
+
+    /// Write a Markdown table of every artifact with the functions producing and consuming it, instead of a diagram
+    #[arg(long, conflicts_with_all = ["format", "report", "split_by_group"])]
+    data_dictionary: bool,
+
+    /// Draw the artifacts instead of the functions, linked by the functions transforming them (DOT output)
+    #[arg(
+        long,
+        conflicts_with_all = ["data_dictionary", "report", "only_tag", "focus", "collapse_groups", "split_by_group", "highlight"]
+    )]
+    types_only: bool,
+
+    /// Title shown in the diagram caption, overriding the front matter `title`
+    #[arg(long, value_name = "TEXT")]
+    title: Option<String>,
+
+    /// Open the generated file in the default browser (HTML output; skipped in CI and headless sessions)
+    #[arg(long)]
+    open: bool,
+}
+
+/// Transformations applied to the simulated graph before exporting it.
+#[derive(Args)]
+struct ViewArgs {
+    /// Only render the flows of artifacts with this tag (e.g. `security` for `variable Token @security`)
+    #[arg(long, value_name = "TAG")]
+    only_tag: Option<String>,
+
+    /// Only render the neighborhood of this function
+    #[arg(long, value_name = "FUNCTION")]
+    focus: Option<String>,
+
+    /// Number of hops around the focused function to keep
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "focus")]
+    depth: usize,
+
+    /// Draw each group as a single node listing its functions
+    #[arg(long)]
+    collapse_groups: bool,
+
+    /// Write one diagram per group into the output directory, plus a collapsed overview
+    #[arg(long, conflicts_with_all = ["collapse_groups", "report"])]
+    split_by_group: bool,
+}
+
+impl ViewArgs {
+    fn apply(&self, mut graph: models::Graph) -> Result<models::Graph> {
+        if let Some(tag) = &self.only_tag {
+            let tag = tag.trim_start_matches('@');
+            graph = graph.filter_by_tag(tag);
+            if graph.edges.is_empty() {
+                anyhow::bail!("No artifact tagged '@{}' flows through the graph", tag);
+            }
+        }
+        if let Some(name) = &self.focus {
+            let node = graph
+                .nodes
+                .iter()
+                .find(|n| &n.function.name == name)
+                .with_context(|| {
+                    format!("Function '{}' is not part of the simulated flow", name)
+                })?;
+            graph = graph.neighborhood(node.uid, self.depth);
+        }
+        if self.collapse_groups {
+            graph = graph.collapse_by_group();
+        }
+        Ok(graph)
+    }
+}
+
+/// Formats produced by `build`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// Interactive web visualization (Vis.js)
+    Html,
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid diagram (Markdown)
+    Mermaid,
+    /// LaTeX / TikZ (LuaLaTeX)
+    Tex,
+    /// Raw graph data
+    Json,
+}
+
+impl OutputFormat {
+    /// Infers the format from a file extension, defaulting to JSON.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("html") => Self::Html,
+            Some("dot" | "gv") => Self::Dot,
+            Some("mmd" | "mermaid") => Self::Mermaid,
+            Some("tex") => Self::Tex,
+            _ => Self::Json,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Dot => "dot",
+            Self::Mermaid => "mmd",
+            Self::Tex => "tex",
+            Self::Json => "json",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Html => "HTML",
+            Self::Dot => "DOT",
+            Self::Mermaid => "Mermaid",
+            Self::Tex => "TikZ/LaTeX",
+            Self::Json => "JSON",
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Generate architecture diagrams.
     ///
-    /// The output format is inferred from the file extension.
+    /// The output format is inferred from the file extension, unless
+    /// given with --format. Use `-o -` to write to stdout.
     ///
     /// Supported formats:
     ///   .html  Interactive web visualization (Vis.js)
@@ -79,16 +338,67 @@ enum Commands {
         #[arg(value_name = "INPUT")]
         input: PathBuf,
 
-        /// Output file path
-        #[arg(short, long, value_name = "OUTPUT")]
-        output: PathBuf,
+        #[command(flatten)]
+        target: Box<BuildTarget>,
+
+        /// Color edges by the group of the function producing their token
+        #[arg(long)]
+        color_by_group: bool,
+
+        /// Truncate node and edge labels longer than N characters (full text kept in tooltips)
+        #[arg(long, value_name = "N")]
+        max_label_len: Option<usize>,
+
+        /// Append fan-in and fan-out counts to function labels (HTML and DOT output)
+        #[arg(long)]
+        show_degree: bool,
+
+        /// How to draw error edges (HTML and DOT output)
+        #[arg(long, value_enum, default_value_t, value_name = "LAYOUT")]
+        error_layout: export::ErrorLayout,
+
+        /// Add the generation time (UTC) to the diagram caption
+        #[arg(long)]
+        timestamp: bool,
+
+        /// Number documented edge labels and list their documentation below the diagram (TikZ output)
+        #[arg(long)]
+        token_notes: bool,
+
+        /// Write each function's documentation below its node (TikZ output)
+        #[arg(long)]
+        node_captions: bool,
+
+        /// Maximize compatibility with limited Mermaid renderers such as GitHub's (Mermaid output)
+        #[arg(long)]
+        compact: bool,
+
+        /// Emphasize part of the graph and dim the rest (HTML, DOT and Mermaid output)
+        #[arg(long, value_enum, value_name = "PART")]
+        highlight: Option<export::Highlight>,
+
+        #[command(flatten)]
+        dot_layout: export::dot::DotLayout,
+
+        #[command(flatten)]
+        physics: vis_js::PhysicsOptions,
+
+        #[command(flatten)]
+        theme: export::theme::ThemeConfig,
+
+        #[command(flatten)]
+        view: ViewArgs,
+
+        #[command(flatten)]
+        flow: FlowArgs,
     },
 
     /// Format Tect source code.
     ///
     /// Applies the standard Tect style:
-    ///   • 4-space indentation
+    ///   • 4-space indentation (configurable)
     ///   • Normalized token lists
+    ///   • Grouped, sorted imports
     ///   • Aligned comments
     ///
     /// By default, overwrites the input file.
@@ -101,6 +411,26 @@ enum Commands {
         /// Write formatted output to a separate file
         #[arg(short, long, value_name = "OUTPUT")]
         output: Option<PathBuf>,
+
+        /// Number of spaces per indentation level
+        #[arg(long, value_name = "N", default_value_t = 4)]
+        indent: usize,
+
+        /// Indent with tabs instead of spaces
+        #[arg(long)]
+        tabs: bool,
+
+        /// Align output token lists into columns
+        #[arg(long)]
+        align: bool,
+
+        /// Sort token lists (constants, variables, errors, then by name)
+        #[arg(long)]
+        sort_tokens: bool,
+
+        /// Keep imports where they are instead of moving them into one sorted block at the top
+        #[arg(long)]
+        keep_import_order: bool,
     },
 
     /// Validate architecture syntax and logic.
@@ -110,12 +440,90 @@ enum Commands {
     ///   2. Semantic analysis (symbol resolution, cycle detection)
     ///   3. Flow simulation (starvation and dead-end detection)
     ///
+    /// Accepts a single file or a directory (checked recursively).
     /// Exits with a non-zero code if errors are found.
     #[command(visible_alias = "c")]
     Check {
+        /// Input .tect file, or a directory to check every .tect file in it
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// Print diagnostics as a JSON array instead of human-readable lines
+        #[arg(long)]
+        diagnostics_json: bool,
+
+        /// Only report diagnostics at or above this severity
+        #[arg(long, value_enum, default_value_t, value_name = "SEVERITY")]
+        min_severity: models::MinSeverity,
+
+        #[command(flatten)]
+        flow: FlowArgs,
+    },
+
+    /// Report architecture metrics.
+    ///
+    /// Prints the critical path (the longest chain of dependent
+    /// functions) and the cross-group coupling matrix: how many edges
+    /// flow from each group into every other group. High coupling often
+    /// signals a leaky module boundary.
+    #[command(visible_alias = "s")]
+    Stats {
         /// Input .tect file
         #[arg(value_name = "INPUT")]
         input: PathBuf,
+
+        /// Print the defined, reachable and unreachable functions as JSON instead
+        #[arg(long)]
+        reachable: bool,
+
+        #[command(flatten)]
+        flow: FlowArgs,
+    },
+
+    /// Explain one function.
+    ///
+    /// Prints its signature, group and documentation, the functions
+    /// feeding its inputs and consuming its outputs, and whether each of
+    /// its errors is handled. With a directory, the first file whose
+    /// flow invokes the function is used.
+    #[command(visible_alias = "e")]
+    Explain {
+        /// Name of the function
+        #[arg(value_name = "FUNCTION")]
+        function: String,
+
+        /// Input .tect file or directory
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        #[command(flatten)]
+        flow: FlowArgs,
+    },
+
+    /// Compare two architectures.
+    ///
+    /// Each input is either a .tect file, which is simulated, or a
+    /// graph previously exported with `tect build -o graph.json`.
+    /// Prints the added and removed nodes and edges.
+    ///
+    /// With --output, also writes a Mermaid diagram of both graphs
+    /// with additions in green and removals in red.
+    #[command(visible_alias = "d")]
+    Diff {
+        /// Old architecture (.tect or .json)
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        /// New architecture (.tect or .json)
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        /// Write a Mermaid diagram of the changes to this file
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        flow: FlowArgs,
     },
 
     /// Start the Language Server (LSP).
@@ -131,6 +539,7 @@ enum Commands {
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    QUIET.store(cli.quiet, Ordering::Relaxed);
 
     let cmd = if cli.stdio {
         Commands::Serve
@@ -139,72 +548,356 @@ async fn main() -> Result<()> {
     };
 
     match cmd {
-        Commands::Build { input, output } => handle_build(input, output),
-        Commands::Fmt { input, output } => handle_fmt(input, output),
-        Commands::Check { input } => handle_check(input),
+        Commands::Build {
+            input,
+            target,
+            color_by_group,
+            max_label_len,
+            show_degree,
+            error_layout,
+            timestamp,
+            token_notes,
+            node_captions,
+            compact,
+            highlight,
+            dot_layout,
+            physics,
+            theme,
+            view,
+            flow,
+        } => {
+            let options = export::ExportOptions {
+                color_edges_by_group: color_by_group,
+                max_label_len,
+                show_degree,
+                error_layout,
+                timestamp: timestamp.then(utc_timestamp),
+                token_notes,
+                node_captions,
+                dot_layout,
+                physics,
+                theme,
+                mermaid_compact: compact,
+                highlight,
+            };
+            handle_build(input, &target, &flow, &view, &options)
+        }
+        Commands::Fmt {
+            input,
+            output,
+            indent,
+            tabs,
+            align,
+            sort_tokens,
+            keep_import_order,
+        } => {
+            let options = formatter::FormatOptions {
+                indent,
+                use_tabs: tabs,
+                align_outputs: align,
+                sort_tokens,
+                group_imports: !keep_import_order,
+            };
+            handle_fmt(input, output, &options)
+        }
+        Commands::Check {
+            input,
+            diagnostics_json,
+            min_severity,
+            flow,
+        } => {
+            if !handle_check(input, &flow, diagnostics_json, min_severity)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Commands::Stats {
+            input,
+            reachable,
+            flow,
+        } => handle_stats(input, &flow, reachable),
+        Commands::Explain {
+            function,
+            input,
+            flow,
+        } => handle_explain(&function, input, &flow),
+        Commands::Diff {
+            old,
+            new,
+            output,
+            flow,
+        } => handle_diff(old, new, output, &flow),
         Commands::Serve => handle_serve().await,
     }
 }
 
-fn handle_build(input: PathBuf, output: PathBuf) -> Result<()> {
-    let content = fs::read_to_string(&input).context("Failed to read input file")?;
-    let abs_path = fs::canonicalize(&input).unwrap_or(input.clone());
+/// Reads the entry file and analyzes it together with its imports.
+fn load_workspace(input: &Path) -> Result<analyzer::Workspace> {
+    let mut workspace = analyzer::Workspace::new();
+    analyze_file(&mut workspace, input)?;
+    Ok(workspace)
+}
+
+/// Analyzes `input` within `workspace`.
+///
+/// Contents are read through the workspace's `SourceManager`, so files already
+/// held in memory take precedence over disk, and files shared by several
+/// entry points are read only once.
+fn analyze_file(workspace: &mut analyzer::Workspace, input: &Path) -> Result<()> {
+    let abs_path = fs::canonicalize(input)
+        .with_context(|| format!("Failed to read input file {:?}", input))?;
     let root_uri =
         Url::from_file_path(abs_path).map_err(|_| anyhow::anyhow!("Invalid file path"))?;
 
+    let file_id = workspace.source_manager.get_id(&root_uri);
+    if !workspace.source_manager.load_file(file_id, None) {
+        anyhow::bail!("Failed to read input file {:?}", input);
+    }
+    workspace.analyze(root_uri, None);
+    Ok(())
+}
+
+/// Writes an output file, creating its parent directories first.
+fn write_output(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Current time as `YYYY-MM-DD HH:MM UTC`.
+fn utc_timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60
+    )
+}
+
+/// Civil date `(year, month, day)` from days since 1970-01-01
+/// (Howard Hinnant's algorithm).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn handle_build(
+    input: PathBuf,
+    target: &BuildTarget,
+    flow_args: &FlowArgs,
+    view: &ViewArgs,
+    options: &export::ExportOptions,
+) -> Result<()> {
+    let output = &target.output;
+    let format = target
+        .format
+        .unwrap_or_else(|| OutputFormat::from_path(output));
+    if target.report && format != OutputFormat::Html {
+        anyhow::bail!("--report requires HTML output");
+    }
+    if target.types_only && format != OutputFormat::Dot {
+        anyhow::bail!("--types-only requires DOT output");
+    }
+    if view.split_by_group && output.as_path() == Path::new("-") {
+        anyhow::bail!("--split-by-group writes several files; give an output directory");
+    }
+
     // 1. Analyze: Parse and resolve the project structure
-    let mut workspace = analyzer::Workspace::new();
-    workspace.analyze(root_uri, Some(content));
-
-    // 2. Simulate: Run the flow engine to determine graph edges
-    let mut flow = engine::Flow::new(true);
-    let graph = flow.simulate(&workspace.structure);
-
-    // 3. Export: Generate output based on file extension
-    let extension = output
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("json");
-
-    match extension {
-        "html" => {
-            let html = vis_js::generate_interactive_html(&graph);
-            fs::write(&output, html)?;
-            println!("{} HTML: {:?}", "Success:".green().bold(), output);
-        }
-        "dot" | "gv" => {
-            let content = export::dot::export(&graph);
-            fs::write(&output, content)?;
-            println!("{} DOT: {:?}", "Success:".green().bold(), output);
+    let mut workspace = load_workspace(&input)?;
+    if let Some(title) = &target.title {
+        workspace.structure.title = Some(title.clone());
+    }
+    let options = &export::ExportOptions {
+        theme: options.theme.clone().or_front_matter(&workspace.structure),
+        ..options.clone()
+    };
+
+    let content = if target.types_only {
+        // The type graph comes from the definitions alone, without simulating
+        export::dot::export_types(&workspace.structure, options)
+    } else {
+        // 2. Simulate: Run the flow engine to determine graph edges
+        let (flow, mut graph) = flow_args.simulate(&workspace.structure)?;
+        workspace.attach_source_files(&mut graph);
+        let mut diagnostics = flow.diagnostics;
+        // The report lists failed assertions like `check`, on the unfiltered graph
+        if target.report {
+            let failed = assertions::evaluate_assertions(&graph, &workspace.structure.assertions);
+            diagnostics.extend(failed);
         }
-        "mmd" | "mermaid" => {
-            let content = export::mermaid::export(&graph);
-            fs::write(&output, content)?;
-            println!("{} Mermaid: {:?}", "Success:".green().bold(), output);
+        let graph = view.apply(graph)?;
+
+        // 3. Export: Generate output in the requested format
+        if view.split_by_group {
+            return write_split_by_group(&graph, output, format, options);
         }
-        "tex" => {
-            let content = export::tikz::export(&graph);
-            fs::write(&output, content)?;
-            println!("{} TikZ/LaTeX: {:?}", "Success:".green().bold(), output);
+        if target.data_dictionary {
+            export::dictionary::export(&workspace.structure, &graph)
+        } else if target.report {
+            workspace.structure.diagnostics.extend(diagnostics);
+            let reports = collect_reports(&mut workspace, &mut HashSet::new(), target.min_severity);
+            vis_js::generate_report_html(&graph, options, &reports)
+        } else {
+            render(&graph, format, options)?
         }
-        _ => {
-            let json = serde_json::to_string_pretty(&graph)?;
-            fs::write(&output, json)?;
-            println!("{} JSON: {:?}", "Success:".green().bold(), output);
+    };
+
+    if output.as_path() == Path::new("-") {
+        print!("{}", content);
+    } else {
+        write_output(output, content)?;
+        let label = if target.data_dictionary {
+            "Data dictionary"
+        } else if target.types_only {
+            "Type graph"
+        } else {
+            format.label()
+        };
+        status(format_args!(
+            "{} {}: {:?}",
+            "Success:".green().bold(),
+            label,
+            output
+        ));
+        if target.open {
+            open_in_browser(output, format);
         }
     }
 
     Ok(())
 }
 
-fn handle_fmt(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
+/// Opens an HTML export with the platform's default handler.
+///
+/// Does nothing (besides a note) for other formats, in CI, and in sessions
+/// without a display.
+fn open_in_browser(path: &Path, format: OutputFormat) {
+    if format != OutputFormat::Html {
+        status(format_args!(
+            "{} --open only applies to HTML output",
+            "Note:".cyan().bold()
+        ));
+        return;
+    }
+    let headless = cfg!(target_os = "linux")
+        && std::env::var_os("DISPLAY").is_none()
+        && std::env::var_os("WAYLAND_DISPLAY").is_none();
+    if std::env::var_os("CI").is_some() || headless {
+        status(format_args!(
+            "{} No display available, not opening {:?}",
+            "Note:".cyan().bold(),
+            path
+        ));
+        return;
+    }
+
+    // No shell on Windows: `cmd /C start` would re-parse `&`, `|` and `^` in the path
+    let mut command = if cfg!(target_os = "windows") {
+        let mut c = std::process::Command::new("rundll32");
+        c.arg("url.dll,FileProtocolHandler");
+        c
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    if let Err(e) = command.arg(path).spawn() {
+        status(format_args!(
+            "{} Could not open {:?}: {}",
+            "Note:".cyan().bold(),
+            path,
+            e
+        ));
+    }
+}
+
+fn render(
+    graph: &models::Graph,
+    format: OutputFormat,
+    options: &export::ExportOptions,
+) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Html => vis_js::generate_interactive_html(graph, options),
+        OutputFormat::Dot => export::dot::export(graph, options),
+        OutputFormat::Mermaid => export::mermaid::export(graph, options),
+        OutputFormat::Tex => export::tikz::export(graph, options),
+        OutputFormat::Json => serde_json::to_string_pretty(graph)?,
+    })
+}
+
+/// Writes `<dir>/<Group>.<ext>` for every group, and `<dir>/_overview.<ext>`
+/// with each group collapsed to a single node.
+fn write_split_by_group(
+    graph: &models::Graph,
+    dir: &Path,
+    format: OutputFormat,
+    options: &export::ExportOptions,
+) -> Result<()> {
+    // Group names cannot start with `_` unless quoted, so the overview rarely collides
+    let mut parts = vec![("_overview".to_string(), graph.collapse_by_group())];
+    parts.extend(graph.split_by_group());
+    let file_names: Vec<String> = parts
+        .iter()
+        .map(|(name, _)| name.replace(['/', '\\'], "_"))
+        .collect();
+    let mut seen = HashSet::new();
+    for ((name, _), file_name) in parts.iter().zip(&file_names) {
+        if !seen.insert(file_name) {
+            anyhow::bail!(
+                "Group '{}' would overwrite another diagram as '{}'; rename it",
+                name,
+                file_name
+            );
+        }
+    }
+    for ((_, part), file_name) in parts.iter().zip(&file_names) {
+        let path = dir.join(format!("{}.{}", file_name, format.extension()));
+        write_output(&path, render(part, format, options)?)?;
+    }
+    status(format_args!(
+        "{} {}: {} files in {:?}",
+        "Success:".green().bold(),
+        format.label(),
+        parts.len(),
+        dir
+    ));
+    Ok(())
+}
+
+fn handle_fmt(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    options: &formatter::FormatOptions,
+) -> Result<()> {
     let content = fs::read_to_string(&input).context("Failed to read input file")?;
 
-    match formatter::format_tect_source(&content) {
+    match formatter::format_tect_source(&content, options) {
         Some(formatted) => {
             let target = output.unwrap_or(input);
-            fs::write(&target, formatted).context("Failed to write formatted output")?;
-            println!("{} Formatted {:?}", "Success:".green().bold(), target);
+            write_output(&target, formatted).context("Failed to write formatted output")?;
+            status(format_args!(
+                "{} Formatted {:?}",
+                "Success:".green().bold(),
+                target
+            ));
             Ok(())
         }
         None => {
@@ -217,109 +910,403 @@ fn handle_fmt(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
     }
 }
 
-fn handle_check(input: PathBuf) -> Result<()> {
-    let content = fs::read_to_string(&input).context("Failed to read input file")?;
-    let abs_path = fs::canonicalize(&input).unwrap_or(input.clone());
-    let root_uri =
-        Url::from_file_path(abs_path).map_err(|_| anyhow::anyhow!("Invalid file path"))?;
+/// Prints the diagnostics of `input` and returns whether it has no errors.
+fn handle_check(
+    input: PathBuf,
+    flow_args: &FlowArgs,
+    diagnostics_json: bool,
+    min_severity: models::MinSeverity,
+) -> Result<bool> {
+    let files = collect_tect_files(&input)?;
+    if files.is_empty() {
+        anyhow::bail!("No .tect files found in {:?}", input);
+    }
 
+    // Files imported by several entry points are reported only once
+    let mut seen = HashSet::new();
+    let mut reports = Vec::new();
     let mut workspace = analyzer::Workspace::new();
-    workspace.analyze(root_uri, Some(content));
+    let progress = Progress::new(files.len());
+    let mut flow_found = false;
 
-    // Run engine only if no fatal parsing errors to avoid cascading noise
-    let has_fatal = workspace
-        .structure
-        .diagnostics
-        .iter()
-        .any(|d| d.severity == DiagnosticSeverity::ERROR);
+    for (i, file) in files.iter().enumerate() {
+        progress.update(i, file);
+        analyze_file(&mut workspace, file)?;
+
+        // Run engine only if no fatal parsing errors to avoid cascading noise
+        let has_fatal = workspace
+            .structure
+            .diagnostics
+            .iter()
+            .any(|d| d.severity == DiagnosticSeverity::ERROR);
+
+        // With `--flow`, files that do not define that flow are only analyzed
+        let defines_flow = flow_args
+            .flow
+            .as_deref()
+            .is_none_or(|name| workspace.structure.named_flow(name).is_some());
+        flow_found |= defines_flow;
+
+        if !has_fatal && defines_flow {
+            let (flow, graph) = flow_args.simulate(&workspace.structure)?;
+            workspace.structure.diagnostics.extend(flow.diagnostics);
+            let failed = assertions::evaluate_assertions(&graph, &workspace.structure.assertions);
+            workspace.structure.diagnostics.extend(failed);
+        }
+
+        let new_reports = collect_reports(&mut workspace, &mut seen, min_severity);
+        progress.clear();
+        if !diagnostics_json {
+            new_reports.iter().for_each(print_report);
+        }
+        reports.extend(new_reports);
+    }
+    if let (Some(name), false) = (&flow_args.flow, flow_found) {
+        anyhow::bail!("Flow '{}' is not defined in {:?}", name, input);
+    }
+
+    let err_count = reports.iter().filter(|r| r.severity == "error").count();
+    let warn_count = reports.iter().filter(|r| r.severity == "warning").count();
+
+    if diagnostics_json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(err_count == 0);
+    }
+
+    if reports.is_empty() {
+        status(format_args!(
+            "{} No issues found.",
+            "Success:".green().bold()
+        ));
+        return Ok(true);
+    }
+
+    let outcome = if err_count > 0 {
+        "Failure:".red().bold()
+    } else {
+        "Success:".green().bold()
+    };
+    status(format_args!(
+        "\n{} Found {} errors, {} warnings.",
+        outcome, err_count, warn_count
+    ));
+
+    Ok(err_count == 0)
+}
+
+/// Resolves the input of `check` to the list of files to analyze.
+///
+/// A file is returned as-is; a directory is searched recursively for `.tect` files.
+fn collect_tect_files(input: &Path) -> Result<Vec<PathBuf>> {
+    if !input.is_dir() {
+        return Ok(vec![input.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(input).sort_by_file_name() {
+        let entry = entry.context("Failed to read input directory")?;
+        if entry.file_type().is_file()
+            && entry.path().extension().and_then(|e| e.to_str()) == Some("tect")
+        {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// Resolves the workspace diagnostics at or above `min_severity`, skipping
+/// any already present in `seen`.
+fn collect_reports(
+    workspace: &mut analyzer::Workspace,
+    seen: &mut HashSet<(Option<Url>, Option<usize>, String)>,
+    min_severity: models::MinSeverity,
+) -> Vec<models::DiagnosticReport> {
+    let mut reports = Vec::new();
+    for diag in &workspace.structure.diagnostics {
+        if !min_severity.admits(diag.severity) || workspace.source_manager.is_suppressed(diag) {
+            continue;
+        }
+        let key = (
+            workspace.source_manager.get_uri(diag.file_id).cloned(),
+            diag.span.map(|s| s.start),
+            diag.message.clone(),
+        );
+        if seen.insert(key) {
+            reports.push(workspace.source_manager.resolve_diagnostic(diag));
+        }
+    }
+    reports
+}
+
+/// Prints a diagnostic in the human-readable `check` format.
+fn print_report(report: &models::DiagnosticReport) {
+    let severity_label = match report.severity.as_str() {
+        "error" => "Error".red().bold(),
+        "warning" => "Warning".yellow().bold(),
+        "information" => "Info".blue().bold(),
+        _ => "Hint".cyan(),
+    };
+
+    let location_str = match (report.line, report.column) {
+        (Some(line), Some(column)) => format!(
+            "{}:{}:{}",
+            report
+                .file
+                .as_deref()
+                .and_then(|f| Path::new(f).file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            line,
+            column
+        ),
+        _ => "global".to_string(),
+    };
 
-    if !has_fatal {
-        let mut flow = engine::Flow::new(true);
-        let _graph = flow.simulate(&workspace.structure);
-        workspace.structure.diagnostics.extend(flow.diagnostics);
+    println!(
+        "{}: {} {}",
+        severity_label,
+        format!("[{}]", location_str).dimmed(),
+        report.message
+    );
+}
+
+fn handle_stats(input: PathBuf, flow_args: &FlowArgs, reachable: bool) -> Result<()> {
+    let workspace = load_workspace(&input)?;
+    let (flow, graph) = flow_args.simulate(&workspace.structure)?;
+
+    if reachable {
+        let mut defined: Vec<&String> = workspace.structure.catalog.keys().collect();
+        defined.sort();
+        let reached = flow.reachable_functions();
+        let (reachable, unreachable): (Vec<&String>, Vec<&String>) =
+            defined.iter().partition(|name| reached.contains(**name));
+        let coverage = serde_json::json!({
+            "defined": defined,
+            "reachable": reachable,
+            "unreachable": unreachable,
+        });
+        println!("{}", serde_json::to_string_pretty(&coverage)?);
+        return Ok(());
     }
 
-    let diagnostics = &workspace.structure.diagnostics;
+    let path = metrics::critical_path(&graph);
+    println!("{} ({} functions)", "Critical path".bold(), path.len());
+    if !path.is_empty() {
+        println!("  {}", path.join(" -> "));
+    }
+    println!();
 
-    if diagnostics.is_empty() {
-        println!("{} No issues found.", "Success:".green().bold());
+    let mut rows: Vec<_> = metrics::coupling_matrix(&graph).into_iter().collect();
+    rows.sort_by(|((fa, ta), ca), ((fb, tb), cb)| cb.cmp(ca).then(fa.cmp(fb)).then(ta.cmp(tb)));
+
+    println!("{}", "Cross-group coupling".bold());
+    if rows.is_empty() {
+        println!("  No edges cross group boundaries.");
         return Ok(());
     }
 
-    let mut err_count = 0;
-    let mut warn_count = 0;
+    let width = rows
+        .iter()
+        .map(|((from, to), _)| from.len() + to.len() + 4)
+        .max()
+        .unwrap_or(0);
+    for ((from, to), count) in rows {
+        let pair = format!("{} -> {}", from, to);
+        println!("  {:<width$}  {}", pair, count, width = width);
+    }
 
-    for diag in diagnostics {
-        let severity_label = match diag.severity {
-            DiagnosticSeverity::ERROR => {
-                err_count += 1;
-                "Error".red().bold()
-            }
-            DiagnosticSeverity::WARNING => {
-                warn_count += 1;
-                "Warning".yellow().bold()
+    Ok(())
+}
+
+/// Finds the function to explain and the simulated graph it is explained against.
+///
+/// Prefers a file whose flow invokes the function over one merely defining it.
+/// With `--flow`, files that do not define that flow are skipped.
+fn find_function(
+    name: &str,
+    input: &Path,
+    flow_args: &FlowArgs,
+) -> Result<(Arc<models::Function>, models::Graph)> {
+    let files = collect_tect_files(input)?;
+    if files.is_empty() {
+        anyhow::bail!("No .tect files found in {:?}", input);
+    }
+
+    let mut found = None;
+    let mut flow_found = false;
+    for file in &files {
+        let workspace = load_workspace(file)?;
+        if let Some(flow) = &flow_args.flow {
+            if workspace.structure.named_flow(flow).is_none() {
+                continue;
             }
-            DiagnosticSeverity::INFORMATION => "Info".blue().bold(),
-            DiagnosticSeverity::HINT => "Hint".cyan(),
-            _ => "Diagnostic".white(),
+        }
+        flow_found = true;
+        let Some(function) = workspace.structure.catalog.get(name).cloned() else {
+            continue;
         };
+        let (_, graph) = flow_args.simulate(&workspace.structure)?;
+        let invoked = metrics::neighbors(&graph, name).is_some();
+        if found.is_none() || invoked {
+            found = Some((function, graph));
+        }
+        if invoked {
+            break;
+        }
+    }
+    if let (Some(flow), false) = (&flow_args.flow, flow_found) {
+        anyhow::bail!("Flow '{}' is not defined in {:?}", flow, input);
+    }
+    found.with_context(|| format!("Function '{}' is not defined in {:?}", name, input))
+}
 
-        let location_str = if let Some(span) = diag.span {
-            let range = workspace.source_manager.resolve_range(span);
-            format!(
-                "{}:{}:{}",
-                workspace
-                    .source_manager
-                    .get_uri(span.file_id)
-                    .and_then(|u| u.to_file_path().ok())
-                    .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-                    .unwrap_or_else(|| "unknown".to_string()),
-                range.start.line + 1,
-                range.start.character + 1
-            )
-        } else {
-            "global".to_string()
-        };
+fn handle_explain(name: &str, input: PathBuf, flow_args: &FlowArgs) -> Result<()> {
+    let (function, graph) = find_function(name, &input, flow_args)?;
 
-        println!(
-            "{}: {} {}",
-            severity_label,
-            format!("[{}]", location_str).dimmed(),
-            diag.message
-        );
+    println!("{}", function.name.bold());
+    println!("  Signature: {}", function.signature());
+    if let Some(group) = &function.group {
+        println!("  Group: {}", group.name);
+    }
+    if let Some(docs) = &function.documentation {
+        for line in docs.lines() {
+            println!("  {}", line.dimmed());
+        }
     }
-
     println!();
-    if err_count > 0 {
-        eprintln!(
-            "{} Found {} errors, {} warnings.",
-            "Failure:".red().bold(),
-            err_count,
-            warn_count
-        );
-        std::process::exit(1);
-    } else {
-        println!(
-            "{} Found {} errors, {} warnings.",
+
+    let Some(neighbors) = metrics::neighbors(&graph, name) else {
+        println!("Not invoked by the simulated flow.");
+        return Ok(());
+    };
+    let describe = |nodes: &[&models::Node]| {
+        nodes
+            .iter()
+            .map(|n| {
+                if n.is_artificial_graph_start {
+                    "(flow input)".to_string()
+                } else if n.is_artificial_graph_end {
+                    "(end of flow)".to_string()
+                } else if n.is_artificial_error_termination {
+                    "UNHANDLED".red().bold().to_string()
+                } else {
+                    n.function.name.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let is_error = |artifact: &str| {
+        function
+            .produces
+            .iter()
+            .flatten()
+            .any(|t| t.kind.name() == artifact && matches!(t.kind, models::Kind::Error(_)))
+    };
+
+    println!("{}", "Inputs".bold());
+    if neighbors.inputs.is_empty() {
+        println!("  None");
+    }
+    for (artifact, producers) in &neighbors.inputs {
+        println!("  {} <- {}", artifact, describe(producers));
+    }
+    let (errors, outputs): (Vec<_>, Vec<_>) = neighbors
+        .outputs
+        .iter()
+        .partition(|(artifact, _)| is_error(artifact));
+    println!("{}", "Outputs".bold());
+    if outputs.is_empty() {
+        println!("  None");
+    }
+    for (artifact, consumers) in outputs {
+        println!("  {} -> {}", artifact, describe(consumers));
+    }
+    if !errors.is_empty() {
+        println!("{}", "Errors".bold());
+        for (artifact, handlers) in errors {
+            println!("  {} -> {}", artifact, describe(handlers));
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads a graph from exported JSON, or by simulating a .tect file.
+fn load_graph(path: &Path, flow_args: &FlowArgs) -> Result<models::Graph> {
+    if path.extension().and_then(|s| s.to_str()) == Some("json") {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read graph {:?}", path))?;
+        return serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse graph {:?}", path));
+    }
+
+    let workspace = load_workspace(path)?;
+    let (_, graph) = flow_args.simulate(&workspace.structure)?;
+    Ok(graph)
+}
+
+fn handle_diff(
+    old: PathBuf,
+    new: PathBuf,
+    output: Option<PathBuf>,
+    flow_args: &FlowArgs,
+) -> Result<()> {
+    let old_graph = load_graph(&old, flow_args)?;
+    let new_graph = load_graph(&new, flow_args)?;
+    let diff = diff::diff_graphs(&old_graph, &new_graph);
+
+    let names: HashMap<u32, &str> = old_graph
+        .nodes
+        .iter()
+        .chain(&new_graph.nodes)
+        .map(|n| (n.uid, n.function.name.as_str()))
+        .collect();
+    let describe = |e: &models::Edge| {
+        format!(
+            "{} -> {} ({}, {:?})",
+            names.get(&e.from_node_uid).unwrap_or(&"?"),
+            names.get(&e.to_node_uid).unwrap_or(&"?"),
+            e.token.kind.name(),
+            e.relation
+        )
+    };
+
+    if diff.is_empty() {
+        println!("No architectural changes.");
+    }
+    for node in &diff.added_nodes {
+        println!("{} node {}", "+".green().bold(), node.function.name);
+    }
+    for node in &diff.removed_nodes {
+        println!("{} node {}", "-".red().bold(), node.function.name);
+    }
+    for edge in &diff.added_edges {
+        println!("{} edge {}", "+".green().bold(), describe(edge));
+    }
+    for edge in &diff.removed_edges {
+        println!("{} edge {}", "-".red().bold(), describe(edge));
+    }
+
+    if let Some(output) = output {
+        let content =
+            export::mermaid::export_diff(&old_graph, &new_graph, &export::ExportOptions::default());
+        write_output(&output, content)?;
+        status(format_args!(
+            "{} Mermaid: {:?}",
             "Success:".green().bold(),
-            err_count,
-            warn_count
-        );
+            output
+        ));
     }
 
     Ok(())
 }
 
 async fn handle_serve() -> Result<()> {
-    let (service, socket) = LspService::build(|client| lsp::Backend {
-        client,
-        workspace: Mutex::new(analyzer::Workspace::new()),
-        open_documents: Mutex::new(HashSet::new()),
-        graph_cache: Mutex::new(HashMap::new()),
-    })
-    .custom_method("tect/getGraph", lsp::Backend::get_visual_graph)
-    .custom_method("tect/exportGraph", lsp::Backend::get_export_content)
-    .finish();
+    let (service, socket) = LspService::build(lsp::Backend::new)
+        .custom_method("tect/getGraph", lsp::Backend::get_visual_graph)
+        .custom_method("tect/exportGraph", lsp::Backend::get_export_content)
+        .finish();
 
     Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
         .serve(service)
@@ -0,0 +1,237 @@
+//! # Architecture Metrics
+//!
+//! Structural measurements computed over a simulated [`Graph`], intended
+//! for architecture review rather than validation.
+
+use crate::models::{EdgeRelation, Graph, Node};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// Label used for functions that do not belong to any group.
+pub const UNGROUPED: &str = "(ungrouped)";
+
+/// Counts the edges crossing group boundaries.
+///
+/// Returns a map keyed by `(source_group, target_group)` holding the number
+/// of edges flowing from the first group into the second. Edges between
+/// nodes of the same group and edges touching artificial nodes are ignored.
+/// Functions without a group are reported under [`UNGROUPED`].
+pub fn coupling_matrix(graph: &Graph) -> HashMap<(String, String), usize> {
+    let groups: HashMap<u32, &str> = graph
+        .nodes
+        .iter()
+        .filter(|n| !is_artificial(n))
+        .map(|n| {
+            let name = n
+                .function
+                .group
+                .as_ref()
+                .map(|g| g.name.as_str())
+                .unwrap_or(UNGROUPED);
+            (n.uid, name)
+        })
+        .collect();
+
+    let mut matrix = HashMap::new();
+    for edge in &graph.edges {
+        let (Some(from), Some(to)) = (
+            groups.get(&edge.from_node_uid),
+            groups.get(&edge.to_node_uid),
+        ) else {
+            continue;
+        };
+        if from != to {
+            *matrix
+                .entry((from.to_string(), to.to_string()))
+                .or_insert(0) += 1;
+        }
+    }
+    matrix
+}
+
+/// Counts incoming and outgoing edges of every function node.
+///
+/// Returns a map from node UID to `(fan_in, fan_out)`. Edges from and to
+/// artificial nodes are counted, but artificial nodes get no entry.
+pub fn degree_map(graph: &Graph) -> HashMap<u32, (usize, usize)> {
+    let mut degrees: HashMap<u32, (usize, usize)> = graph
+        .nodes
+        .iter()
+        .filter(|n| !is_artificial(n))
+        .map(|n| (n.uid, (0, 0)))
+        .collect();
+
+    for edge in &graph.edges {
+        if let Some((_, fan_out)) = degrees.get_mut(&edge.from_node_uid) {
+            *fan_out += 1;
+        }
+        if let Some((fan_in, _)) = degrees.get_mut(&edge.to_node_uid) {
+            *fan_in += 1;
+        }
+    }
+    degrees
+}
+
+/// Returns the longest chain of functions connected by edges, in flow order.
+///
+/// This is the deepest sequential dependency of the architecture. Artificial
+/// nodes are skipped, and edges closing a cycle are ignored so the graph can be
+/// treated as a DAG. Ties are resolved in favor of nodes appearing earlier in
+/// the graph.
+pub fn critical_path(graph: &Graph) -> Vec<String> {
+    let nodes: Vec<&Node> = graph.nodes.iter().filter(|n| !is_artificial(n)).collect();
+    let index: HashMap<u32, usize> = nodes.iter().enumerate().map(|(i, n)| (n.uid, i)).collect();
+
+    let mut successors = vec![Vec::new(); nodes.len()];
+    for edge in &graph.edges {
+        if let (Some(&from), Some(&to)) =
+            (index.get(&edge.from_node_uid), index.get(&edge.to_node_uid))
+        {
+            successors[from].push(to);
+        }
+    }
+    for next in &mut successors {
+        next.sort_unstable();
+        next.dedup();
+    }
+
+    let mut chains = ChainSearch {
+        successors: &successors,
+        memo: vec![None; nodes.len()],
+        on_stack: vec![false; nodes.len()],
+    };
+    let mut best: Option<(usize, usize)> = None;
+    for start in 0..nodes.len() {
+        let len = chains.longest_from(start);
+        if best.is_none_or(|(best_len, _)| len > best_len) {
+            best = Some((len, start));
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut current = best.map(|(_, start)| start);
+    while let Some(i) = current {
+        path.push(nodes[i].function.name.clone());
+        current = chains.memo[i].and_then(|(_, next)| next);
+    }
+    path
+}
+
+/// Nodes and edges lying on some path into a fatal error node.
+#[derive(Debug, Default)]
+pub struct ErrorPaths {
+    /// UIDs of the contributing nodes, fatal error nodes included.
+    pub nodes: HashSet<u32>,
+    /// Indices into [`Graph::edges`] of the contributing edges.
+    pub edges: HashSet<usize>,
+}
+
+/// Traces everything that can contribute to a failure.
+///
+/// Walks backwards from every fatal error node over error and data edges,
+/// so the result holds the functions raising unhandled errors together with
+/// the producers of their inputs. Control and terminal edges are not followed.
+pub fn error_paths(graph: &Graph) -> ErrorPaths {
+    let mut paths = ErrorPaths::default();
+    let mut queue: VecDeque<u32> = graph
+        .nodes
+        .iter()
+        .filter(|n| n.is_artificial_error_termination)
+        .map(|n| n.uid)
+        .collect();
+    paths.nodes.extend(queue.iter().copied());
+
+    while let Some(uid) = queue.pop_front() {
+        for (i, edge) in graph.edges.iter().enumerate() {
+            if edge.to_node_uid != uid
+                || !matches!(
+                    edge.relation,
+                    EdgeRelation::ErrorFlow | EdgeRelation::DataFlow
+                )
+            {
+                continue;
+            }
+            paths.edges.insert(i);
+            if paths.nodes.insert(edge.from_node_uid) {
+                queue.push_back(edge.from_node_uid);
+            }
+        }
+    }
+    paths
+}
+
+/// The simulated surroundings of one function, computed by [`neighbors`].
+#[derive(Debug, Default)]
+pub struct Neighbors<'a> {
+    /// Producers of each consumed artifact, by artifact name. External inputs
+    /// come from the artificial start node.
+    pub inputs: BTreeMap<&'a str, Vec<&'a Node>>,
+    /// Consumers of each produced artifact, by artifact name. Artifacts left
+    /// over go to the artificial end node, unhandled errors to the fatal one.
+    pub outputs: BTreeMap<&'a str, Vec<&'a Node>>,
+}
+
+/// Lists which nodes feed the function's inputs and which consume its outputs.
+///
+/// Returns `None` if the function is not part of the simulated graph.
+pub fn neighbors<'a>(graph: &'a Graph, function: &str) -> Option<Neighbors<'a>> {
+    let node = graph
+        .nodes
+        .iter()
+        .find(|n| !is_artificial(n) && n.function.name == function)?;
+    let nodes: HashMap<u32, &Node> = graph.nodes.iter().map(|n| (n.uid, n)).collect();
+
+    let mut result = Neighbors::default();
+    for edge in &graph.edges {
+        let (side, other) = if edge.to_node_uid == node.uid {
+            (&mut result.inputs, edge.from_node_uid)
+        } else if edge.from_node_uid == node.uid {
+            (&mut result.outputs, edge.to_node_uid)
+        } else {
+            continue;
+        };
+        let Some(other) = nodes.get(&other) else {
+            continue;
+        };
+        let entry = side.entry(edge.token.kind.name()).or_default();
+        if !entry.iter().any(|n| n.uid == other.uid) {
+            entry.push(other);
+        }
+    }
+    Some(result)
+}
+
+/// Memoized longest-chain search used by [`critical_path`].
+struct ChainSearch<'a> {
+    successors: &'a [Vec<usize>],
+    /// Chain length starting at each node, and the next node on that chain.
+    memo: Vec<Option<(usize, Option<usize>)>>,
+    on_stack: Vec<bool>,
+}
+
+impl ChainSearch<'_> {
+    fn longest_from(&mut self, i: usize) -> usize {
+        if let Some((len, _)) = self.memo[i] {
+            return len;
+        }
+        self.on_stack[i] = true;
+        let mut best = (1, None);
+        for &next in &self.successors[i] {
+            if self.on_stack[next] {
+                continue; // Back edge: closes a cycle
+            }
+            let len = 1 + self.longest_from(next);
+            if len > best.0 {
+                best = (len, Some(next));
+            }
+        }
+        self.on_stack[i] = false;
+        self.memo[i] = Some(best);
+        best.0
+    }
+}
+
+fn is_artificial(node: &Node) -> bool {
+    node.is_artificial_graph_start
+        || node.is_artificial_graph_end
+        || node.is_artificial_error_termination
+}
@@ -4,7 +4,7 @@
 //! and the diagnostic structures used across the compiler pipeline.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag};
@@ -70,6 +70,57 @@ pub struct DiagnosticWithContext {
     pub severity: DiagnosticSeverity,
     /// Additional tags (e.g., Unnecessary, Deprecated).
     pub tags: Vec<DiagnosticTag>,
+    /// UID of the graph node the diagnostic is about, when it concerns a function.
+    pub node_uid: Option<u32>,
+    /// Stable code of a suppressible diagnostic, e.g. `unhandled-error`.
+    /// A `# tect:allow <code>` comment above the statement silences it.
+    pub code: Option<&'static str>,
+}
+
+/// Lowest severity a diagnostic needs to be reported, e.g. to hide
+/// informational notes such as unused constants when only errors matter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum MinSeverity {
+    Error,
+    Warning,
+    Information,
+    /// Report everything.
+    #[default]
+    Hint,
+}
+
+impl MinSeverity {
+    /// Whether a diagnostic of this severity is at or above the threshold.
+    pub fn admits(self, severity: DiagnosticSeverity) -> bool {
+        let level = match severity {
+            DiagnosticSeverity::ERROR => MinSeverity::Error,
+            DiagnosticSeverity::WARNING => MinSeverity::Warning,
+            DiagnosticSeverity::INFORMATION => MinSeverity::Information,
+            _ => MinSeverity::Hint,
+        };
+        level <= self
+    }
+}
+
+/// A diagnostic resolved to a file path and 1-based line/column, for tooling.
+///
+/// Produced by [`crate::source_manager::SourceManager::resolve_diagnostic`].
+/// Position fields are `None` for diagnostics not tied to a span.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticReport {
+    /// File path (or URI if not a local file), `None` if unknown.
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub end_line: Option<u32>,
+    pub end_column: Option<u32>,
+    /// One of `error`, `warning`, `information` or `hint`.
+    pub severity: String,
+    pub message: String,
+    /// UID of the related graph node (see [`DiagnosticWithContext::node_uid`]).
+    pub node_uid: Option<u32>,
+    /// Suppression code (see [`DiagnosticWithContext::code`]).
+    pub code: Option<String>,
 }
 
 // --- Core Logic ---
@@ -97,6 +148,8 @@ pub enum EdgeRelation {
     ControlFlow,
     /// Represents a direct function call relationship.
     Call,
+    /// A collection feeding a unitary consumer, which runs once per item ("for each").
+    FanOut,
 }
 
 // --- Type Definitions (Archetypes) ---
@@ -131,6 +184,9 @@ pub struct Constant {
     pub name: String,
     /// Optional documentation.
     pub documentation: Option<String>,
+    /// Concerns the artifact is tagged with (`@security`), without the `@`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl Constant {
@@ -139,6 +195,7 @@ impl Constant {
             uid: hash_name(&name),
             name,
             documentation,
+            tags: Vec::new(),
         }
     }
 }
@@ -152,6 +209,12 @@ pub struct Variable {
     pub name: String,
     /// Optional documentation.
     pub documentation: Option<String>,
+    /// Concerns the artifact is tagged with (`@security`), without the `@`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whether tokens survive being consumed, like constants (`@immutable`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub immutable: bool,
 }
 
 impl Variable {
@@ -160,6 +223,8 @@ impl Variable {
             uid: hash_name(&name),
             name,
             documentation,
+            tags: Vec::new(),
+            immutable: false,
         }
     }
 }
@@ -173,6 +238,9 @@ pub struct Error {
     pub name: String,
     /// Optional documentation.
     pub documentation: Option<String>,
+    /// Concerns the artifact is tagged with (`@security`), without the `@`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl Error {
@@ -181,6 +249,7 @@ impl Error {
             uid: hash_name(&name),
             name,
             documentation,
+            tags: Vec::new(),
         }
     }
 }
@@ -211,6 +280,19 @@ impl Kind {
         }
     }
 
+    /// Returns whether tokens of this kind survive being consumed.
+    ///
+    /// Constants and variables tagged `@immutable` may feed any number of
+    /// consumers, while other variables and errors are removed from the pool
+    /// once consumed.
+    pub fn is_immutable(&self) -> bool {
+        match self {
+            Kind::Constant(_) => true,
+            Kind::Variable(v) => v.immutable,
+            Kind::Error(_) => false,
+        }
+    }
+
     pub fn docs(&self) -> Option<&str> {
         match self {
             Kind::Constant(c) => c.documentation.as_deref(),
@@ -218,6 +300,21 @@ impl Kind {
             Kind::Error(e) => e.documentation.as_deref(),
         }
     }
+
+    /// Returns the tags of the inner kind, without their `@`.
+    pub fn tags(&self) -> &[String] {
+        match self {
+            Kind::Constant(c) => &c.tags,
+            Kind::Variable(v) => &v.tags,
+            Kind::Error(e) => &e.tags,
+        }
+    }
+
+    /// Whether the artifact is an error tagged `@terminal`: an expected outcome
+    /// that ends the flow at `FinalNode` instead of being fatal.
+    pub fn is_terminal_error(&self) -> bool {
+        matches!(self, Kind::Error(e) if e.tags.iter().any(|t| t == "terminal"))
+    }
 }
 
 // --- Contract Entities ---
@@ -231,6 +328,10 @@ pub struct Token {
     pub kind: Kind,
     /// The cardinality of the token usage (Unitary or Collection).
     pub cardinality: Cardinality,
+    /// The group of the function that produced this token (set during simulation).
+    pub group: Option<Arc<Group>>,
+    /// Whether a consumer can run without this token.
+    pub optional: bool,
 }
 
 impl Token {
@@ -240,6 +341,8 @@ impl Token {
             uid,
             kind,
             cardinality,
+            group: None,
+            optional: false,
         }
     }
 }
@@ -259,6 +362,9 @@ pub struct Function {
     pub produces: Vec<Vec<Token>>,
     /// The logical group this function belongs to (if any).
     pub group: Option<Arc<Group>>,
+    /// Fill color set with a `# @color #rrggbb` pragma, overriding the theme.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
 }
 
 impl Function {
@@ -276,6 +382,7 @@ impl Function {
             consumes,
             produces,
             group,
+            color: None,
         }
     }
 
@@ -291,7 +398,65 @@ impl Function {
             consumes: Vec::new(),
             produces: Vec::new(),
             group,
+            color: None,
+        }
+    }
+
+    /// Renders the contract as `Inputs -> Outputs | Errors`, with `()` for none
+    /// and collections in brackets.
+    pub fn signature(&self) -> String {
+        let label = |t: &Token| match t.cardinality {
+            Cardinality::Collection => format!("[{}]", t.kind.name()),
+            Cardinality::Unitary => t.kind.name().to_string(),
+        };
+        let mut inputs = self
+            .consumes
+            .iter()
+            .map(label)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut outputs = self
+            .produces
+            .iter()
+            .map(|branch| branch.iter().map(label).collect::<Vec<_>>().join(", "))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        if inputs.is_empty() {
+            inputs = "()".to_string();
+        }
+        if outputs.is_empty() {
+            outputs = "()".to_string();
         }
+        format!("{} -> {}", inputs, outputs)
+    }
+
+    /// Describes the contract canonically: the consumed tokens and the output branches.
+    ///
+    /// Token order, branch order and usage UIDs are ignored, so two functions
+    /// consuming and producing the same artifacts have the same description.
+    pub fn canonical_signature(&self) -> String {
+        let describe = |tokens: &[Token]| {
+            let mut names: Vec<String> = tokens
+                .iter()
+                .map(|t| {
+                    let name = match t.cardinality {
+                        Cardinality::Collection => format!("[{}]", t.kind.name()),
+                        _ => t.kind.name().to_string(),
+                    };
+                    if t.optional {
+                        name + "?"
+                    } else {
+                        name
+                    }
+                })
+                .collect();
+            names.sort();
+            names.join(",")
+        };
+
+        let mut branches: Vec<String> = self.produces.iter().map(|b| describe(b)).collect();
+        branches.sort();
+        format!("{}>{}", describe(&self.consumes), branches.join("|"))
     }
 }
 
@@ -306,15 +471,63 @@ pub struct ProgramStructure {
     pub groups: HashMap<String, Arc<Group>>,
     /// Map of function names to Function definitions.
     pub catalog: HashMap<String, Arc<Function>>,
-    /// The ordered sequence of flow steps defined in the program.
+    /// The ordered sequence of top-level flow steps defined in the program.
     pub flow: Vec<FlowStep>,
+    /// Named flows (`flow Name { ... }`), each with its own ordered steps.
+    pub flows: HashMap<String, Vec<FlowStep>>,
+    /// Names of the named flows in definition order.
+    pub flow_order: Vec<String>,
     /// Symbol table for looking up definition spans and occurrences.
     pub symbol_table: HashMap<u32, SymbolMetadata>,
+    /// Title from the root file's front matter.
+    pub title: Option<String>,
+    /// Author from the root file's front matter.
+    pub author: Option<String>,
+    /// Font settings from the root file's front matter (`font`, `node_font_size`, `edge_font_size`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_font_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_font_size: Option<u32>,
+    /// Invariants to check against the simulated graph (`assert ...`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<Assertion>,
     /// Diagnostics collected during parsing and analysis.
     #[serde(skip)]
     pub diagnostics: Vec<DiagnosticWithContext>,
 }
 
+impl ProgramStructure {
+    /// Returns the flow simulated when none is selected explicitly.
+    ///
+    /// This is the top-level flow if it has steps, otherwise the first named flow.
+    pub fn default_flow(&self) -> &[FlowStep] {
+        if !self.flow.is_empty() {
+            return &self.flow;
+        }
+        self.flow_order
+            .first()
+            .and_then(|name| self.flows.get(name))
+            .map_or(&[], |steps| steps.as_slice())
+    }
+
+    /// Returns the steps of a named flow.
+    pub fn named_flow(&self, name: &str) -> Option<&[FlowStep]> {
+        self.flows.get(name).map(|steps| steps.as_slice())
+    }
+
+    /// Iterates over the steps of every flow, top-level first.
+    pub fn all_steps(&self) -> impl Iterator<Item = &FlowStep> {
+        self.flow.iter().chain(
+            self.flow_order
+                .iter()
+                .filter_map(|name| self.flows.get(name))
+                .flatten(),
+        )
+    }
+}
+
 /// Represents a step in the execution flow.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FlowStep {
@@ -322,11 +535,37 @@ pub struct FlowStep {
     pub function_name: String,
     /// The source span where this step is defined.
     pub span: Span,
+    /// Documentation comments written directly above the step (e.g. why it runs here).
+    pub documentation: Option<String>,
+}
+
+/// An architecture invariant declared with `assert`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Assertion {
+    pub kind: AssertionKind,
+    /// The source span of the whole statement.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssertionKind {
+    /// `assert From -> To reachable`: a path of edges leads from one function to the other.
+    Reachable { from: String, to: String },
+    /// `assert no Error unhandled`: the error never reaches the fatal error node.
+    Handled { error: String },
 }
 
 // --- Flow Entities ---
 
 /// Represents a node in the execution graph suitable for visualization or analysis.
+///
+/// # UID contract
+/// Node UIDs are never allocated from a counter. A function node takes the UID of its
+/// function (the [hash_name] of the function name) and artificial nodes hash their
+/// fixed name, so repeated invocations of a function share one UID and the same source
+/// always yields the same UIDs, regardless of run or test order. Within a [Graph],
+/// nodes are listed in flow order, preceded by `InitialNode` and followed by
+/// `FinalNode` and `FatalErrors` when those are present.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     /// Unique identifier for the node.
@@ -339,6 +578,13 @@ pub struct Node {
     pub is_artificial_graph_end: bool,
     /// Indicates if this is an artificial error termination node.
     pub is_artificial_error_termination: bool,
+    /// Documentation of the flow step that invoked this node, if any.
+    pub documentation: Option<String>,
+    /// URI of the file defining the function, if attached after simulation.
+    pub source_file: Option<String>,
+    /// Names of the functions this node stands for, when it is a collapsed group.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
 }
 
 impl PartialEq for Node {
@@ -370,6 +616,9 @@ impl Node {
             is_artificial_graph_start: false,
             is_artificial_graph_end: false,
             is_artificial_error_termination: false,
+            documentation: None,
+            source_file: None,
+            members: Vec::new(),
         }
     }
 
@@ -388,6 +637,9 @@ impl Node {
             is_artificial_graph_start: is_start,
             is_artificial_graph_end: is_end,
             is_artificial_error_termination: is_error,
+            documentation: None,
+            source_file: None,
+            members: Vec::new(),
         }
     }
 }
@@ -414,6 +666,257 @@ pub struct Graph {
     pub nodes: Vec<Node>,
     /// The collection of edges connecting the nodes.
     pub edges: Vec<Edge>,
+    /// Diagram title, from the root file's front matter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Diagram author, from the root file's front matter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+impl Graph {
+    /// Returns the subgraph of nodes within `depth` hops of `node_uid`.
+    ///
+    /// Hops follow edges in either direction. Only edges between kept nodes are
+    /// retained. The result is empty if `node_uid` is not in the graph.
+    pub fn neighborhood(&self, node_uid: u32, depth: usize) -> Graph {
+        if !self.nodes.iter().any(|n| n.uid == node_uid) {
+            return Graph::default();
+        }
+
+        let mut kept = HashSet::from([node_uid]);
+        let mut frontier = vec![node_uid];
+        for _ in 0..depth {
+            let mut next = Vec::new();
+            for edge in &self.edges {
+                for (from, to) in [
+                    (edge.from_node_uid, edge.to_node_uid),
+                    (edge.to_node_uid, edge.from_node_uid),
+                ] {
+                    if frontier.contains(&from) && kept.insert(to) {
+                        next.push(to);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+
+        self.subgraph(&kept)
+    }
+
+    /// Returns the nodes whose UID is in `keep`, and the edges between them.
+    pub fn subgraph(&self, keep: &HashSet<u32>) -> Graph {
+        Graph {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|n| keep.contains(&n.uid))
+                .cloned()
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter(|e| keep.contains(&e.from_node_uid) && keep.contains(&e.to_node_uid))
+                .cloned()
+                .collect(),
+            title: self.title.clone(),
+            author: self.author.clone(),
+        }
+    }
+
+    /// Keeps the edges carrying artifacts tagged `tag`, and the nodes they connect.
+    ///
+    /// This gives a view of one concern (e.g. `security`) across all groups.
+    pub fn filter_by_tag(&self, tag: &str) -> Graph {
+        let edges: Vec<Edge> = self
+            .edges
+            .iter()
+            .filter(|e| e.token.kind.tags().iter().any(|t| t == tag))
+            .cloned()
+            .collect();
+        let keep: HashSet<u32> = edges
+            .iter()
+            .flat_map(|e| [e.from_node_uid, e.to_node_uid])
+            .collect();
+        Graph {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|n| keep.contains(&n.uid))
+                .cloned()
+                .collect(),
+            edges,
+            title: self.title.clone(),
+            author: self.author.clone(),
+        }
+    }
+
+    /// Splits the graph into one subgraph per group, sorted by group name.
+    ///
+    /// Each subgraph holds the group's functions and the edges between them,
+    /// and is titled after the group.
+    pub fn split_by_group(&self) -> Vec<(String, Graph)> {
+        let mut members: BTreeMap<&str, HashSet<u32>> = BTreeMap::new();
+        for node in &self.nodes {
+            if let Some(group) = &node.function.group {
+                members.entry(&group.name).or_default().insert(node.uid);
+            }
+        }
+        members
+            .into_iter()
+            .map(|(group, keep)| {
+                let mut graph = self.subgraph(&keep);
+                graph.title = Some(match &self.title {
+                    Some(title) => format!("{} · {}", title, group),
+                    None => group.to_string(),
+                });
+                (group.to_string(), graph)
+            })
+            .collect()
+    }
+
+    /// Contracts each group into a single node listing its member functions.
+    ///
+    /// Ungrouped and artificial nodes are kept. Edges inside a group are dropped,
+    /// and edges carrying the same token between the same endpoints are merged.
+    /// Group nodes hash `group:<name>`, so they never collide with function UIDs.
+    pub fn collapse_by_group(&self) -> Graph {
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut group_nodes: HashMap<String, usize> = HashMap::new();
+        let mut uid_map: HashMap<u32, u32> = HashMap::new();
+
+        for node in &self.nodes {
+            let Some(group) = &node.function.group else {
+                uid_map.insert(node.uid, node.uid);
+                nodes.push(node.clone());
+                continue;
+            };
+            let index = *group_nodes.entry(group.name.clone()).or_insert_with(|| {
+                nodes.push(Node {
+                    uid: hash_name(&format!("group:{}", group.name)),
+                    function: Arc::new(Function::new(
+                        group.name.clone(),
+                        group.documentation.clone(),
+                        vec![],
+                        vec![],
+                        Some(group.clone()),
+                    )),
+                    is_artificial_graph_start: false,
+                    is_artificial_graph_end: false,
+                    is_artificial_error_termination: false,
+                    documentation: None,
+                    source_file: None,
+                    members: Vec::new(),
+                });
+                nodes.len() - 1
+            });
+            let group_node = &mut nodes[index];
+            if !group_node.members.contains(&node.function.name) {
+                group_node.members.push(node.function.name.clone());
+            }
+            uid_map.insert(node.uid, group_node.uid);
+        }
+
+        let mut seen = HashSet::new();
+        let edges = self
+            .edges
+            .iter()
+            .filter_map(|edge| {
+                let from = *uid_map.get(&edge.from_node_uid)?;
+                let to = *uid_map.get(&edge.to_node_uid)?;
+                if from == to
+                    || !seen.insert((from, to, edge.token.kind.name(), edge.relation.clone()))
+                {
+                    return None;
+                }
+                Some(Edge {
+                    from_node_uid: from,
+                    to_node_uid: to,
+                    ..edge.clone()
+                })
+            })
+            .collect();
+
+        Graph {
+            nodes,
+            edges,
+            title: self.title.clone(),
+            author: self.author.clone(),
+        }
+    }
+
+    /// Checks referential integrity, catching engine regressions before export.
+    ///
+    /// A self-loop is only expected when the function consumes a token it also
+    /// produces (a repeated step feeding itself).
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut nodes: HashMap<u32, &Node> = HashMap::new();
+        for node in &self.nodes {
+            if nodes.insert(node.uid, node).is_some() {
+                issues.push(ValidationIssue::DuplicateNode { uid: node.uid });
+            }
+        }
+
+        for (index, edge) in self.edges.iter().enumerate() {
+            for uid in [edge.from_node_uid, edge.to_node_uid] {
+                if !nodes.contains_key(&uid) {
+                    issues.push(ValidationIssue::DanglingEdge {
+                        edge: index,
+                        node_uid: uid,
+                    });
+                }
+            }
+            if edge.from_node_uid != edge.to_node_uid {
+                continue;
+            }
+            let name = edge.token.kind.name();
+            let feeds_itself = nodes.get(&edge.from_node_uid).is_some_and(|node| {
+                node.function.consumes.iter().any(|t| t.kind.name() == name)
+                    && node
+                        .function
+                        .produces
+                        .iter()
+                        .flatten()
+                        .any(|t| t.kind.name() == name)
+            });
+            if !feeds_itself {
+                issues.push(ValidationIssue::SelfLoop {
+                    edge: index,
+                    node_uid: edge.from_node_uid,
+                });
+            }
+        }
+        issues
+    }
+}
+
+/// A referential integrity problem found by [`Graph::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// Two nodes share a UID.
+    DuplicateNode { uid: u32 },
+    /// Edge `edge` (an index into [`Graph::edges`]) references a missing node.
+    DanglingEdge { edge: usize, node_uid: u32 },
+    /// Edge `edge` leaves and enters the same node without feeding it back.
+    SelfLoop { edge: usize, node_uid: u32 },
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::DuplicateNode { uid } => write!(f, "duplicate node uid {}", uid),
+            ValidationIssue::DanglingEdge { edge, node_uid } => {
+                write!(f, "edge {} references missing node {}", edge, node_uid)
+            }
+            ValidationIssue::SelfLoop { edge, node_uid } => {
+                write!(f, "edge {} loops on node {}", edge, node_uid)
+            }
+        }
+    }
 }
 
 // --- Symbol Metadata ---
@@ -427,4 +930,7 @@ pub struct SymbolMetadata {
     pub definition_span: Span,
     /// List of spans wherever the symbol is referenced.
     pub occurrences: Vec<Span>,
+    /// Definitions of the same artifact in other files that were merged into this symbol.
+    /// These spans are also listed in `occurrences`.
+    pub merged_definitions: Vec<Span>,
 }
@@ -4,11 +4,11 @@
 //! Handles Url <-> FileId mapping, lazy loading of files, and resolving
 //! byte-offset Spans to LSP Line/Column Ranges.
 
-use crate::models::{FileId, Span};
+use crate::models::{DiagnosticReport, DiagnosticWithContext, FileId, Span};
 use std::collections::HashMap;
 use std::fs;
 use std::sync::atomic::{AtomicU32, Ordering};
-use tower_lsp::lsp_types::{Position, Range, Url};
+use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range, Url};
 
 /// Manages source files, their contents, and their unique IDs.
 ///
@@ -170,4 +170,63 @@ impl SourceManager {
 
         Range::new(start, end)
     }
+
+    /// Returns true if a `# tect:allow <code>` comment silences the diagnostic.
+    ///
+    /// The pragma must sit in the comment block directly above the line the
+    /// diagnostic starts on. Several codes may be listed, separated by spaces or commas.
+    pub fn is_suppressed(&self, diag: &DiagnosticWithContext) -> bool {
+        let (Some(code), Some(span)) = (diag.code, diag.span) else {
+            return false;
+        };
+        let Some(content) = self.get_content(span.file_id) else {
+            return false;
+        };
+        let line_start = content[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        content[..line_start]
+            .lines()
+            .rev()
+            .map(str::trim)
+            .take_while(|line| line.starts_with('#'))
+            .filter_map(|line| {
+                line.trim_start_matches('#')
+                    .trim()
+                    .strip_prefix("tect:allow")
+            })
+            .any(|codes| {
+                codes
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .any(|c| c == code)
+            })
+    }
+
+    /// Resolves a diagnostic to its file path and 1-based line/column.
+    pub fn resolve_diagnostic(&mut self, diag: &DiagnosticWithContext) -> DiagnosticReport {
+        let file_id = diag.span.map_or(diag.file_id, |s| s.file_id);
+        let file = self.get_uri(file_id).map(|uri| {
+            uri.to_file_path()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| uri.to_string())
+        });
+        let range = diag.span.map(|span| self.resolve_range(span));
+
+        let severity = match diag.severity {
+            DiagnosticSeverity::ERROR => "error",
+            DiagnosticSeverity::WARNING => "warning",
+            DiagnosticSeverity::INFORMATION => "information",
+            _ => "hint",
+        };
+
+        DiagnosticReport {
+            file,
+            line: range.map(|r| r.start.line + 1),
+            column: range.map(|r| r.start.character + 1),
+            end_line: range.map(|r| r.end.line + 1),
+            end_column: range.map(|r| r.end.character + 1),
+            severity: severity.to_string(),
+            message: diag.message.clone(),
+            node_uid: diag.node_uid,
+            code: diag.code.map(str::to_string),
+        }
+    }
 }
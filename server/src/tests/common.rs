@@ -1,5 +1,24 @@
+use crate::analyzer::Workspace;
+use crate::engine::Flow;
+use crate::models::Graph;
 use std::fs;
 use std::path::Path;
+use tower_lsp::lsp_types::Url;
+
+/// Analyzes an inline source as a single file.
+pub fn analyze(input: &str) -> Workspace {
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///test.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    workspace
+}
+
+/// Simulates an inline source into a graph.
+pub fn simulate(input: &str) -> Graph {
+    Flow::new(true).simulate(&analyze(input).structure)
+}
 
 pub fn assert_output(path_str: &str, actual: String) {
     let path = Path::new(path_str);
@@ -0,0 +1,57 @@
+use crate::diff::diff_graphs;
+use crate::engine::Flow;
+use crate::export::{mermaid, ExportOptions};
+use crate::models::Graph;
+use tower_lsp::lsp_types::Url;
+
+fn simulate(input: &str) -> Graph {
+    let mut workspace = crate::analyzer::Workspace::new();
+    let uri = Url::parse("file:///diff.tect").unwrap();
+    workspace.analyze(uri, Some(input.to_string()));
+    Flow::new(true).simulate(&workspace.structure)
+}
+
+const OLD: &str = "variable Raw\nvariable Report\nfunction Load\n    > Raw\nfunction Analyze Raw\n    > Report\n\nLoad\nAnalyze\n";
+const NEW: &str = "variable Raw\nvariable Cleaned\nvariable Report\nfunction Load\n    > Raw\nfunction Clean Raw\n    > Cleaned\nfunction Analyze Cleaned\n    > Report\n\nLoad\nClean\nAnalyze\n";
+
+#[test]
+fn test_diff_graphs_reports_changes() {
+    let old = simulate(OLD);
+    let new = simulate(NEW);
+
+    let diff = diff_graphs(&old, &new);
+    let added: Vec<_> = diff
+        .added_nodes
+        .iter()
+        .map(|n| n.function.name.as_str())
+        .collect();
+    assert_eq!(added, vec!["Clean"]);
+    assert!(diff.removed_nodes.is_empty());
+
+    let removed: Vec<_> = diff
+        .removed_edges
+        .iter()
+        .map(|e| e.token.kind.name())
+        .collect();
+    assert_eq!(removed, vec!["Raw"], "{:?}", diff.removed_edges);
+    assert_eq!(diff.added_edges.len(), 2, "{:?}", diff.added_edges);
+
+    assert!(diff_graphs(&new, &new).is_empty());
+}
+
+#[test]
+fn test_mermaid_diff_highlights_changes() {
+    let old = simulate(OLD);
+    let new = simulate(NEW);
+
+    let out = mermaid::export_diff(&old, &new, &ExportOptions::default());
+    let clean = new
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Clean")
+        .unwrap();
+    assert!(out.contains(&format!("class N_{} added", clean.uid)));
+    assert_eq!(out.matches("stroke:#16a34a").count(), 2);
+    assert_eq!(out.matches("stroke:#dc2626,stroke-width").count(), 1);
+    assert_eq!(out.matches("-.->").count(), 1);
+}
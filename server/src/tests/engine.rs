@@ -1,9 +1,12 @@
-use super::common::assert_output;
-use crate::engine::Flow;
+use super::common::{analyze, assert_output};
+use crate::engine::{Consumed, Flow, FlowConfig, TokenPool};
+use crate::export::ExportOptions;
+use crate::models::{Cardinality, EdgeRelation, Node};
 use crate::vis_js;
 use std::fs;
 use std::path::PathBuf;
-use tower_lsp::lsp_types::Url;
+use std::sync::Arc;
+use tower_lsp::lsp_types::{Position, Url};
 
 #[test]
 fn generate_blog_architecture_json() -> std::io::Result<()> {
@@ -20,6 +23,7 @@ fn generate_blog_architecture_json() -> std::io::Result<()> {
     // 2. Simulate Flow
     let mut flow = Flow::new(true);
     let graph = flow.simulate(structure);
+    assert_eq!(graph.validate(), vec![]);
 
     // 3. Serialize artifacts
     let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
@@ -40,7 +44,7 @@ fn generate_blog_architecture_json() -> std::io::Result<()> {
     let func_json = String::from_utf8(func_buf).expect("Generated JSON was not valid UTF-8");
 
     // C) HTML Output
-    let html_content = vis_js::generate_interactive_html(&graph);
+    let html_content = vis_js::generate_interactive_html(&graph, &ExportOptions::default());
 
     // 4. Save artifacts
     let output_dir = "../examples/test_outputs";
@@ -60,3 +64,744 @@ fn generate_blog_architecture_json() -> std::io::Result<()> {
 
     Ok(())
 }
+
+/// Flow errors must underline the flow step that failed, not the start of the file.
+#[test]
+fn test_flow_error_points_at_step() {
+    // Output has a producer (Finish), but it never runs
+    let input = "variable Input\nvariable Middle\nvariable Output\nfunction Start Input\n    > Middle\nfunction Finish Middle\n    > Output\nfunction Consume Output\n\nStart\nConsume\n";
+    let uri = Url::parse("file:///flow_error.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut flow = Flow::new(true);
+    flow.simulate(&workspace.structure);
+
+    let diag = flow
+        .diagnostics
+        .iter()
+        .find(|d| d.message.contains("'Consume' could not execute"))
+        .expect("Expected a starvation diagnostic for 'Consume'");
+
+    let range = workspace.source_manager.resolve_range(diag.span.unwrap());
+    assert_eq!(range.start, Position::new(10, 0));
+    assert_eq!(range.end, Position::new(10, 7));
+}
+
+/// Automatic ordering runs producers before consumers regardless of authoring order.
+#[test]
+fn test_simulate_auto_reorders_steps() {
+    let input = "constant Config\nvariable Raw\nvariable Parsed\nvariable Page\nfunction Load Config\n    > Raw\nfunction Parse Raw\n    > Parsed\nfunction Render Parsed\n    > Page\n\nLoad\nRender\nParse\n";
+    let uri = Url::parse("file:///auto_order.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut authored = Flow::new(true);
+    authored.simulate(&workspace.structure);
+    assert!(authored
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("'Render' could not execute")));
+
+    let mut auto = Flow::new(true);
+    let graph = auto.simulate_auto(&workspace.structure);
+    assert!(auto.diagnostics.is_empty(), "{:?}", auto.diagnostics);
+
+    let names: Vec<&str> = graph
+        .nodes
+        .iter()
+        .map(|n| n.function.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec!["InitialNode", "Load", "Parse", "Render", "FinalNode"]
+    );
+
+    // The same ordering can be requested through the simulation options
+    let mut configured = Flow::with_config(FlowConfig {
+        auto_order: true,
+        ..FlowConfig::default()
+    });
+    let configured_graph = configured.simulate(&workspace.structure);
+    assert!(configured.diagnostics.is_empty());
+    assert_eq!(configured_graph.nodes.len(), graph.nodes.len());
+}
+
+/// A producer/consumer cycle no step of which can fire is reported.
+#[test]
+fn test_simulate_auto_reports_unseeded_cycle() {
+    let input = "variable Ping\nvariable Pong\nvariable Ready\nfunction Start\n    > Ready\nfunction Serve Ping\n    > Pong\nfunction Return Pong\n    > Ping\n\nStart\nServe\nReturn\n";
+    let uri = Url::parse("file:///auto_cycle.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut flow = Flow::new(true);
+    flow.simulate_auto(&workspace.structure);
+
+    let diag = flow
+        .diagnostics
+        .iter()
+        .find(|d| d.message.contains("No valid execution order"))
+        .expect("Expected a cycle diagnostic");
+    assert!(diag.message.contains("Serve, Return"));
+}
+
+/// A cycle entered through a token of the initial pool runs like the authored flow.
+#[test]
+fn test_simulate_auto_runs_seeded_cycle() {
+    let input = "variable Ping\nvariable Pong\nfunction Serve Ping\n    > Pong\nfunction Reply Pong\n    > Ping\n\nReply\nServe\n";
+    let uri = Url::parse("file:///auto_seeded.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut authored = Flow::new(true);
+    let expected = authored.simulate(&workspace.structure);
+    assert!(
+        authored.diagnostics.is_empty(),
+        "{:?}",
+        authored.diagnostics
+    );
+
+    let mut auto = Flow::new(true);
+    let graph = auto.simulate_auto(&workspace.structure);
+    assert!(auto.diagnostics.is_empty(), "{:?}", auto.diagnostics);
+    assert_eq!(graph, expected);
+}
+
+/// Missing optional inputs do not starve a function; present ones still create edges.
+#[test]
+fn test_optional_inputs() {
+    let input = "variable Request\nvariable Cache\nvariable Response\nfunction Serve Request, Cache?\n    > Response\nfunction Warm Response\n    > Request, Cache\n\nServe\nWarm\nServe\n";
+    let uri = Url::parse("file:///optional.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+    assert!(
+        workspace.structure.diagnostics.is_empty(),
+        "{:?}",
+        workspace.structure.diagnostics
+    );
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    assert!(flow.diagnostics.is_empty(), "{:?}", flow.diagnostics);
+
+    // The first Serve only receives Request; the optional Cache is not seeded
+    let initial = graph
+        .nodes
+        .iter()
+        .find(|n| n.is_artificial_graph_start)
+        .unwrap();
+    assert!(graph
+        .edges
+        .iter()
+        .filter(|e| e.from_node_uid == initial.uid)
+        .all(|e| e.token.kind.name() == "Request"));
+
+    // Once Warm produces it, the Cache edge is marked optional
+    let cache_edge = graph
+        .edges
+        .iter()
+        .find(|e| e.token.kind.name() == "Cache" && e.relation == EdgeRelation::DataFlow)
+        .expect("Expected Cache to flow into Serve");
+    assert!(cache_edge.token.optional);
+}
+
+/// Outputs cannot be marked optional.
+#[test]
+fn test_optional_output_rejected() {
+    let input = "variable A\nvariable B\nfunction F A\n    > B?\n";
+    let uri = Url::parse("file:///optional_output.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+    assert!(workspace
+        .structure
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("cannot be optional")));
+}
+
+/// Immutable constants feed every consumer, while variables are used up by the first.
+#[test]
+fn test_immutable_tokens_survive_consumption() {
+    let input = "constant Settings\nvariable Job\nfunction First Settings, Job\nfunction Second Settings\nfunction Third Job\n\nFirst\nSecond\nThird\n";
+    let uri = Url::parse("file:///immutable.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+
+    let settings_edges = graph
+        .edges
+        .iter()
+        .filter(|e| e.token.kind.name() == "Settings")
+        .count();
+    assert_eq!(settings_edges, 2);
+
+    let starved: Vec<&str> = flow
+        .diagnostics
+        .iter()
+        .map(|d| d.message.as_str())
+        .collect();
+    assert_eq!(starved.len(), 1, "{:?}", starved);
+    assert!(starved[0].contains("'Third' could not execute. Missing inputs: [Job]"));
+}
+
+/// Variables tagged `@immutable` feed every consumer and do not end the flow once used.
+#[test]
+fn test_immutable_variables_survive_consumption() {
+    let input = "variable Config @immutable\nvariable Job\nfunction Load\n    > Config, Job\nfunction First Config, Job\nfunction Second Config\n\nLoad\nFirst\nSecond\n";
+    let workspace = analyze(input);
+    let config = workspace.structure.artifacts["Config"].clone();
+    assert!(config.is_immutable());
+    assert!(!workspace.structure.artifacts["Job"].is_immutable());
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    assert!(flow.diagnostics.is_empty(), "{:?}", flow.diagnostics);
+
+    let consumers: Vec<&str> = graph
+        .edges
+        .iter()
+        .filter(|e| e.token.kind.name() == "Config")
+        .map(|e| graph.nodes.iter().find(|n| n.uid == e.to_node_uid).unwrap())
+        .map(|n| n.function.name.as_str())
+        .collect();
+    assert_eq!(consumers, vec!["First", "Second"]);
+}
+
+/// A collection feeding a unitary requirement is marked as a fan-out edge.
+#[test]
+fn test_collection_to_unitary_is_fan_out() {
+    let input = "variable File\nvariable Page\nfunction Scan\n    > [File]\nfunction Render File\n    > Page\nfunction Index [Page]\n\nScan\nRender\nIndex\n";
+    let uri = Url::parse("file:///fan_out.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+
+    let relation_of = |name: &str| {
+        graph
+            .edges
+            .iter()
+            .find(|e| e.token.kind.name() == name && e.relation != EdgeRelation::TerminalFlow)
+            .map(|e| e.relation.clone())
+    };
+    assert_eq!(relation_of("File"), Some(EdgeRelation::FanOut));
+    assert_eq!(relation_of("Page"), Some(EdgeRelation::DataFlow));
+
+    let dot = crate::export::dot::export(&graph, &ExportOptions::default());
+    assert!(dot.contains("arrowhead=\"normalnormal\""));
+}
+
+/// Independent simulations, even on parallel threads, serialize identically.
+#[test]
+fn test_simulation_is_deterministic() {
+    let run = || {
+        std::thread::spawn(|| {
+            let input_path = "../examples/dsbg.tect";
+            let content = fs::read_to_string(input_path).unwrap();
+            let uri = Url::from_file_path(fs::canonicalize(input_path).unwrap()).unwrap();
+
+            let mut workspace = crate::analyzer::Workspace::new();
+            workspace.analyze(uri, Some(content));
+
+            let mut flow = Flow::new(true);
+            let graph = flow.simulate(&workspace.structure);
+            let messages: Vec<String> = flow.diagnostics.into_iter().map(|d| d.message).collect();
+            (serde_json::to_string(&graph).unwrap(), messages)
+        })
+    };
+
+    let (a, b) = (run(), run());
+    assert_eq!(a.join().unwrap(), b.join().unwrap());
+}
+
+/// Comments directly above a flow step document that invocation in the graph.
+#[test]
+fn test_flow_step_docs_reach_nodes() {
+    let input = "variable Job\nfunction Run Job\n\n# Runs last to flush \"pending\" work\nRun\n";
+    let uri = Url::parse("file:///step_docs.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+    assert!(workspace.structure.diagnostics.is_empty());
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    let node = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Run")
+        .unwrap();
+    assert_eq!(
+        node.documentation.as_deref(),
+        Some("Runs last to flush \"pending\" work")
+    );
+
+    let dot = crate::export::dot::export(&graph, &ExportOptions::default());
+    assert!(dot.contains("tooltip=\"Runs last to flush \\\"pending\\\" work\""));
+}
+
+/// A function listed twice is one node, carrying the edges and docs of both steps.
+#[test]
+fn test_repeated_steps_share_node() {
+    let input = "variable Job\nvariable Done\nfunction Queue\n    > Job\nfunction Run Job\n    > Done\n\n# First batch\nQueue\nRun\n# Second batch\nQueue\n# Retry\nRun\n";
+    let uri = Url::parse("file:///repeated.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+    let graph = Flow::new(false).simulate(&workspace.structure);
+
+    let runs: Vec<&Node> = graph
+        .nodes
+        .iter()
+        .filter(|n| n.function.name == "Run")
+        .collect();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].documentation.as_deref(), Some("Retry"));
+    let queue = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Queue")
+        .unwrap();
+    assert_eq!(
+        queue.documentation.as_deref(),
+        Some("First batch\n\nSecond batch")
+    );
+    let jobs = graph
+        .edges
+        .iter()
+        .filter(|e| e.from_node_uid == queue.uid && e.to_node_uid == runs[0].uid)
+        .count();
+    assert_eq!(jobs, 2);
+    assert!(graph.validate().is_empty());
+}
+
+/// A single constant feeds every consumer without being used up.
+#[test]
+fn test_constant_feeds_three_consumers() {
+    let input = "constant Settings\nconstant Templates\nvariable Files\nvariable Html\nfunction LoadTemplates Settings\n    > Templates\nfunction ScanFS Settings\n    > Files\nfunction RenderHTML Settings, Templates, Files\n    > Html\n\nLoadTemplates\nScanFS\nRenderHTML\n";
+    let uri = Url::parse("file:///shared_constant.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    assert!(flow.diagnostics.is_empty(), "{:?}", flow.diagnostics);
+
+    let mut consumers: Vec<&str> = graph
+        .edges
+        .iter()
+        .filter(|e| e.token.kind.name() == "Settings")
+        .filter_map(|e| graph.nodes.iter().find(|n| n.uid == e.to_node_uid))
+        .map(|n| n.function.name.as_str())
+        .collect();
+    consumers.sort();
+    assert_eq!(consumers, vec!["LoadTemplates", "RenderHTML", "ScanFS"]);
+
+    // A consumed constant is not reported as a leftover
+    assert!(!graph
+        .edges
+        .iter()
+        .any(|e| e.token.kind.name() == "Settings" && e.relation == EdgeRelation::TerminalFlow));
+}
+
+/// Constants produced by a function but never consumed are reported at the producer.
+#[test]
+fn test_unused_produced_constant_is_reported() {
+    let input = "constant Settings\nconstant Templates\nvariable Html\nfunction LoadTemplates Settings\n    > Templates\nfunction Render Settings\n    > Html\n\nLoadTemplates\nRender\n";
+    let uri = Url::parse("file:///unused_constant.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut flow = Flow::new(true);
+    flow.simulate(&workspace.structure);
+
+    let infos: Vec<_> = flow
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == tower_lsp::lsp_types::DiagnosticSeverity::INFORMATION)
+        .collect();
+    assert_eq!(infos.len(), 1, "{:?}", flow.diagnostics);
+    assert!(infos[0]
+        .message
+        .contains("'Templates' is produced by 'LoadTemplates'"));
+
+    let range = workspace
+        .source_manager
+        .resolve_range(infos[0].span.unwrap());
+    assert_eq!(range.start, Position::new(3, 9));
+}
+
+/// Named flows are independent entry points; the first one is the default.
+#[test]
+fn test_named_flows() {
+    let input = "variable Request\nvariable Report\nfunction Serve Request\n    > Report\nfunction Build\n    > Report\n\nflow Build {\n    Build\n}\n\nflow Serve {\n    # Handles one request\n    Serve\n}\n";
+    let uri = Url::parse("file:///named_flows.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+    let structure = &workspace.structure;
+    assert!(
+        structure.diagnostics.is_empty(),
+        "{:?}",
+        structure.diagnostics
+    );
+    assert_eq!(structure.flow_order, vec!["Build", "Serve"]);
+
+    let names = |graph: &crate::models::Graph| -> Vec<String> {
+        graph
+            .nodes
+            .iter()
+            .filter(|n| !n.is_artificial_graph_start && !n.is_artificial_graph_end)
+            .map(|n| n.function.name.clone())
+            .collect()
+    };
+
+    let graph = Flow::new(true).simulate(structure);
+    assert_eq!(names(&graph), vec!["Build"]);
+
+    let steps = structure.named_flow("Serve").unwrap();
+    assert_eq!(
+        steps[0].documentation.as_deref(),
+        Some("Handles one request")
+    );
+    let graph = Flow::new(true).simulate_steps(structure, steps);
+    assert_eq!(names(&graph), vec!["Serve"]);
+
+    assert!(structure.named_flow("Deploy").is_none());
+}
+
+#[test]
+fn test_duplicate_flow_is_error() {
+    let input = "function Build\n\nflow Build {\n    Build\n}\n\nflow Build {\n}\n";
+    let uri = Url::parse("file:///duplicate_flow.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+    assert!(workspace
+        .structure
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("Flow 'Build' is already defined")));
+}
+
+/// A `> None` branch routes the consumed inputs to the FinalNode.
+#[test]
+fn test_none_output_routes_inputs_to_final_node() {
+    let input = "variable Message\nfunction Compose\n    > Message\nfunction Log Message\n    > None\n\nCompose\nLog\n";
+    let uri = Url::parse("file:///none_output.tect").unwrap();
+
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(uri, Some(input.to_string()));
+    assert!(
+        workspace.structure.diagnostics.is_empty(),
+        "{:?}",
+        workspace.structure.diagnostics
+    );
+    assert_eq!(workspace.structure.catalog["Log"].produces, vec![vec![]]);
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    assert!(flow.diagnostics.is_empty(), "{:?}", flow.diagnostics);
+
+    let uid = |name: &str| {
+        graph
+            .nodes
+            .iter()
+            .find(|n| n.function.name == name)
+            .unwrap()
+            .uid
+    };
+    let terminal: Vec<_> = graph
+        .edges
+        .iter()
+        .filter(|e| e.relation == EdgeRelation::TerminalFlow)
+        .collect();
+    assert_eq!(terminal.len(), 1, "{:?}", terminal);
+    assert_eq!(terminal[0].from_node_uid, uid("Log"));
+    assert_eq!(terminal[0].to_node_uid, uid("FinalNode"));
+    assert_eq!(terminal[0].token.kind.name(), "Message");
+}
+
+/// A comment between output lines does not add or shift branches.
+#[test]
+fn test_comment_between_outputs() {
+    let input = "variable Page\nerror Missing\nfunction Load\n    > Page\n    # the file may be gone\n    | Missing\nfunction Show Page\n\nLoad\nShow\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///comments.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    assert!(
+        workspace.structure.diagnostics.is_empty(),
+        "{:?}",
+        workspace.structure.diagnostics
+    );
+    assert_eq!(workspace.structure.catalog["Load"].produces.len(), 2);
+}
+
+/// Flow diagnostics about a function point at its graph node.
+#[test]
+fn test_diagnostics_reference_nodes() {
+    let input = "variable Page\nerror Missing\nvariable Unused\nfunction Load\n    > Page\n    | Missing\nfunction Spare\n    > Unused\nfunction Show Page, Unused\n\nLoad\nShow\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///node_refs.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+
+    let node_of = |message: &str| {
+        flow.diagnostics
+            .iter()
+            .find(|d| d.message.contains(message))
+            .and_then(|d| d.node_uid)
+    };
+    let load = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Load")
+        .unwrap();
+    assert_eq!(node_of("Unhandled Error"), Some(load.uid));
+    assert_eq!(
+        node_of("'Show' could not execute"),
+        Some(workspace.structure.catalog["Show"].uid)
+    );
+    // Every link resolves to a node of the returned graph
+    assert!(flow
+        .diagnostics
+        .iter()
+        .filter_map(|d| d.node_uid)
+        .all(|uid| graph.nodes.iter().any(|n| n.uid == uid)));
+
+    let diag = flow
+        .diagnostics
+        .iter()
+        .find(|d| d.node_uid.is_some())
+        .unwrap();
+    let report = workspace.source_manager.resolve_diagnostic(diag);
+    assert_eq!(report.node_uid, diag.node_uid);
+}
+
+#[test]
+fn test_assertions_checked_against_graph() {
+    let input = "variable Page\nvariable Html\nerror Missing\nfunction Load\n    > Page\n    | Missing\nfunction Render Page\n    > Html\nfunction Audit\n\nassert Load -> Render reachable\nassert Render -> Load reachable\nassert Audit -> Render reachable\nassert no Missing unhandled\n\nLoad\nRender\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///assertions.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    assert_eq!(workspace.structure.assertions.len(), 4);
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    let failed = crate::assertions::evaluate_assertions(&graph, &workspace.structure.assertions);
+    let messages: Vec<&str> = failed.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Assertion failed: 'Load' is not reachable from 'Render'.",
+            "Assertion failed: 'Audit' is not part of the simulated flow.",
+            "Assertion failed: 'Missing' from 'Load' is never handled.",
+        ]
+    );
+    let load = workspace.structure.catalog["Load"].uid;
+    assert_eq!(failed[2].node_uid, Some(load));
+    assert_eq!(failed[0].span, Some(workspace.structure.assertions[1].span));
+}
+
+/// Errors tagged `@terminal` end at `FinalNode` and are not reported as unhandled.
+#[test]
+fn test_terminal_errors_route_to_final_node() {
+    let input = "variable Page\nerror Timeout @terminal\nerror Missing\nfunction Load\n    > Page\n    | Timeout\n    | Missing\nfunction Show Page\n\nLoad\nShow\n\nassert no Timeout unhandled\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///terminal.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+
+    let target = |error: &str| {
+        let edge = graph
+            .edges
+            .iter()
+            .find(|e| e.token.kind.name() == error)
+            .unwrap();
+        let node = graph
+            .nodes
+            .iter()
+            .find(|n| n.uid == edge.to_node_uid)
+            .unwrap();
+        (node.function.name.as_str(), edge.relation.clone())
+    };
+    assert_eq!(target("Timeout"), ("FinalNode", EdgeRelation::TerminalFlow));
+    assert_eq!(target("Missing"), ("FatalErrors", EdgeRelation::ErrorFlow));
+
+    let unhandled: Vec<&str> = flow
+        .diagnostics
+        .iter()
+        .filter(|d| d.code == Some("unhandled-error"))
+        .map(|d| d.message.as_str())
+        .collect();
+    assert_eq!(
+        unhandled,
+        ["Unhandled Error: 'Missing' is produced by 'Load' but never consumed (rescued)."]
+    );
+    let failed = crate::assertions::evaluate_assertions(&graph, &workspace.structure.assertions);
+    assert!(failed.is_empty(), "{:?}", failed);
+}
+
+/// Consumption reports which requirement each edge satisfied, e.g. a unit fed by a collection.
+#[test]
+fn test_consumed_maps_requirements_to_edges() {
+    let input = "variable Page\nvariable Html\nfunction Crawl\n    > [Page]\nfunction Render Page\n    > Html\n\nCrawl\nRender\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///fan_out.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let catalog = &workspace.structure.catalog;
+    let crawl = Arc::new(Node::new(catalog["Crawl"].clone()));
+    let render = Arc::new(Node::new(catalog["Render"].clone()));
+
+    let mut pool = TokenPool::new(vec![], crawl.clone());
+    pool.produce(catalog["Crawl"].produces[0].clone(), crawl.clone());
+    let Consumed::AllTokens(consumed) =
+        pool.try_to_consume(catalog["Render"].consumes.clone(), render.clone())
+    else {
+        panic!("Render should find its input");
+    };
+
+    assert_eq!(consumed.len(), 1);
+    let (requirement, edge) = &consumed[0];
+    assert_eq!(requirement.kind.name(), "Page");
+    assert_eq!(requirement.cardinality, Cardinality::Unitary);
+    assert_eq!(edge.token.cardinality, Cardinality::Collection);
+    assert_eq!(edge.relation, EdgeRelation::FanOut);
+    assert_eq!(
+        (edge.from_node_uid, edge.to_node_uid),
+        (crawl.uid, render.uid)
+    );
+}
+
+#[test]
+fn test_token_matching_rules() {
+    use crate::models::{Constant, Kind, Token, Variable};
+    let page = Kind::Variable(Arc::new(Variable::new("Page".into(), None)));
+    let other = Kind::Variable(Arc::new(Variable::new("Html".into(), None)));
+    let config = Kind::Constant(Arc::new(Constant::new("Config".into(), None)));
+
+    let req = Token::new(page.clone(), Cardinality::Unitary, 1);
+    // Instance, cardinality and optionality do not matter, only the kind
+    assert!(TokenPool::matches(
+        &req,
+        &Token::new(page.clone(), Cardinality::Unitary, 2)
+    ));
+    assert!(TokenPool::matches(
+        &req,
+        &Token::new(page.clone(), Cardinality::Collection, 3)
+    ));
+    let mut optional = req.clone();
+    optional.optional = true;
+    assert!(TokenPool::matches(&optional, &req));
+
+    assert!(!TokenPool::matches(
+        &req,
+        &Token::new(other, Cardinality::Unitary, 4)
+    ));
+    assert!(!TokenPool::matches(
+        &req,
+        &Token::new(config, Cardinality::Unitary, 5)
+    ));
+}
+
+/// Inputs nothing produces are seeded up front, wherever the flow needs them.
+#[test]
+fn test_external_inputs_seed_the_flow() {
+    let input = "constant Config\nvariable Request\nvariable Page\nvariable Html\nerror Missing\nfunction Load Request\n    > Page\nfunction Render Page, Config\n    > Html\nfunction Recover Missing\n    > Html\n\nLoad\nRender\n";
+    let workspace = analyze(input);
+
+    let inputs = Flow::required_external_inputs(&workspace.structure);
+    let names: Vec<&str> = inputs.iter().map(|t| t.kind.name()).collect();
+    assert_eq!(names, ["Request", "Config"]);
+
+    // Config is only needed by the second step, yet Render does not starve
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    assert!(
+        !flow
+            .diagnostics
+            .iter()
+            .any(|d| d.message.contains("could not execute")),
+        "{:?}",
+        flow.diagnostics
+    );
+    let initial = graph
+        .nodes
+        .iter()
+        .find(|n| n.is_artificial_graph_start)
+        .unwrap();
+    assert!(graph
+        .edges
+        .iter()
+        .any(|e| e.from_node_uid == initial.uid && e.token.kind.name() == "Config"));
+}
+
+/// Declared steps are never pruned, even when they starved and have no edges.
+#[test]
+fn test_starved_steps_are_kept() {
+    let input = "variable Page\nvariable Draft\nfunction Ping\nfunction Publish Draft\n    > Page\nfunction Spare\n    > Draft\nfunction Show Page\n\nPing\nPublish\nShow\n";
+    let workspace = analyze(input);
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    let names: Vec<&str> = graph
+        .nodes
+        .iter()
+        .map(|n| n.function.name.as_str())
+        .collect();
+    // `Ping` ran with an empty contract; `Publish` and `Show` starved but stay
+    assert_eq!(names, ["Ping", "Publish", "Show"]);
+    let starved = flow
+        .diagnostics
+        .iter()
+        .find(|d| d.message.contains("'Publish' could not execute"))
+        .unwrap();
+    assert!(graph.nodes.iter().any(|n| Some(n.uid) == starved.node_uid));
+
+    let graph = Flow::with_config(FlowConfig {
+        keep_isolated: true,
+        ..FlowConfig::default()
+    })
+    .simulate(&workspace.structure);
+    assert_eq!(graph.nodes.len(), 3);
+}
+
+/// Reachable functions are those whose node received or emitted a token.
+#[test]
+fn test_reachable_functions() {
+    let input = "variable Page\nvariable Draft\nfunction Load\n    > Page\nfunction Show Page\nfunction Ping\nfunction Edit Draft\n    > Page\nfunction Unused\n    > Draft\n\nLoad\nShow\nPing\nEdit\n";
+    let workspace = analyze(input);
+    let mut flow = Flow::new(true);
+    flow.simulate(&workspace.structure);
+
+    let mut reachable: Vec<String> = flow.reachable_functions().into_iter().collect();
+    reachable.sort();
+    // `Ping` runs without a contract, `Edit` starved and `Unused` is never invoked
+    assert_eq!(reachable, ["Load", "Ping", "Show"]);
+}
@@ -0,0 +1,797 @@
+use super::common::{analyze, simulate};
+use crate::engine::Flow;
+use crate::export::theme::{self, truncate_label, ThemeConfig};
+use crate::export::{
+    dictionary, dot, mermaid, sanitize_id, tikz, vis_js, ErrorLayout, ExportOptions, Highlight,
+};
+use crate::metrics::{degree_map, error_paths};
+use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range, Url};
+
+#[test]
+fn test_truncate_label() {
+    assert_eq!(truncate_label("Short", 10), "Short");
+    assert_eq!(truncate_label("ExactlyTen", 10), "ExactlyTen");
+    assert_eq!(truncate_label("TheVeryLongName", 8), "TheVery\u{2026}");
+}
+
+/// Long labels are cut in every exporter, with the full name kept in DOT tooltips.
+#[test]
+fn test_max_label_len_applies_to_exports() {
+    let input = "variable TheVeryLongDescriptiveArtifactName\nfunction Produce\n    > TheVeryLongDescriptiveArtifactName\nfunction Consume TheVeryLongDescriptiveArtifactName\n\nProduce\nConsume\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///labels.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+
+    let options = ExportOptions {
+        max_label_len: Some(10),
+        ..ExportOptions::default()
+    };
+
+    let dot = dot::export(&graph, &options);
+    assert!(dot.contains("label=\"TheVeryLo\u{2026}\""));
+    assert!(dot.contains("edgetooltip=\"TheVeryLongDescriptiveArtifactName\""));
+
+    let mmd = mermaid::export(&graph, &options);
+    assert!(mmd.contains("|TheVeryLo\u{2026}|"));
+    assert!(!mmd.contains("TheVeryLongDescriptiveArtifactName"));
+}
+
+/// Function and token documentation become DOT tooltips.
+#[test]
+fn test_dot_tooltips_from_docs() {
+    let input = "# Parsed \"front matter\"\nvariable Meta\n# Reads the header\nfunction Read\n    > Meta\nfunction Use Meta\n\nRead\nUse\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///tooltips.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let dot = dot::export(&graph, &ExportOptions::default());
+
+    assert!(dot.contains("tooltip=\"Reads the header\""));
+    assert!(dot.contains("edgetooltip=\"Parsed \\\"front matter\\\"\""));
+}
+
+/// With `token_notes`, documented edge labels are numbered and explained below the TikZ picture.
+#[test]
+fn test_tikz_token_notes() {
+    let input = "# 50% of the payload\nvariable Meta\nvariable Body\nfunction Read\n    > Meta, Body\nfunction Use Meta, Body\n\nRead\nUse\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///notes.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+
+    assert!(!tikz::export(&graph, &ExportOptions::default()).contains("$^{1}$"));
+
+    let options = ExportOptions {
+        token_notes: true,
+        ..ExportOptions::default()
+    };
+    let tex = tikz::export(&graph, &options);
+    assert!(tex.contains("edge label=\"Meta$^{1}$\""), "{}", tex);
+    assert!(tex.contains("edge label=\"Body\""));
+    assert!(tex.contains("{$^{1}$ \\textbf{Meta}: 50\\% of the payload};"));
+}
+
+/// With `node_captions`, function docs are written below their TikZ nodes.
+#[test]
+fn test_tikz_node_captions() {
+    let input = "variable Page\n# Loads 100% of a page\n# from_disk\nfunction Load\n    > Page\nfunction Show Page\n\nLoad\nShow\n";
+    let graph = simulate(input);
+
+    assert!(!tikz::export(&graph, &ExportOptions::default()).contains("tect_caption"));
+
+    let options = ExportOptions {
+        node_captions: true,
+        ..ExportOptions::default()
+    };
+    let tex = tikz::export(&graph, &options);
+    assert!(
+        tex.contains("label={[tect_caption]below:{Loads 100\\% of a page\\\\from\\_disk}}"),
+        "{}",
+        tex
+    );
+    let show = tex.lines().find(|l| l.contains("as=\"Show\"")).unwrap();
+    assert!(!show.contains("label="));
+}
+
+/// Quoted names are escaped for LaTeX in TikZ node and edge labels.
+#[test]
+fn test_tikz_escapes_quoted_names() {
+    let input = "# Half the pages\nvariable \"Out & 100%\"\nfunction \"Load & Save_x\"\n    > \"Out & 100%\"\nfunction Show \"Out & 100%\"\n\n\"Load & Save_x\"\nShow\n";
+    let graph = simulate(input);
+
+    let options = ExportOptions {
+        token_notes: true,
+        ..ExportOptions::default()
+    };
+    let tex = tikz::export(&graph, &options);
+    assert!(tex.contains("as=\"Load \\& Save\\_x\""), "{}", tex);
+    assert!(
+        tex.contains("edge label=\"Out \\& 100\\%$^{1}$\""),
+        "{}",
+        tex
+    );
+}
+
+/// With `show_degree`, function labels carry their fan-in and fan-out.
+#[test]
+fn test_show_degree_annotates_labels() {
+    let input =
+        "variable A\nvariable B\nfunction Make\n    > A, B\nfunction Render A, B\n\nMake\nRender\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///degree.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+
+    let degrees = degree_map(&graph);
+    let render = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Render")
+        .unwrap();
+    assert_eq!(degrees.get(&render.uid), Some(&(2, 0)));
+    assert_eq!(degrees.len(), 2);
+
+    let options = ExportOptions {
+        show_degree: true,
+        ..ExportOptions::default()
+    };
+    let dot = dot::export(&graph, &options);
+    assert!(dot.contains("<B>Render (2→0)</B>"));
+    assert!(dot.contains("<B>Make (0→2)</B>"));
+
+    let data = vis_js::produce_vis_data(&graph, &options);
+    assert!(data.nodes.iter().any(|n| n.label == " Render (2→0) "));
+}
+
+/// Tokens inherit their producer's group, whose color their edges take with `color_edges_by_group`.
+#[test]
+fn test_color_edges_by_group() {
+    let input = "group Web\nvariable Page\nWeb function Load\n    > Page\nfunction Show Page\n\nLoad\nShow\n";
+    let graph = simulate(input);
+    let page = graph
+        .edges
+        .iter()
+        .find(|e| e.token.kind.name() == "Page")
+        .unwrap();
+    assert_eq!(page.token.group.as_ref().unwrap().name, "Web");
+
+    let (group_hex, group_latex) = theme::Theme::get_group_color("Web");
+    let by_group = ExportOptions {
+        color_edges_by_group: true,
+        ..ExportOptions::default()
+    };
+    for (options, hex, latex) in [
+        (&ExportOptions::default(), theme::VARIABLE_COLOR, "TectGray"),
+        (&by_group, group_hex.as_str(), group_latex.as_str()),
+    ] {
+        let dot = dot::export(&graph, options);
+        assert!(
+            dot.contains(&format!("[label=\"Page\", color=\"{}\"", hex)),
+            "{}",
+            dot
+        );
+
+        let data = vis_js::produce_vis_data(&graph, options);
+        let edge = data.edges.iter().find(|e| e.label == "Page").unwrap();
+        assert_eq!(edge.color, hex);
+
+        let tex = tikz::export(&graph, options);
+        assert!(
+            tex.contains(&format!("color={}, edge label=\"Page\"", latex)),
+            "{}",
+            tex
+        );
+    }
+}
+
+/// Functions without outputs are drawn as sinks where their inputs end.
+#[test]
+fn test_void_function_is_drawn_as_sink() {
+    let input = "variable Html\nfunction Render\n    > Html\nfunction WriteToDisk Html\n\nRender\nWriteToDisk\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///sink.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let sink = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "WriteToDisk")
+        .unwrap();
+    assert!(graph
+        .edges
+        .iter()
+        .any(|e| e.to_node_uid == sink.uid && e.token.kind.name() == "Html"));
+
+    let dot = dot::export(&graph, &ExportOptions::default());
+    assert!(
+        dot.contains(&format!(
+            "N_{} [label=<<B>WriteToDisk</B>>, shape=box, peripheries=2",
+            sink.uid
+        )),
+        "{}",
+        dot
+    );
+
+    let mmd = mermaid::export(&graph, &ExportOptions::default());
+    assert!(mmd.contains(&format!("N_{}[/\"WriteToDisk\"\\]", sink.uid)));
+
+    let data = vis_js::produce_vis_data(&graph, &ExportOptions::default());
+    let dashed = |uid: u32| {
+        let node = data.nodes.iter().find(|n| n.id == uid).unwrap();
+        node.shape_properties
+            .as_ref()
+            .is_some_and(|p| p.border_dashes)
+    };
+    assert!(dashed(sink.uid));
+    assert!(graph
+        .nodes
+        .iter()
+        .filter(|n| n.uid != sink.uid)
+        .all(|n| !dashed(n.uid)));
+}
+
+/// `ErrorLayout::Return` draws error edges back toward their producer.
+#[test]
+fn test_error_layout_return() {
+    let input = "variable Page\nerror Missing\nfunction Load\n    > Page\n    | Missing\nfunction Show Page\n\nLoad\nShow\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///errors.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+
+    let forward = dot::export(&graph, &ExportOptions::default());
+    assert!(!forward.contains("dir=\"back\""));
+
+    let options = ExportOptions {
+        error_layout: ErrorLayout::Return,
+        ..ExportOptions::default()
+    };
+    let dot = dot::export(&graph, &options);
+    assert_eq!(dot.matches("dir=\"back\"").count(), 1, "{}", dot);
+
+    let data = vis_js::produce_vis_data(&graph, &options);
+    let error_edge = data.edges.iter().find(|e| e.label == "Missing").unwrap();
+    assert!(error_edge.arrows.to.is_none());
+    assert_eq!(error_edge.arrows.from.as_ref().unwrap().kind, "circle");
+    assert!(error_edge.smooth.is_some());
+    assert!(data
+        .edges
+        .iter()
+        .filter(|e| e.label != "Missing")
+        .all(|e| e.arrows.to.is_some() && e.arrows.from.is_none() && e.smooth.is_none()));
+}
+
+/// Title and generation time are captioned in every exporter.
+#[test]
+fn test_caption_title_and_timestamp() {
+    let input = "variable Data\nfunction Load\n    > Data\n\nLoad\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///caption.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let mut graph = Flow::new(true).simulate(&workspace.structure);
+    graph.title = Some("Data_Pipeline".to_string());
+
+    let options = ExportOptions {
+        timestamp: Some("2024-01-02 03:04 UTC".to_string()),
+        ..ExportOptions::default()
+    };
+
+    let dot = dot::export(&graph, &options);
+    assert!(
+        dot.contains("label=\"Data_Pipeline\\nGenerated 2024-01-02 03:04 UTC\""),
+        "{}",
+        dot
+    );
+
+    let mmd = mermaid::export(&graph, &options);
+    assert!(mmd.starts_with("---\ntitle: \"Data_Pipeline · Generated 2024-01-02 03:04 UTC\"\n---"));
+
+    let tex = tikz::export(&graph, &options);
+    assert!(
+        tex.contains("{Data\\_Pipeline\\\\Generated 2024-01-02 03:04 UTC};"),
+        "{}",
+        tex
+    );
+
+    let html = vis_js::generate_interactive_html(&graph, &options);
+    assert!(html.contains("<title>Data_Pipeline</title>"));
+    assert!(html.contains("<div>Generated 2024-01-02 03:04 UTC</div>"));
+
+    assert!(!dot::export(&graph, &ExportOptions::default()).contains("Generated"));
+}
+
+/// Edge roles get distinct vis.js arrowheads.
+#[test]
+fn test_vis_arrow_types_by_relation() {
+    let input = "variable Page\nvariable Report\nvariable Summary\nerror Missing\nfunction Load\n    > Page\n    | Missing\nfunction Collect\n    > [Report]\nfunction Archive [Report]\n    > Summary\nfunction Publish Summary\n\nLoad\nCollect\nArchive\nPublish\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///arrows.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let data = vis_js::produce_vis_data(&graph, &ExportOptions::default());
+
+    let head = |label: &str| {
+        let edge = data.edges.iter().find(|e| e.label == label).unwrap();
+        edge.arrows.to.as_ref().unwrap().kind.clone()
+    };
+    assert_eq!(head("Missing"), "circle");
+    assert_eq!(head("Page"), "bar");
+    assert_eq!(head("[Report]"), "crow");
+    assert_eq!(head("Summary"), "arrow");
+}
+
+/// The HTML legend uses the theme colors, including one entry per group.
+#[test]
+fn test_html_legend_matches_theme() {
+    let input = "variable Data\ngroup Storage\nStorage function Load\n    > Data\nfunction Show Data\n\nLoad\nShow\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///legend.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let html = vis_js::generate_interactive_html(&graph, &ExportOptions::default());
+
+    assert!(html.contains("<details id=\"legend\" open><summary>Legend</summary>"));
+    assert!(html.contains(&format!(
+        "border-top:1.5px dashed {}\"></span>Constant",
+        crate::export::theme::CONSTANT_COLOR
+    )));
+    let (group_color, _) = crate::export::theme::Theme::get_group_color("Storage");
+    assert!(html.contains(&format!(
+        "3px solid {}\"></span>Group: Storage",
+        group_color
+    )));
+}
+
+/// DOT layout attributes are only written when set.
+#[test]
+fn test_dot_layout_attributes() {
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///layout.tect").unwrap(),
+        Some("variable Data\nfunction Load\n    > Data\n\nLoad\n".to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+
+    let plain = dot::export(&graph, &ExportOptions::default());
+    assert!(!plain.contains("ratio=") && !plain.contains("nodesep="));
+
+    let options = ExportOptions {
+        dot_layout: dot::DotLayout {
+            ratio: Some("compress".into()),
+            size: Some("6.5,9".into()),
+            nodesep: Some(0.3),
+            ranksep: None,
+        },
+        ..ExportOptions::default()
+    };
+    let dot = dot::export(&graph, &options);
+    assert!(dot.contains("    ratio=\"compress\";\n    size=\"6.5,9\";\n    nodesep=0.3;\n"));
+    assert!(!dot.contains("ranksep="));
+}
+
+/// Collection edges are thick and bracketed in Mermaid, like in the other exporters.
+#[test]
+fn test_mermaid_collection_edges() {
+    let input = "variable Report\nvariable Summary\nfunction Collect\n    > [Report]\nfunction Archive [Report]\n    > Summary\nfunction Publish Summary\n\nCollect\nArchive\nPublish\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///collections.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let mmd = mermaid::export(&graph, &ExportOptions::default());
+
+    assert!(mmd.contains(" ==>|\"[Report]\"| "), "{}", mmd);
+    assert!(mmd.contains(" -->|Summary| "));
+}
+
+/// The HTML report lists diagnostics, linking those about a function to its node.
+#[test]
+fn test_html_report_lists_diagnostics() {
+    let input = "variable Page\nerror Missing\nfunction Load\n    > Page\n    | Missing\nfunction Show Page\n\nLoad\nShow\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///report.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    let reports: Vec<_> = flow
+        .diagnostics
+        .iter()
+        .map(|d| workspace.source_manager.resolve_diagnostic(d))
+        .collect();
+    let load = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Load")
+        .unwrap();
+
+    let html = vis_js::generate_report_html(&graph, &ExportOptions::default(), &reports);
+    assert!(html.contains(&format!("<h3>Diagnostics ({})</h3>", reports.len())));
+    assert!(html.contains(&format!(
+        "<li class=\"diag-warning\" data-node=\"{}\">",
+        load.uid
+    )));
+    assert!(html.contains("'Missing' is produced by 'Load'"));
+
+    let plain = vis_js::generate_interactive_html(&graph, &ExportOptions::default());
+    assert!(!plain.contains("id=\"diagnostics\""));
+}
+
+/// Front matter fonts apply to every exporter unless overridden on the command line.
+#[test]
+fn test_theme_fonts_across_exporters() {
+    let input = "---\nfont: Inter\nedge_font_size: 9\n---\nvariable Data\nfunction Load\n    > Data\n\nLoad\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///fonts.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let cli = ThemeConfig {
+        node_font_size: Some(16),
+        ..ThemeConfig::default()
+    };
+    let options = ExportOptions {
+        theme: cli.or_front_matter(&workspace.structure),
+        ..ExportOptions::default()
+    };
+
+    let dot = dot::export(&graph, &options);
+    assert!(dot.contains("node [fontname=\"Inter\", fontsize=16];"));
+    assert!(dot.contains("edge [fontname=\"Inter\", fontsize=9];"));
+
+    let data = vis_js::produce_vis_data(&graph, &options);
+    assert!(data
+        .nodes
+        .iter()
+        .all(|n| n.font.size == 16 && n.font.face == "'Inter', sans-serif"));
+    assert!(data
+        .edges
+        .iter()
+        .all(|e| e.font.size == 9 && e.font.face == "'Inter', sans-serif"));
+
+    let mmd = mermaid::export(&graph, &options);
+    assert!(mmd.contains("    fontFamily: \"Inter\"\n    fontSize: \"16px\""));
+
+    let tex = tikz::export(&graph, &options);
+    assert!(tex.contains("\\setsansfont{Inter}"));
+    assert!(tex.contains("font=\\sffamily\\fontsize{9}{10}\\selectfont"));
+
+    // Without a theme each exporter keeps its own defaults
+    let defaults = ExportOptions::default();
+    let plain = dot::export(&graph, &defaults);
+    assert!(plain.contains("node [fontname=\"Helvetica\", fontsize=10];"));
+    assert!(plain.contains("edge [fontname=\"Helvetica\", fontsize=9];"));
+    assert!(!plain.contains("    fontname="));
+    let data = vis_js::produce_vis_data(&graph, &defaults);
+    assert!(data.nodes.iter().all(|n| n.font.face == "sans-serif"));
+    assert!(data.edges.iter().all(|e| e.font.face == "monospace"));
+    assert!(!mermaid::export(&graph, &defaults).contains("config:"));
+    let tex = tikz::export(&graph, &defaults);
+    assert!(!tex.contains("fontspec"));
+    assert!(tex.contains("font=\\sffamily\\small"));
+}
+
+/// Compact Mermaid styles nodes inline and uses short ids, without classes or directions.
+#[test]
+fn test_mermaid_compact() {
+    let input = "group Web\nvariable Page\nerror Missing\nWeb function Load\n    > Page\n    | Missing\nfunction Show Page\n\nLoad\nShow\n";
+    let mut workspace = crate::analyzer::Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///compact.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+
+    let regular = mermaid::export(&graph, &ExportOptions::default());
+    assert!(regular.contains("classDef group0") && regular.contains("direction TB"));
+
+    let options = ExportOptions {
+        mermaid_compact: true,
+        ..ExportOptions::default()
+    };
+    let mmd = mermaid::export(&graph, &options);
+    assert!(!mmd.contains("classDef"));
+    assert!(!mmd.contains("class ") && !mmd.contains("group"));
+    assert!(!mmd.contains("direction") && !mmd.contains("N_"));
+    assert!(mmd.contains("stroke-width:3px"));
+    assert_eq!(
+        mmd.lines()
+            .filter(|l| l.trim_start().starts_with("style n"))
+            .count(),
+        graph.nodes.len()
+    );
+}
+
+/// Compact Mermaid keeps edges whose endpoint is missing from the graph.
+#[test]
+fn test_mermaid_compact_dangling_edge() {
+    let mut graph =
+        simulate("variable Page\nfunction Load\n    > Page\nfunction Show Page\n\nLoad\nShow\n");
+    graph.edges[0].to_node_uid = 999;
+
+    let options = ExportOptions {
+        mermaid_compact: true,
+        ..ExportOptions::default()
+    };
+    let mmd = mermaid::export(&graph, &options);
+    assert!(mmd.contains("N_999"), "{}", mmd);
+}
+
+/// Highlighting errors emphasizes every path into the fatal node and dims the rest.
+#[test]
+fn test_highlight_error_paths() {
+    let input = "variable Raw\nvariable Page\nerror Missing\nfunction Read\n    > Raw\nfunction Load Raw\n    > Page\n    | Missing\nfunction Show Page\n\nRead\nLoad\nShow\n";
+    let graph = simulate(input);
+
+    let paths = error_paths(&graph);
+    let mut names: Vec<&str> = graph
+        .nodes
+        .iter()
+        .filter(|n| paths.nodes.contains(&n.uid))
+        .map(|n| n.function.name.as_str())
+        .collect();
+    names.sort_unstable();
+    assert_eq!(names, ["FatalErrors", "Load", "Read"]);
+    assert_eq!(paths.edges.len(), 2);
+
+    let options = ExportOptions {
+        highlight: Some(Highlight::Errors),
+        ..ExportOptions::default()
+    };
+    let show = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Show")
+        .unwrap();
+
+    let dot = dot::export(&graph, &options);
+    assert_eq!(dot.matches("penwidth=2.5").count(), 2);
+    let show_line = dot
+        .lines()
+        .find(|l| l.trim_start().starts_with(&format!("N_{} [", show.uid)))
+        .unwrap();
+    assert!(show_line.contains(&format!("fillcolor=\"{}\"", theme::DIMMED_FILL)));
+
+    let mmd = mermaid::export(&graph, &options);
+    assert_eq!(mmd.matches("linkStyle").count(), graph.edges.len());
+    assert_eq!(mmd.matches("stroke-width:3px;").count(), 2);
+
+    let data = vis_js::produce_vis_data(&graph, &options);
+    let node = data.nodes.iter().find(|n| n.id == show.uid).unwrap();
+    assert_eq!(node.color.background, theme::DIMMED_FILL);
+    assert_eq!(
+        data.edges
+            .iter()
+            .filter(|e| e.color == theme::HIGHLIGHT_COLOR)
+            .count(),
+        2
+    );
+
+    // Without the option nothing is dimmed
+    assert!(!dot::export(&graph, &ExportOptions::default()).contains(theme::DIMMED_FILL));
+}
+
+/// The data dictionary lists every artifact with its producers and consumers.
+#[test]
+fn test_data_dictionary() {
+    let input = "# Rendered | cached\nvariable Page\nconstant Config\nerror Missing\nvariable Unused\nfunction Load Config\n    > Page\n    | Missing\nfunction Show Page\n\nLoad\nShow\n";
+    let workspace = analyze(input);
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let md = dictionary::export(&workspace.structure, &graph);
+
+    let rows: Vec<&str> = md.lines().filter(|l| l.starts_with("| ")).collect();
+    assert_eq!(
+        rows,
+        [
+            "| Name | Kind | Description | Produced by | Consumed by |",
+            "| Config | constant |  | — | Load |",
+            "| Missing | error |  | Load | — |",
+            "| Page | variable | Rendered \\| cached | Load | Show |",
+            "| Unused | variable |  | — | — |",
+        ]
+    );
+}
+
+/// The type graph links artifacts through the functions transforming them.
+#[test]
+fn test_type_graph() {
+    let input = "constant Config\nvariable Page\nvariable Html\nerror Missing\nfunction Load Config\n    > Page\n    | Missing\nfunction Render Page, Config\n    > Html\nfunction Unused Page\n    > Html\n\nLoad\nRender\n";
+    let workspace = analyze(input);
+    let structure = &workspace.structure;
+    let out = dot::export_types(structure, &ExportOptions::default());
+
+    let id = |name: &str| format!("T_{}", structure.artifacts[name].uid());
+    let nodes: Vec<&str> = out
+        .lines()
+        .filter(|l| l.starts_with("    T_") && !l.contains("->"))
+        .map(|l| l.trim().split(' ').next().unwrap())
+        .collect();
+    assert_eq!(nodes, ["Config", "Html", "Missing", "Page"].map(id));
+    assert!(out.contains(&format!("fillcolor=\"{}\"", theme::ERROR_COLOR)));
+
+    let edge = |from: &str, function: &str, to: &str| {
+        format!("    {} -> {} [label=\"{}\"", id(from), id(to), function)
+    };
+    let edges: Vec<&str> = out.lines().filter(|l| l.contains("->")).collect();
+    let expected = [
+        edge("Config", "Load", "Page"),
+        edge("Config", "Load", "Missing"),
+        edge("Page", "Render", "Html"),
+        edge("Config", "Render", "Html"),
+        edge("Page", "Unused", "Html"),
+    ];
+    assert_eq!(edges.len(), expected.len());
+    for (line, prefix) in edges.iter().zip(&expected) {
+        assert!(line.starts_with(prefix.as_str()), "{}", line);
+    }
+    assert!(edges[1].contains("style=\"dotted\""));
+}
+
+/// A `# @color` pragma overrides the node fill in every exporter.
+#[test]
+fn test_color_pragma_overrides_node_fill() {
+    let input = "group Web\n# @color #00f\nvariable Page\n# Loads the page\n# @color #FF8800\nWeb function Load\n    > Page\n# @color orange\nfunction Show Page\n# @color #0A4\nfunction Save Page\n\nLoad\nShow\nSave\n";
+    let workspace = analyze(input);
+    let structure = &workspace.structure;
+    let load = &structure.catalog["Load"];
+    assert_eq!(load.color.as_deref(), Some("#ff8800"));
+    assert_eq!(load.documentation.as_deref(), Some("Loads the page"));
+    assert_eq!(structure.catalog["Show"].color, None);
+    assert_eq!(structure.catalog["Save"].color.as_deref(), Some("#00aa44"));
+    assert!(structure
+        .diagnostics
+        .iter()
+        .any(|d| d.message.starts_with("Invalid color 'orange'")));
+    assert!(structure
+        .diagnostics
+        .iter()
+        .any(|d| d.code == Some("ignored-color") && d.message.ends_with("on a variable.")));
+
+    let graph = Flow::new(true).simulate(structure);
+    let options = ExportOptions::default();
+    let node = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Load")
+        .unwrap();
+
+    // The body takes the custom color, the border keeps the group color
+    let dot = dot::export(&graph, &options);
+    let line = dot
+        .lines()
+        .find(|l| l.trim_start().starts_with(&format!("N_{} [", node.uid)))
+        .unwrap();
+    assert!(line.contains("fillcolor=\"#ff8800\""));
+    assert!(line.contains(&format!(
+        "color=\"{}\"",
+        theme::Theme::get_group_color("Web").0
+    )));
+
+    let mmd = mermaid::export(&graph, &options);
+    assert!(mmd.contains(&format!("style N_{} fill:#ff8800", node.uid)));
+
+    let tex = tikz::export(&graph, &options);
+    assert!(tex.contains("fill={rgb,255:red,255;green,136;blue,0}"));
+
+    let data = vis_js::produce_vis_data(&graph, &options);
+    let vis_node = data.nodes.iter().find(|n| n.id == node.uid).unwrap();
+    assert_eq!(vis_node.color.background, "#ff8800");
+}
+
+/// Unicode names keep distinct identifiers in exports and exact UTF-16 ranges.
+#[test]
+fn test_unicode_identifiers() {
+    let input = "group Données\ngroup \"Donn es\"\ngroup Donn_es\nvariable Сторінка\nconstant 𝒜ccount\nDonnées function Régler\n    > Сторінка\n\"Donn es\" function Показати 𝒜ccount, Сторінка\nDonn_es function Show 𝒜ccount\n\nRégler\nПоказати\nShow\n";
+    let mut workspace = analyze(input);
+    assert!(
+        !workspace
+            .structure
+            .diagnostics
+            .iter()
+            .any(|d| d.severity == DiagnosticSeverity::ERROR),
+        "Unicode identifiers should parse"
+    );
+
+    let ids = ["Données", "Donn es", "Donn_es"].map(sanitize_id);
+    assert!(ids[0] != ids[1] && ids[1] != ids[2] && ids[0] != ids[2]);
+    assert_eq!(sanitize_id("Core"), "Core");
+
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    let options = ExportOptions::default();
+
+    let dot = dot::export(&graph, &options);
+    for id in &ids {
+        assert_eq!(dot.matches(&format!("subgraph cluster_{} ", id)).count(), 1);
+    }
+    assert!(dot.contains("label=\"Données\";"));
+    assert!(dot.contains("<B>Régler</B>") && dot.contains("label=\"Сторінка\""));
+
+    let mmd = mermaid::export(&graph, &options);
+    for id in &ids {
+        assert_eq!(mmd.matches(&format!("subgraph {} ", id)).count(), 1);
+    }
+    assert!(mmd.contains("[\"Donn es\"]") && mmd.contains("\"Показати\""));
+
+    // Columns count UTF-16 code units: 𝒜 takes two
+    let uid = crate::models::hash_name("Сторінка");
+    let spans = workspace.structure.symbol_table[&uid].occurrences.clone();
+    let ranges: Vec<Range> = spans
+        .into_iter()
+        .map(|s| workspace.source_manager.resolve_range(s))
+        .collect();
+    assert!(ranges.contains(&Range::new(Position::new(7, 38), Position::new(7, 46))));
+    assert!(ranges.contains(&Range::new(Position::new(3, 9), Position::new(3, 17))));
+
+    // Keyword boundaries are Unicode-aware: `Noneé` and `flowé` are identifiers
+    let input = "variable Noneé\nfunction flowé\n    > Noneé\nfunction Read Noneé\n\nflowé\nRead\n";
+    let workspace = analyze(input);
+    assert!(
+        workspace.structure.diagnostics.is_empty(),
+        "{:?}",
+        workspace.structure.diagnostics
+    );
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    assert!(graph.edges.iter().any(|e| e.token.kind.name() == "Noneé"));
+}
+
+/// Physics options are serialized into the interactive page.
+#[test]
+fn test_physics_options_in_html() {
+    let input = "variable Page\nfunction Load\n    > Page\nfunction Show Page\n\nLoad\nShow\n";
+    let graph = simulate(input);
+
+    let html = vis_js::generate_interactive_html(&graph, &ExportOptions::default());
+    assert!(html.contains(
+        "forceAtlas2Based: {\"gravitationalConstant\":-100.0,\"springLength\":10.0,\"avoidOverlap\":1.0,\"damping\":0.75}"
+    ));
+
+    let options = ExportOptions {
+        physics: vis_js::PhysicsOptions {
+            gravitational_constant: -300.0,
+            spring_length: 80.0,
+            ..vis_js::PhysicsOptions::default()
+        },
+        ..ExportOptions::default()
+    };
+    let html = vis_js::generate_interactive_html(&graph, &options);
+    assert!(html.contains("\"gravitationalConstant\":-300.0,\"springLength\":80.0"));
+
+    // The command line defaults match `Default`, and fractions stay within 0..=1
+    #[derive(clap::Parser)]
+    struct Args {
+        #[command(flatten)]
+        physics: vis_js::PhysicsOptions,
+    }
+    use clap::Parser;
+    let parsed = Args::try_parse_from(["tect"]).unwrap().physics;
+    let defaults = vis_js::PhysicsOptions::default();
+    assert_eq!(
+        serde_json::to_string(&parsed).unwrap(),
+        serde_json::to_string(&defaults).unwrap()
+    );
+    assert!(Args::try_parse_from(["tect", "--damping", "0.5"]).is_ok());
+    assert!(Args::try_parse_from(["tect", "--damping", "1.5"]).is_err());
+    assert!(Args::try_parse_from(["tect", "--avoid-overlap", "2"]).is_err());
+}
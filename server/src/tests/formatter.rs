@@ -1,5 +1,5 @@
 use super::common::assert_output;
-use crate::formatter::format_tect_source;
+use crate::formatter::{format_tect_source, FormatOptions};
 use std::fs::{self, File};
 use std::io::Write;
 
@@ -8,7 +8,8 @@ fn test_format_dsbg() {
     let input_path = "../examples/dsbg.tect";
     let content = fs::read_to_string(input_path).expect("Failed to read dsbg.tect");
 
-    let formatted = format_tect_source(&content).expect("Failed to format content");
+    let formatted =
+        format_tect_source(&content, &FormatOptions::default()).expect("Failed to format content");
     let mut output = File::create("../examples/test_outputs/formatted_dsbg.tect")
         .expect("Failed to create ../examples/test_outputs/formatted_dsbg.tect");
     write!(output, "{}", formatted)
@@ -19,3 +20,193 @@ fn test_format_dsbg() {
         formatted,
     );
 }
+
+#[test]
+fn test_format_custom_indentation() {
+    let input = "function F A\n> B\n| C\n";
+
+    let two_spaces = FormatOptions {
+        indent: 2,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        format_tect_source(input, &two_spaces).unwrap(),
+        "function F A\n  > B\n  | C\n"
+    );
+
+    let tabs = FormatOptions {
+        use_tabs: true,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        format_tect_source(input, &tabs).unwrap(),
+        "function F A\n\t> B\n\t| C\n"
+    );
+}
+
+#[test]
+fn test_format_aligned_outputs() {
+    let input = "function Render Article, Templates\n> Html, [Asset], Report\n| RenderError, Report\n| [FileSystemError]\n";
+    let options = FormatOptions {
+        align_outputs: true,
+        ..FormatOptions::default()
+    };
+
+    let expected = "function Render Article, Templates\n    > Html,        [Asset], Report\n    | RenderError, Report\n    | [FileSystemError]\n";
+    assert_eq!(format_tect_source(input, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_format_sorted_tokens() {
+    let input = "constant Settings\nvariable Article\nerror IoError\nfunction Render IoError, Article, Settings\n> Zeta, IoError, [Article]\n";
+    let options = FormatOptions {
+        sort_tokens: true,
+        ..FormatOptions::default()
+    };
+
+    let expected = "constant Settings\nvariable Article\nerror IoError\nfunction Render Settings, Article, IoError\n    > [Article], IoError, Zeta\n";
+    assert_eq!(format_tect_source(input, &options).unwrap(), expected);
+
+    // Sorting is opt-in
+    let unsorted = format_tect_source(input, &FormatOptions::default()).unwrap();
+    assert!(unsorted.contains("function Render IoError, Article, Settings"));
+}
+
+#[test]
+fn test_format_preserves_optional_marker() {
+    let input = "variable Page\nvariable Cache\nfunction Render  Page ,[Cache] ?\n";
+    let expected = "variable Page\nvariable Cache\nfunction Render Page, [Cache]?\n";
+    assert_eq!(
+        format_tect_source(input, &FormatOptions::default()).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_format_none_output() {
+    let input = "variable Message\nfunction Log Message\n  >   None\n";
+    let expected = "variable Message\nfunction Log Message\n    > None\n";
+    assert_eq!(
+        format_tect_source(input, &FormatOptions::default()).unwrap(),
+        expected
+    );
+}
+
+/// Comments between output branches are kept at the output indentation.
+#[test]
+fn test_format_comment_between_outputs() {
+    let input = "variable Page\nerror Missing\nfunction Load\n  > Page\n      # the file may be gone\n  |   Missing\n";
+    let expected = "variable Page\nerror Missing\nfunction Load\n    > Page\n    # the file may be gone\n    | Missing\n";
+    let formatted = format_tect_source(input, &FormatOptions::default()).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(
+        format_tect_source(&formatted, &FormatOptions::default()).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_format_assertion() {
+    let input = "function Load\nfunction Save\n## Saving follows loading\nassert   Load->Save  reachable\nassert no  Missing unhandled\n";
+    let expected = "function Load\nfunction Save\n## Saving follows loading\nassert Load -> Save reachable\nassert no Missing unhandled\n";
+    let formatted = format_tect_source(input, &FormatOptions::default()).unwrap();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn test_format_flow_block() {
+    let input = "function Build\n# Release pipeline\nflow  Release {\nBuild\n\n\n  # again\n      Build\n}\n";
+    let expected = "function Build\n# Release pipeline\nflow Release {\n    Build\n\n    # again\n    Build\n}\n";
+    let formatted = format_tect_source(input, &FormatOptions::default()).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(
+        format_tect_source(&formatted, &FormatOptions::default()).unwrap(),
+        expected
+    );
+}
+
+/// Formatting already formatted code must not change it.
+#[test]
+fn test_format_idempotent_on_examples() {
+    let variants = [
+        FormatOptions::default(),
+        FormatOptions {
+            align_outputs: true,
+            sort_tokens: true,
+            ..FormatOptions::default()
+        },
+    ];
+
+    // Layouts around comments and output-less functions that used to drift
+    let samples = [
+        "function F\n\n# doc\n\nconstant X\n",
+        "function F A\n\n# doc\nconstant X\n",
+        "function F A\n\nF\n",
+        "constant A # trailing\nconstant B\n",
+    ];
+    for sample in samples {
+        let first = format_tect_source(sample, &FormatOptions::default()).unwrap();
+        let second = format_tect_source(&first, &FormatOptions::default()).unwrap();
+        assert_eq!(
+            first, second,
+            "Formatting is not idempotent for {:?}",
+            sample
+        );
+    }
+    assert_eq!(
+        format_tect_source(samples[0], &FormatOptions::default()).unwrap(),
+        samples[0]
+    );
+
+    for entry in walkdir::WalkDir::new("../examples")
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "tect"))
+    {
+        let content = fs::read_to_string(entry.path()).unwrap();
+        for options in &variants {
+            let Some(first) = format_tect_source(&content, options) else {
+                continue;
+            };
+            let second = format_tect_source(&first, options).unwrap_or_else(|| {
+                panic!("Formatted output of {:?} no longer parses", entry.path())
+            });
+            assert_eq!(
+                first,
+                second,
+                "Formatting is not idempotent for {:?}",
+                entry.path()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_format_groups_imports() {
+    let input = "# Header\nimport \"./b.tect\"\nconstant A\nimport \"./a.tect\"\n\nA\n";
+
+    let expected = "# Header\nimport \"./a.tect\"\nimport \"./b.tect\"\n\nconstant A\n\nA\n";
+    assert_eq!(
+        format_tect_source(input, &FormatOptions::default()).unwrap(),
+        expected
+    );
+
+    let options = FormatOptions {
+        group_imports: false,
+        ..FormatOptions::default()
+    };
+    assert_eq!(format_tect_source(input, &options).unwrap(), input);
+}
+
+#[test]
+fn test_format_hoists_imports_above_definitions() {
+    let input = "---\ntitle: Shop\n---\n# Header\n\nfunction Load\n    > Page\n\n# Shared pages\nimport \"./pages.tect\"\n\nLoad\n";
+
+    let expected = "---\ntitle: Shop\n---\n# Header\n\n# Shared pages\nimport \"./pages.tect\"\n\nfunction Load\n    > Page\n\nLoad\n";
+    let formatted = format_tect_source(input, &FormatOptions::default()).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(
+        format_tect_source(&formatted, &FormatOptions::default()).unwrap(),
+        expected
+    );
+}
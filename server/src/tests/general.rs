@@ -60,3 +60,831 @@ fn test_lowercase_allowed() {
     let pair = TectParser::parse(Rule::program, input);
     assert!(pair.is_ok());
 }
+
+/// Ensures a flow step that reuses a type name is reported as such at the step.
+#[test]
+fn test_flow_step_shadowing_type_name() {
+    let input = "constant Settings\nfunction Load Settings\nSettings";
+    let mut a = Workspace::new();
+    let uri = Url::parse("file:///test.tect").unwrap();
+    a.analyze(uri, Some(input.to_string()));
+
+    let diag = a
+        .structure
+        .diagnostics
+        .iter()
+        .find(|d| d.message.contains("is a constant, not a function"))
+        .expect("Expected a shadowing diagnostic");
+    let span = diag.span.unwrap();
+    assert_eq!(&input[span.start..span.end], "Settings");
+    assert_eq!(span.start, input.rfind("Settings").unwrap());
+}
+
+/// Artifacts redefined in an imported file unify with the original definition.
+#[test]
+fn test_cross_file_artifacts_are_merged() {
+    let mut a = Workspace::new();
+
+    let lib_uri = Url::parse("file:///lib.tect").unwrap();
+    let lib_id = a.source_manager.get_id(&lib_uri);
+    a.source_manager.load_file(
+        lib_id,
+        Some(
+            "# Settings\nconstant Settings\nvariable Page\nfunction Render Settings\n    > Page\n"
+                .to_string(),
+        ),
+    );
+
+    let main_uri = Url::parse("file:///main.tect").unwrap();
+    let main = "import \"./lib.tect\"\n# Other docs\nconstant Settings\nvariable Page\nfunction Publish Page, Settings\n\nRender\nPublish\n";
+    a.analyze(main_uri, Some(main.to_string()));
+
+    let messages: Vec<&str> = a
+        .structure
+        .diagnostics
+        .iter()
+        .map(|d| d.message.as_str())
+        .collect();
+    assert!(
+        !messages.iter().any(|m| m.contains("already defined")),
+        "{:?}",
+        messages
+    );
+    assert!(
+        !messages.iter().any(|m| m.contains("Unused symbol")),
+        "{:?}",
+        messages
+    );
+    assert_eq!(
+        messages
+            .iter()
+            .filter(|m| m.contains("different documentation"))
+            .count(),
+        1,
+        "{:?}",
+        messages
+    );
+
+    let mut flow = crate::engine::Flow::new(true);
+    flow.simulate(&a.structure);
+    assert!(flow.diagnostics.is_empty(), "{:?}", flow.diagnostics);
+}
+
+/// Duplicates within a single file are still errors.
+#[test]
+fn test_same_file_duplicate_is_error() {
+    let mut a = Workspace::new();
+    let uri = Url::parse("file:///dup.tect").unwrap();
+    a.analyze(uri, Some("constant A\nconstant A\n".to_string()));
+    assert!(a
+        .structure
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("already defined")));
+}
+
+/// The CLI reads files through the SourceManager, so in-memory content wins over disk.
+#[test]
+fn test_cli_analysis_prefers_in_memory_content() {
+    let path = std::path::Path::new("../examples/dsbg.tect");
+    let uri = Url::from_file_path(std::fs::canonicalize(path).unwrap()).unwrap();
+
+    let mut workspace = Workspace::new();
+    let file_id = workspace.source_manager.get_id(&uri);
+    workspace
+        .source_manager
+        .load_file(file_id, Some("function Edited\n\nEdited\n".to_string()));
+
+    crate::analyze_file(&mut workspace, path).unwrap();
+    assert_eq!(workspace.structure.flow.len(), 1);
+    assert_eq!(workspace.structure.flow[0].function_name, "Edited");
+
+    assert!(crate::load_workspace(std::path::Path::new("../examples/missing.tect")).is_err());
+}
+
+/// Graph nodes link back to the file defining their function.
+#[test]
+fn test_attach_source_files() {
+    let mut workspace = Workspace::new();
+
+    let lib_uri = Url::parse("file:///lib.tect").unwrap();
+    let lib_id = workspace.source_manager.get_id(&lib_uri);
+    workspace.source_manager.load_file(
+        lib_id,
+        Some("variable Page\nfunction Render\n    > Page\n".to_string()),
+    );
+
+    let main_uri = Url::parse("file:///main.tect").unwrap();
+    let main = "import \"./lib.tect\"\nfunction Publish Page\n\nRender\nPublish\n";
+    workspace.analyze(main_uri, Some(main.to_string()));
+
+    let mut graph = crate::engine::Flow::new(true).simulate(&workspace.structure);
+    workspace.attach_source_files(&mut graph);
+
+    let source = |name: &str| {
+        graph
+            .nodes
+            .iter()
+            .find(|n| n.function.name == name)
+            .and_then(|n| n.source_file.clone())
+    };
+    assert_eq!(source("Render").as_deref(), Some("file:///lib.tect"));
+    assert_eq!(source("Publish").as_deref(), Some("file:///main.tect"));
+    assert_eq!(source("InitialNode"), None);
+}
+
+/// Statements around a syntax error are still analyzed.
+#[test]
+fn test_syntax_error_recovery() {
+    let input = "variable Page\nfunction Render\n    > Page\n%% broken %%\nfunction Publish Page\n!!\n\nRender\nPublish\n";
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///recovery.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let structure = &workspace.structure;
+
+    let syntax_errors: Vec<_> = structure
+        .diagnostics
+        .iter()
+        .filter(|d| d.message.starts_with("Syntax Error"))
+        .collect();
+    assert_eq!(syntax_errors.len(), 2, "{:?}", structure.diagnostics);
+    assert!(syntax_errors[1].message.contains("'!!'"));
+
+    assert!(structure.catalog.contains_key("Publish"));
+    let steps: Vec<_> = structure
+        .flow
+        .iter()
+        .map(|s| s.function_name.as_str())
+        .collect();
+    assert_eq!(steps, vec!["Render", "Publish"]);
+}
+
+/// Quoted identifiers allow names with spaces; the quotes are not part of the name.
+#[test]
+fn test_quoted_identifiers() {
+    let input = "variable \"Raw Data\"\ngroup \"Data Access\"\n\"Data Access\" function \"Load Config\"\n    > \"Raw Data\"\nfunction Use \"Raw Data\"\n\n\"Load Config\"\nUse\n";
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///quoted.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let structure = &workspace.structure;
+    assert!(
+        structure.diagnostics.is_empty(),
+        "{:?}",
+        structure.diagnostics
+    );
+
+    let load = &structure.catalog["Load Config"];
+    assert_eq!(load.group.as_ref().unwrap().name, "Data Access");
+    assert_eq!(load.produces[0][0].kind.name(), "Raw Data");
+    assert_eq!(structure.flow[0].function_name, "Load Config");
+
+    let graph = crate::engine::Flow::new(true).simulate(structure);
+    let dot = crate::export::dot::export(&graph, &Default::default());
+    assert!(dot.contains("<B>Load Config</B>"));
+}
+
+/// Timestamps convert days since the epoch to civil dates across leap rules.
+#[test]
+fn test_civil_from_days() {
+    assert_eq!(crate::civil_from_days(0), (1970, 1, 1));
+    assert_eq!(crate::civil_from_days(-1), (1969, 12, 31));
+    assert_eq!(crate::civil_from_days(11_016), (2000, 2, 29));
+    assert_eq!(crate::civil_from_days(19_782), (2024, 2, 29));
+    // 2100 is not a leap year
+    assert_eq!(crate::civil_from_days(47_540), (2100, 2, 28));
+    assert_eq!(crate::civil_from_days(47_541), (2100, 3, 1));
+}
+
+/// Exports can target directories that do not exist yet.
+#[test]
+fn test_write_output_creates_directories() {
+    let dir = std::env::temp_dir().join(format!("tect_out_{}", std::process::id()));
+    let path = dir.join("graphs").join("arch.dot");
+
+    crate::write_output(&path, "digraph {}").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "digraph {}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Front matter of the root file titles the graph; imported files' front matter is ignored.
+#[test]
+fn test_front_matter_titles_graph() {
+    let mut workspace = Workspace::new();
+    let lib_uri = Url::parse("file:///lib.tect").unwrap();
+    let lib_id = workspace.source_manager.get_id(&lib_uri);
+    workspace.source_manager.load_file(
+        lib_id,
+        Some("---\ntitle: Library\n---\nvariable Page\n".to_string()),
+    );
+
+    let main = "---\ntitle: \"Blog Engine\"\nauthor: Jane\nlayout: ignored\n---\nimport \"./lib.tect\"\nfunction Render\n    > Page\n\nRender\n";
+    workspace.analyze(
+        Url::parse("file:///main.tect").unwrap(),
+        Some(main.to_string()),
+    );
+    let structure = &workspace.structure;
+    assert!(
+        structure.diagnostics.is_empty(),
+        "{:?}",
+        structure.diagnostics
+    );
+    assert_eq!(structure.title.as_deref(), Some("Blog Engine"));
+    assert_eq!(structure.author.as_deref(), Some("Jane"));
+
+    let graph = crate::engine::Flow::new(true).simulate(structure);
+    assert_eq!(graph.title.as_deref(), Some("Blog Engine"));
+
+    let dot = crate::export::dot::export(&graph, &Default::default());
+    assert!(dot.contains("label=\"Blog Engine\\nJane\";"));
+    let mmd = crate::export::mermaid::export(&graph, &Default::default());
+    assert!(mmd.starts_with("---\ntitle: \"Blog Engine\"\n---\nflowchart TD"));
+
+    let formatted = crate::formatter::format_tect_source(main, &Default::default()).unwrap();
+    assert!(
+        formatted.starts_with("---\ntitle: \"Blog Engine\"\nauthor: Jane\nlayout: ignored\n---\n")
+    );
+}
+
+/// Front matter values lose one pair of matching quotes, and keep unbalanced ones.
+#[test]
+fn test_front_matter_strips_matching_quotes() {
+    let title = |value: &str| {
+        let mut workspace = Workspace::new();
+        workspace.analyze(
+            Url::parse("file:///quotes.tect").unwrap(),
+            Some(format!("---\ntitle: {}\n---\nvariable Page\n", value)),
+        );
+        workspace.structure.title
+    };
+
+    assert_eq!(title("'Ops'").as_deref(), Some("Ops"));
+    assert_eq!(title("\"Ops\"").as_deref(), Some("Ops"));
+    assert_eq!(title("It's").as_deref(), Some("It's"));
+    assert_eq!(title("'Ops").as_deref(), Some("'Ops"));
+    assert_eq!(title("a\"").as_deref(), Some("a\""));
+    assert_eq!(title("'Ops\"").as_deref(), Some("'Ops\""));
+    assert_eq!(title("\"\""), None);
+}
+
+/// Diagnostics resolve to a serializable report with 1-based positions.
+#[test]
+fn test_diagnostic_report_resolves_positions() {
+    let mut workspace = Workspace::new();
+    let uri = Url::parse("file:///tmp/report.tect").unwrap();
+    workspace.analyze(
+        uri,
+        Some("variable Data\n\nfunction Load Missing\n".to_string()),
+    );
+
+    let diag = workspace
+        .structure
+        .diagnostics
+        .iter()
+        .find(|d| d.message.contains("Missing"))
+        .cloned()
+        .expect("undefined token should be reported");
+    let report = workspace.source_manager.resolve_diagnostic(&diag);
+
+    assert_eq!(report.file.as_deref(), Some("/tmp/report.tect"));
+    assert_eq!(report.line, Some(3));
+    assert_eq!(report.column, Some(15));
+    assert_eq!(report.end_column, Some(22));
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["line"], 3);
+    assert!(json["severity"].is_string());
+}
+
+/// Indexed sibling files contribute definitions, but not flows or diagnostics.
+#[test]
+fn test_index_directory_resolves_sibling_definitions() {
+    let dir = std::env::temp_dir().join(format!("tect_siblings_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("types.tect"),
+        "variable Order\nvariable Unused\nfunction Ship Order\n\nShip\n",
+    )
+    .unwrap();
+    let main_path = dir.join("main.tect");
+    std::fs::write(
+        &main_path,
+        "function Checkout\n    > Order\n\nCheckout\nShip\n",
+    )
+    .unwrap();
+
+    let uri = Url::from_file_path(&main_path).unwrap();
+    let mut workspace = Workspace::new();
+    workspace.analyze(uri.clone(), None);
+    assert!(workspace
+        .structure
+        .diagnostics
+        .iter()
+        .any(|d| d.severity == tower_lsp::lsp_types::DiagnosticSeverity::ERROR));
+
+    assert!(workspace.needs_indexing(&uri));
+    let mut progress = Vec::new();
+    workspace.index_directory_with_progress(&uri, |done, total| progress.push((done, total)));
+    assert_eq!(progress, [(1, 2), (2, 2)]);
+    assert!(!workspace.needs_indexing(&uri));
+    workspace.analyze(uri, None);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let structure = &workspace.structure;
+    assert!(
+        structure.diagnostics.is_empty(),
+        "{:?}",
+        structure.diagnostics
+    );
+    assert!(structure.artifacts.contains_key("Order"));
+    assert_eq!(structure.catalog["Ship"].consumes.len(), 1);
+    assert_eq!(structure.flow.len(), 2);
+}
+
+/// Functions with the same inputs and outputs are reported once, as information.
+#[test]
+fn test_identical_contracts_are_reported() {
+    let input = "variable Raw\nvariable Clean\nerror Invalid\nfunction Sanitize Raw\n    > Clean\n    | Invalid\nfunction Scrub Raw\n    | Invalid\n    > Clean\nfunction Keep Raw\n    > Clean\n\nSanitize\nScrub\nKeep\n";
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///contracts.tect").unwrap(),
+        Some(input.to_string()),
+    );
+
+    let infos: Vec<_> = workspace
+        .structure
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == tower_lsp::lsp_types::DiagnosticSeverity::INFORMATION)
+        .collect();
+    assert_eq!(infos.len(), 1, "{:?}", workspace.structure.diagnostics);
+    assert_eq!(
+        infos[0].message,
+        "Functions 'Sanitize', 'Scrub' have identical contracts. Consider merging them."
+    );
+
+    let catalog = &workspace.structure.catalog;
+    assert_eq!(
+        catalog["Sanitize"].canonical_signature(),
+        catalog["Scrub"].canonical_signature()
+    );
+    assert_eq!(catalog["Keep"].canonical_signature(), "Raw>Clean");
+}
+
+/// A group without member functions is reported as information on its definition.
+#[test]
+fn test_empty_group_is_reported() {
+    let input =
+        "group Storage\ngroup Web\nvariable Page\nWeb function Render\n    > Page\n\nRender\n";
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///groups.tect").unwrap(),
+        Some(input.to_string()),
+    );
+
+    let diagnostics = &workspace.structure.diagnostics;
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(
+        diagnostics[0].message,
+        "Group 'Storage' has no member functions."
+    );
+    assert_eq!(
+        diagnostics[0].severity,
+        tower_lsp::lsp_types::DiagnosticSeverity::INFORMATION
+    );
+    assert_eq!(diagnostics[0].span.map(|s| s.start), Some(6));
+}
+
+#[test]
+fn test_assertion_targets_are_validated() {
+    let input = "variable Page\nerror Missing\nfunction Load\n    > Page\n    | Missing\n\nassert Load -> Page reachable\nassert no Load unhandled\nassert no Gone unhandled\n\nLoad\n";
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///asserts.tect").unwrap(),
+        Some(input.to_string()),
+    );
+
+    let messages: Vec<&str> = workspace
+        .structure
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == tower_lsp::lsp_types::DiagnosticSeverity::ERROR)
+        .map(|d| d.message.as_str())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "'Page' is a variable, not a function.",
+            "'Load' is a function, not an error.",
+            "Undefined error: 'Gone'",
+        ]
+    );
+    assert!(workspace.structure.assertions.is_empty());
+}
+
+/// `# tect:allow <code>` above a statement silences that diagnostic only.
+#[test]
+fn test_allow_pragma_suppresses_diagnostic() {
+    let input = "variable Page\nvariable Draft\nerror Missing\n# tect:allow unused-symbol\nvariable Legacy\n# Loads a page\n# tect:allow dead-function, unhandled-error\nfunction Load\n    > Page\n    | Missing\n\nLoad\n";
+    let mut workspace = Workspace::new();
+    let uri = Url::parse("file:///allow.tect").unwrap();
+    workspace.analyze(uri, Some(input.to_string()));
+    let mut flow = crate::engine::Flow::new(true);
+    flow.simulate(&workspace.structure);
+    workspace.structure.diagnostics.extend(flow.diagnostics);
+
+    let codes: Vec<_> = workspace
+        .structure
+        .diagnostics
+        .iter()
+        .filter(|d| !workspace.source_manager.is_suppressed(d))
+        .map(|d| d.code)
+        .collect();
+    assert_eq!(codes, vec![Some("unused-symbol")]);
+    assert!(workspace
+        .structure
+        .diagnostics
+        .iter()
+        .any(|d| d.code == Some("unhandled-error")));
+    assert_eq!(
+        workspace.structure.catalog["Load"].documentation.as_deref(),
+        Some("Loads a page")
+    );
+}
+
+/// Functions referenced outside any flow (here by an `assert`) are reported as dead.
+#[test]
+fn test_function_never_invoked_in_flow() {
+    let input = "variable Page\nfunction Load\n    > Page\nfunction Show Page\n# tect:allow dead-function\nfunction Archive Page\n\nLoad\n\nassert Load -> Show reachable\nassert Load -> Archive reachable\n";
+    let mut workspace = Workspace::new();
+    let uri = Url::parse("file:///dead.tect").unwrap();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let dead: Vec<&str> = workspace
+        .structure
+        .diagnostics
+        .iter()
+        .filter(|d| d.code == Some("dead-function"))
+        .filter(|d| !workspace.source_manager.is_suppressed(d))
+        .map(|d| d.message.as_str())
+        .collect();
+    assert_eq!(dead, ["Function 'Show' is never invoked in a flow."]);
+
+    // A file without flow steps only defines functions for others to use
+    let mut library = Workspace::new();
+    let uri = Url::parse("file:///library.tect").unwrap();
+    library.analyze(uri, Some("variable Page\nfunction Show Page\n".to_string()));
+    assert!(!library
+        .structure
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("never invoked")));
+}
+
+/// Imports resolve against the importing file, then the `tect.toml` project root,
+/// and may not leave the project root.
+#[test]
+fn test_imports_resolve_within_project_root() {
+    let base = std::env::temp_dir().join(format!("tect_root_{}", std::process::id()));
+    let project = base.join("project");
+    std::fs::create_dir_all(project.join("shared")).unwrap();
+    std::fs::create_dir_all(project.join("app")).unwrap();
+    std::fs::write(project.join("tect.toml"), "# Project settings\n").unwrap();
+    std::fs::write(project.join("shared/types.tect"), "variable Order\n").unwrap();
+    std::fs::write(base.join("outside.tect"), "variable Secret\n").unwrap();
+    let main_path = project.join("app/main.tect");
+    std::fs::write(
+        &main_path,
+        "import \"shared/types.tect\"\nimport \"../../outside.tect\"\nfunction Checkout\n    > Order\n\nCheckout\n",
+    )
+    .unwrap();
+
+    let mut workspace = Workspace::new();
+    workspace.analyze(Url::from_file_path(&main_path).unwrap(), None);
+    let project = std::fs::canonicalize(&project).unwrap();
+    std::fs::remove_dir_all(&base).unwrap();
+
+    assert_eq!(workspace.project_root.as_deref(), Some(project.as_path()));
+    let structure = &workspace.structure;
+    assert!(structure.artifacts.contains_key("Order"));
+    assert!(!structure.artifacts.contains_key("Secret"));
+    let errors: Vec<&str> = structure
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == tower_lsp::lsp_types::DiagnosticSeverity::ERROR)
+        .map(|d| d.message.as_str())
+        .collect();
+    assert_eq!(
+        errors,
+        ["Import '../../outside.tect' resolves outside the project root"]
+    );
+}
+
+/// Without a `tect.toml`, imports are contained in the workspace folder,
+/// or else in the directory of the analyzed file.
+#[test]
+fn test_imports_contained_without_manifest() {
+    let base = std::env::temp_dir().join(format!("tect_no_manifest_{}", std::process::id()));
+    std::fs::create_dir_all(base.join("app")).unwrap();
+    std::fs::write(base.join("outside.tect"), "variable Secret\n").unwrap();
+    let main_path = base.join("app/main.tect");
+    std::fs::write(
+        &main_path,
+        "import \"../outside.tect\"\nfunction Leak Secret\n\nLeak\n",
+    )
+    .unwrap();
+    let uri = Url::from_file_path(&main_path).unwrap();
+
+    let mut contained = Workspace::new();
+    contained.analyze(uri.clone(), None);
+    let mut in_folder = Workspace::new();
+    in_folder.default_root = Some(base.clone());
+    in_folder.analyze(uri, None);
+    let app = std::fs::canonicalize(base.join("app")).unwrap();
+    std::fs::remove_dir_all(&base).unwrap();
+
+    assert_eq!(contained.project_root.as_deref(), Some(app.as_path()));
+    assert!(!contained.structure.artifacts.contains_key("Secret"));
+    assert!(contained
+        .structure
+        .diagnostics
+        .iter()
+        .any(|d| d.message == "Import '../outside.tect' resolves outside the project root"));
+    assert!(in_folder.structure.artifacts.contains_key("Secret"));
+}
+
+/// Symlinks cannot lead imports out of the project, and a project opened
+/// through a symlink still resolves its own files.
+#[cfg(unix)]
+#[test]
+fn test_imports_through_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let base = std::env::temp_dir().join(format!("tect_symlink_{}", std::process::id()));
+    let project = base.join("project");
+    std::fs::create_dir_all(project.join("shared")).unwrap();
+    std::fs::create_dir_all(base.join("elsewhere")).unwrap();
+    std::fs::write(project.join("tect.toml"), "# Project settings\n").unwrap();
+    std::fs::write(project.join("shared/types.tect"), "variable Order\n").unwrap();
+    std::fs::write(base.join("elsewhere/secret.tect"), "variable Secret\n").unwrap();
+    symlink(base.join("elsewhere"), project.join("escape")).unwrap();
+    symlink(&project, base.join("alias")).unwrap();
+    let main = "import \"shared/types.tect\"\nimport \"escape/secret.tect\"\nfunction Checkout\n    > Order\n\nCheckout\n";
+    std::fs::write(project.join("main.tect"), main).unwrap();
+
+    let mut workspace = Workspace::new();
+    let uri = Url::from_file_path(base.join("alias/main.tect")).unwrap();
+    workspace.analyze(uri, None);
+    std::fs::remove_dir_all(&base).unwrap();
+
+    let structure = &workspace.structure;
+    assert!(structure.artifacts.contains_key("Order"));
+    assert!(!structure.artifacts.contains_key("Secret"));
+    let errors: Vec<&str> = structure
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == tower_lsp::lsp_types::DiagnosticSeverity::ERROR)
+        .map(|d| d.message.as_str())
+        .collect();
+    assert_eq!(
+        errors,
+        ["Import 'escape/secret.tect' resolves outside the project root"]
+    );
+}
+
+/// A minimum severity keeps errors and drops informational notes.
+#[test]
+fn test_min_severity_filters_diagnostics() {
+    use crate::models::MinSeverity;
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+
+    let input = "group Empty\nvariable Page\nfunction Show Page\n\nShow\nMissing\n";
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///severity.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let diagnostics = &workspace.structure.diagnostics;
+    assert!(diagnostics
+        .iter()
+        .any(|d| d.severity == DiagnosticSeverity::INFORMATION));
+
+    let kept: Vec<_> = diagnostics
+        .iter()
+        .filter(|d| MinSeverity::Warning.admits(d.severity))
+        .collect();
+    assert!(!kept.is_empty());
+    assert!(kept
+        .iter()
+        .all(|d| d.severity != DiagnosticSeverity::INFORMATION));
+    assert!(diagnostics
+        .iter()
+        .all(|d| MinSeverity::default().admits(d.severity)));
+    assert!(MinSeverity::Error.admits(DiagnosticSeverity::ERROR));
+    assert!(!MinSeverity::Error.admits(DiagnosticSeverity::WARNING));
+}
+
+/// The HTML report lists failed assertions alongside the other diagnostics.
+#[test]
+fn test_report_includes_failed_assertions() {
+    use clap::Parser;
+
+    let dir = std::env::temp_dir().join(format!("tect_report_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("main.tect");
+    std::fs::write(
+        &input,
+        "variable Page\nfunction Load\n    > Page\nfunction Show Page\n\nassert Show -> Load reachable\n\nLoad\nShow\n",
+    )
+    .unwrap();
+    let output = dir.join("report.html");
+
+    let cli = crate::Cli::try_parse_from([
+        "tect",
+        "build",
+        input.to_str().unwrap(),
+        "--report",
+        "-o",
+        output.to_str().unwrap(),
+    ])
+    .unwrap();
+    let Some(crate::Commands::Build {
+        input,
+        target,
+        view,
+        flow,
+        ..
+    }) = cli.command
+    else {
+        panic!("expected a build command");
+    };
+    crate::handle_build(input, &target, &flow, &view, &Default::default()).unwrap();
+
+    let html = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(html.contains("is not reachable from"), "{}", html);
+}
+
+/// Split diagrams keep the overview apart from a group named `overview`,
+/// and refuse groups that would overwrite another diagram.
+#[test]
+fn test_split_by_group_reserves_overview() {
+    let input = "group overview\ngroup \"_overview\"\nvariable Page\noverview function Load\n    > Page\nfunction Show Page\n\nLoad\nShow\n";
+    let mut workspace = Workspace::new();
+    workspace.analyze(
+        Url::parse("file:///split.tect").unwrap(),
+        Some(input.to_string()),
+    );
+    let graph = crate::engine::Flow::new(true).simulate(&workspace.structure);
+    let dir = std::env::temp_dir().join(format!("tect_split_{}", std::process::id()));
+    let options = Default::default();
+
+    crate::write_split_by_group(&graph, &dir, crate::OutputFormat::Mermaid, &options).unwrap();
+    assert!(dir.join("_overview.mmd").is_file());
+    assert!(dir.join("overview.mmd").is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let input = input.replace("overview function", "\"_overview\" function");
+    workspace.analyze(Url::parse("file:///split.tect").unwrap(), Some(input));
+    let graph = crate::engine::Flow::new(true).simulate(&workspace.structure);
+    let err = crate::write_split_by_group(&graph, &dir, crate::OutputFormat::Mermaid, &options)
+        .unwrap_err();
+    assert!(err.to_string().contains("would overwrite"), "{}", err);
+    assert!(!dir.exists());
+}
+
+/// Builds are only titled by the front matter or `--title`, not the file name.
+#[test]
+fn test_build_title_comes_from_front_matter_or_cli() {
+    use clap::Parser;
+
+    let dir = std::env::temp_dir().join(format!("tect_title_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("checkout.tect");
+    std::fs::write(&input, "variable Page\nfunction Load\n    > Page\n\nLoad\n").unwrap();
+    let output = dir.join("out.dot");
+    let build = |extra: &[&str]| {
+        let mut args = vec![
+            "tect",
+            "build",
+            input.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ];
+        args.extend(extra);
+        let Some(crate::Commands::Build {
+            input,
+            target,
+            view,
+            flow,
+            ..
+        }) = crate::Cli::try_parse_from(args).unwrap().command
+        else {
+            panic!("expected a build command");
+        };
+        crate::handle_build(input, &target, &flow, &view, &Default::default()).unwrap();
+        std::fs::read_to_string(&output).unwrap()
+    };
+
+    let untitled = build(&[]);
+    let titled = build(&["--title", "Checkout Flow"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(!untitled.contains("label=\"checkout"), "{}", untitled);
+    assert!(!untitled.contains("labelloc=t;"));
+    assert!(titled.contains("label=\"Checkout Flow\";"), "{}", titled);
+}
+
+/// Checking a directory with `--flow` skips the files without that flow,
+/// fails only when no file defines it, and reports errors as a failed check.
+#[test]
+fn test_check_directory_with_named_flow() {
+    use clap::Parser;
+
+    let dir = std::env::temp_dir().join(format!("tect_flows_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("serve.tect"),
+        "variable Request\nfunction Accept\n    > Request\nfunction Reply Request\n\nflow Serve {\n    Accept\n    Reply\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("other.tect"),
+        "variable Page\nfunction Load\n    > Page\nfunction Show Page\n\nLoad\nShow\n",
+    )
+    .unwrap();
+    let check = |flow: &str| {
+        let args = ["tect", "check", dir.to_str().unwrap(), "--flow", flow];
+        let Some(crate::Commands::Check {
+            input,
+            diagnostics_json,
+            min_severity,
+            flow,
+        }) = crate::Cli::try_parse_from(args).unwrap().command
+        else {
+            panic!("expected a check command");
+        };
+        crate::handle_check(input, &flow, diagnostics_json, min_severity)
+    };
+
+    let serve = check("Serve");
+    let missing = check("Deploy");
+    std::fs::write(dir.join("broken.tect"), "function Load Unknown\n\nLoad\n").unwrap();
+    let broken = check("Serve");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(serve.unwrap());
+    assert!(!broken.unwrap());
+    let err = missing.unwrap_err().to_string();
+    assert!(err.starts_with("Flow 'Deploy' is not defined"), "{}", err);
+}
+
+/// Explaining a function in a directory with `--flow` skips the files without
+/// that flow, and fails only when no file defines it.
+#[test]
+fn test_explain_directory_with_named_flow() {
+    use clap::Parser;
+
+    let dir = std::env::temp_dir().join(format!("tect_explain_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("a_other.tect"),
+        "variable Page\nfunction Load\n    > Page\nfunction Show Page\n\nLoad\nShow\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("serve.tect"),
+        "variable Request\nfunction Accept\n    > Request\nfunction Reply Request\n\nflow Serve {\n    Accept\n    Reply\n}\n",
+    )
+    .unwrap();
+    let explain = |function: &str, flow: &str| {
+        let args = [
+            "tect",
+            "explain",
+            function,
+            dir.to_str().unwrap(),
+            "--flow",
+            flow,
+        ];
+        let Some(crate::Commands::Explain {
+            function,
+            input,
+            flow,
+        }) = crate::Cli::try_parse_from(args).unwrap().command
+        else {
+            panic!("expected an explain command");
+        };
+        crate::find_function(&function, &input, &flow)
+    };
+
+    let reply = explain("Reply", "Serve");
+    let missing = explain("Reply", "Deploy");
+    std::fs::remove_dir_all(&dir).unwrap();
+    let (function, graph) = reply.unwrap();
+    assert_eq!(function.name, "Reply");
+    assert!(crate::metrics::neighbors(&graph, "Reply").is_some());
+    let err = missing.unwrap_err().to_string();
+    assert!(err.starts_with("Flow 'Deploy' is not defined"), "{}", err);
+}
@@ -0,0 +1,130 @@
+use super::common::simulate;
+use crate::models::{Graph, ValidationIssue};
+
+fn names(graph: &Graph) -> Vec<&str> {
+    graph
+        .nodes
+        .iter()
+        .map(|n| n.function.name.as_str())
+        .collect()
+}
+
+const CHAIN: &str = "variable A\nvariable B\nvariable C\nvariable D\nfunction One A\n    > B\nfunction Two B\n    > C\nfunction Three C\n    > D\nfunction Four D\n\nOne\nTwo\nThree\nFour\n";
+
+#[test]
+fn test_neighborhood_limits_depth() {
+    let graph = simulate(CHAIN);
+    let two = graph
+        .nodes
+        .iter()
+        .find(|n| n.function.name == "Two")
+        .unwrap();
+
+    let near = graph.neighborhood(two.uid, 1);
+    assert_eq!(names(&near), vec!["One", "Two", "Three"]);
+    assert_eq!(near.edges.len(), 2);
+
+    let far = graph.neighborhood(two.uid, 2);
+    assert_eq!(
+        names(&far),
+        vec!["InitialNode", "One", "Two", "Three", "Four"]
+    );
+
+    assert!(graph.neighborhood(two.uid, 0).edges.is_empty());
+    assert!(graph.neighborhood(0, 3).nodes.is_empty());
+}
+
+#[test]
+fn test_collapse_by_group() {
+    let input = "variable A\nvariable B\nvariable C\ngroup Core\nCore function Load\n    > A\nCore function Parse A\n    > B\nfunction Check B\n    > C\nCore function Store C\n\nLoad\nParse\nCheck\nStore\n";
+    let graph = simulate(input);
+    let collapsed = graph.collapse_by_group();
+
+    assert_eq!(names(&collapsed), vec!["Core", "Check"]);
+    let core = &collapsed.nodes[0];
+    assert_eq!(core.members, vec!["Load", "Parse", "Store"]);
+    assert_ne!(core.uid, core.function.uid);
+
+    // Core -> Check (B) and Check -> Core (C); Load -> Parse stays inside the group
+    let check = collapsed.nodes[1].uid;
+    let mut crossing: Vec<_> = collapsed
+        .edges
+        .iter()
+        .map(|e| (e.from_node_uid, e.to_node_uid, e.token.kind.name()))
+        .collect();
+    crossing.sort();
+    let mut expected = vec![(core.uid, check, "B"), (check, core.uid, "C")];
+    expected.sort();
+    assert_eq!(crossing, expected);
+
+    let dot = crate::export::dot::export(&collapsed, &Default::default());
+    assert!(dot.contains("<B>Core</B><BR/><FONT POINT-SIZE=\"8\">Load</FONT>"));
+    assert!(!dot.contains("subgraph cluster_Core"));
+}
+
+#[test]
+fn test_validate_reports_malformed_graphs() {
+    let graph = simulate(CHAIN);
+    assert!(graph.validate().is_empty());
+
+    let mut broken = graph.clone();
+    broken.nodes.push(broken.nodes[0].clone());
+    let mut edge = broken.edges[0].clone();
+    edge.to_node_uid = edge.from_node_uid;
+    broken.edges.push(edge);
+    broken.edges[0].to_node_uid = 7;
+    let uid = broken.nodes[0].uid;
+    let last = broken.edges.len() - 1;
+
+    let issues = broken.validate();
+    assert_eq!(
+        issues,
+        vec![
+            ValidationIssue::DuplicateNode { uid },
+            ValidationIssue::DanglingEdge {
+                edge: 0,
+                node_uid: 7
+            },
+            ValidationIssue::SelfLoop {
+                edge: last,
+                node_uid: broken.edges[last].from_node_uid
+            },
+        ]
+    );
+}
+
+/// A repeated step consuming what it produces loops on its node by design.
+#[test]
+fn test_validate_allows_feedback_loops() {
+    let graph = simulate("variable Draft\nfunction Start\n    > Draft\nfunction Revise Draft\n    > Draft\n\nStart\nRevise\nRevise\n");
+    assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+}
+
+#[test]
+fn test_split_by_group() {
+    let mut graph = simulate("group Web\ngroup Store\nvariable A\nvariable B\nStore function Load\n    > A\nWeb function Parse A\n    > B\nWeb function Show B\n\nLoad\nParse\nShow\n");
+    graph.title = Some("Site".to_string());
+
+    let parts = graph.split_by_group();
+    let groups: Vec<&str> = parts.iter().map(|(g, _)| g.as_str()).collect();
+    assert_eq!(groups, vec!["Store", "Web"]);
+
+    let web = &parts[1].1;
+    assert_eq!(names(web), vec!["Parse", "Show"]);
+    assert_eq!(web.edges.len(), 1);
+    assert_eq!(web.title.as_deref(), Some("Site · Web"));
+    assert!(web.validate().is_empty());
+}
+
+/// Filtering by tag keeps the flows of tagged artifacts and their endpoints.
+#[test]
+fn test_filter_by_tag() {
+    let graph = simulate(
+        "variable Request\nvariable Token @security @io\nvariable Page\nfunction Receive\n    > Request\nfunction Authenticate Request\n    > Token\nfunction Render Token\n    > Page\nfunction Show Page\n\nReceive\nAuthenticate\nRender\nShow\n",
+    );
+    let tagged = graph.filter_by_tag("security");
+    assert_eq!(names(&tagged), ["Authenticate", "Render"]);
+    assert_eq!(tagged.edges.len(), 1);
+    assert_eq!(tagged.edges[0].token.kind.tags(), ["io", "security"]);
+    assert!(graph.filter_by_tag("audit").nodes.is_empty());
+}
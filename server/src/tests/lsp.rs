@@ -0,0 +1,234 @@
+use crate::analyzer::Workspace;
+use crate::lsp::{Backend, SEMANTIC_TOKEN_TYPES};
+use tower_lsp::lsp_types::{Position, SemanticTokenType, Url};
+
+/// Decodes the delta-encoded tokens back into `(line, column, length, type)`.
+fn decode(content: &str) -> Vec<(u32, u32, u32, SemanticTokenType)> {
+    let mut line = 0;
+    let mut col = 0;
+    Backend::semantic_tokens(content)
+        .expect("Source should parse")
+        .into_iter()
+        .map(|t| {
+            if t.delta_line > 0 {
+                line += t.delta_line;
+                col = t.delta_start;
+            } else {
+                col += t.delta_start;
+            }
+            (
+                line,
+                col,
+                t.length,
+                SEMANTIC_TOKEN_TYPES[t.token_type as usize].clone(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_semantic_tokens_classification() {
+    let input = "# Docs\nconstant Settings\ngroup Core\nCore function Load Settings\n    > [Settings]\n\nLoad\n";
+    let tokens = decode(input);
+
+    let expected = vec![
+        (0, 0, 6, SemanticTokenType::COMMENT),
+        (1, 0, 8, SemanticTokenType::KEYWORD),
+        (1, 9, 8, SemanticTokenType::TYPE),
+        (2, 0, 5, SemanticTokenType::KEYWORD),
+        (2, 6, 4, SemanticTokenType::NAMESPACE),
+        (3, 0, 4, SemanticTokenType::NAMESPACE),
+        (3, 5, 8, SemanticTokenType::KEYWORD),
+        (3, 14, 4, SemanticTokenType::FUNCTION),
+        (3, 19, 8, SemanticTokenType::TYPE),
+        (4, 7, 8, SemanticTokenType::TYPE),
+        (6, 0, 4, SemanticTokenType::FUNCTION),
+    ];
+    assert_eq!(tokens, expected);
+}
+
+/// A syntax error does not blank out highlighting for the rest of the file.
+#[test]
+fn test_semantic_tokens_survive_syntax_errors() {
+    let input = "constant Settings\nfunction Broken Settings,,\nfunction Load Settings\n";
+    let tokens = decode(input);
+
+    assert!(tokens.contains(&(0, 9, 8, SemanticTokenType::TYPE)));
+    assert!(tokens.contains(&(2, 0, 8, SemanticTokenType::KEYWORD)));
+    assert!(tokens.contains(&(2, 9, 4, SemanticTokenType::FUNCTION)));
+}
+
+#[test]
+fn test_group_hover_lists_members() {
+    let input = "# Rendering layer\ngroup Rendering\nvariable Page\nvariable Html\nRendering function Render Page\n    > Html\nRendering function Minify Html\n    > Html\nfunction Other Html\n";
+    let mut ws = Workspace::new();
+    ws.analyze(
+        Url::parse("file:///hover.tect").unwrap(),
+        Some(input.to_string()),
+    );
+
+    let markdown = Backend::group_hover("Rendering", &ws.structure).unwrap();
+    assert_eq!(
+        markdown,
+        "### Group: `Rendering`\n\n---\n\nRendering layer\n\n**Members**\n\n- `Minify`: `Html -> Html`\n- `Render`: `Page -> Html`"
+    );
+    assert!(Backend::group_hover("Page", &ws.structure).is_none());
+}
+
+/// Quoted names are a single symbol for highlighting and hover.
+#[test]
+fn test_quoted_identifiers_are_one_symbol() {
+    let input = "variable Config\nfunction \"Load Config\"\n    > Config\n\n\"Load Config\"\n";
+    let tokens = decode(input);
+    assert!(tokens.contains(&(1, 9, 13, SemanticTokenType::FUNCTION)));
+    assert!(tokens.contains(&(4, 0, 13, SemanticTokenType::FUNCTION)));
+
+    let (word, range) = Backend::get_word_at(input, Position::new(4, 6)).unwrap();
+    assert_eq!(word, "Load Config");
+    assert_eq!((range.start.character, range.end.character), (0, 13));
+}
+
+/// Applying the delta edits to the previous tokens yields the new tokens.
+#[test]
+fn test_semantic_tokens_delta() {
+    let old = Backend::semantic_tokens("variable Page\nfunction Load\n    > Page\n").unwrap();
+    let new = Backend::semantic_tokens("variable Page\nerror Missing\nfunction Load\n    > Page\n")
+        .unwrap();
+
+    let edits = Backend::semantic_tokens_edits(&old, &new);
+    assert_eq!(edits.len(), 1);
+    let edit = &edits[0];
+    assert_eq!(edit.start % 5, 0);
+    assert_eq!(edit.delete_count % 5, 0);
+
+    let start = edit.start as usize / 5;
+    let mut patched = old.clone();
+    patched.splice(
+        start..start + edit.delete_count as usize / 5,
+        edit.data.clone().unwrap_or_default(),
+    );
+    assert_eq!(patched, new);
+
+    assert!(Backend::semantic_tokens_edits(&new, &new).is_empty());
+}
+
+/// Import paths link to the imported file, resolved next to the importing one,
+/// then at the project root; missing and out-of-project targets get no link.
+#[test]
+fn test_import_document_links() {
+    let content = "# Shared definitions\nimport \"shared.tect\"\n  import \"../lib/types.tect\"\nimport \"lib/types.tect\"\nimport \"missing.tect\"\nimport \"../../outside.tect\"\nvariable Page\n";
+    let base = Url::parse("file:///project/src/main.tect").unwrap();
+    let mut ws = Workspace::new();
+    ws.project_root = Some(std::path::PathBuf::from("/project"));
+    for file in [
+        "file:///project/src/shared.tect",
+        "file:///project/lib/types.tect",
+        "file:///outside.tect",
+    ] {
+        let id = ws.source_manager.get_id(&Url::parse(file).unwrap());
+        ws.source_manager
+            .load_file(id, Some("variable Page\n".to_string()));
+    }
+    let links = Backend::import_links(&mut ws, content, &base);
+
+    assert_eq!(links.len(), 3);
+    assert_eq!(
+        links[0].target.as_ref().unwrap().as_str(),
+        "file:///project/src/shared.tect"
+    );
+    assert_eq!(links[0].range.start, Position::new(1, 8));
+    assert_eq!(links[0].range.end, Position::new(1, 19));
+    assert_eq!(
+        links[1].target.as_ref().unwrap().as_str(),
+        "file:///project/lib/types.tect"
+    );
+    assert_eq!(links[1].range.start, Position::new(2, 10));
+    assert_eq!(
+        links[2].target.as_ref().unwrap().as_str(),
+        "file:///project/lib/types.tect"
+    );
+    assert_eq!(links[2].range.start.line, 3);
+}
+
+/// Edits return at once; only the last edit of a burst is analyzed, after the quiet period.
+#[tokio::test]
+async fn test_did_change_debounces_analysis() {
+    use crate::lsp::DIAGNOSTICS_DEBOUNCE;
+    use crate::models::hash_name;
+    use tower_lsp::lsp_types::{
+        DidChangeTextDocumentParams, TextDocumentContentChangeEvent,
+        VersionedTextDocumentIdentifier,
+    };
+    use tower_lsp::{LanguageServer, LspService};
+
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner();
+    let uri = Url::parse("file:///debounce.tect").unwrap();
+    let edit = |version: i32, text: &str| DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: text.to_string(),
+        }],
+    };
+    let analyzed = |name: &str| {
+        let ws = backend.workspace.lock().unwrap();
+        ws.structure.symbol_table.contains_key(&hash_name(name))
+    };
+
+    backend
+        .did_change(edit(1, "function First\n\nFirst\n"))
+        .await;
+    let first = backend.pending_analyses.lock().unwrap()[&uri].abort_handle();
+    backend
+        .did_change(edit(2, "function Second\n\nSecond\n"))
+        .await;
+    tokio::task::yield_now().await;
+    assert!(first.is_finished(), "the superseded analysis is aborted");
+    assert!(!analyzed("Second"), "analysis waits for the quiet period");
+
+    tokio::time::sleep(DIAGNOSTICS_DEBOUNCE * 2).await;
+    assert!(analyzed("Second"));
+    assert!(!analyzed("First"));
+}
+
+/// Diagnostics are published on the file they belong to, with the version
+/// of the open document they were computed from.
+#[tokio::test]
+async fn test_diagnostics_are_published_per_file() {
+    use tower_lsp::LspService;
+
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner();
+    let main = Url::parse("file:///main.tect").unwrap();
+    let lib = Url::parse("file:///lib.tect").unwrap();
+    {
+        let mut ws = backend.workspace.lock().unwrap();
+        let id = ws.source_manager.get_id(&lib);
+        ws.source_manager
+            .load_file(id, Some("variable Page\nvariable Unused\n".to_string()));
+    }
+    backend
+        .open_documents
+        .lock()
+        .unwrap()
+        .insert(main.clone(), 3);
+
+    let content = "import \"./lib.tect\"\nfunction Render Page\n\nRender\n";
+    let (published, _) = backend.analyze_documents(&main, Some(content.to_string()));
+
+    let uris: Vec<&Url> = published.iter().map(|p| &p.uri).collect();
+    assert_eq!(uris, [&lib, &main]);
+    let (lib_params, main_params) = (&published[0], &published[1]);
+    assert_eq!(lib_params.version, None);
+    assert_eq!(main_params.version, Some(3));
+    assert!(lib_params
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("Unused")));
+    assert!(main_params
+        .diagnostics
+        .iter()
+        .all(|d| !d.message.contains("Unused")));
+}
@@ -0,0 +1,125 @@
+use crate::engine::Flow;
+use crate::metrics::{coupling_matrix, critical_path, neighbors, UNGROUPED};
+use tower_lsp::lsp_types::Url;
+
+/// Counts edges between groups, ignoring intra-group and artificial edges.
+#[test]
+fn test_coupling_matrix_counts_cross_group_edges() {
+    let input = r#"
+constant Config
+variable Raw
+variable Parsed
+variable Report
+
+group Input
+group Core
+
+Input function Load Config
+    > Raw
+Input function Parse Raw
+    > Parsed
+Core function Analyze Parsed, Config
+    > Report
+function Publish Report
+
+Load
+Parse
+Analyze
+Publish
+"#;
+    let mut workspace = crate::analyzer::Workspace::new();
+    let uri = Url::parse("file:///coupling.tect").unwrap();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let mut flow = Flow::new(true);
+    let graph = flow.simulate(&workspace.structure);
+    let matrix = coupling_matrix(&graph);
+
+    let key = |a: &str, b: &str| (a.to_string(), b.to_string());
+    assert_eq!(matrix.get(&key("Input", "Core")), Some(&1));
+    assert_eq!(matrix.get(&key("Core", UNGROUPED)), Some(&1));
+    assert!(!matrix.contains_key(&key("Input", "Input")));
+    assert_eq!(matrix.values().sum::<usize>(), 2, "{:?}", matrix);
+}
+
+/// The critical path is the longest chain of dependent functions.
+#[test]
+fn test_critical_path_follows_longest_chain() {
+    let input = r#"
+variable Raw
+variable Parsed
+variable Summary
+variable Report
+
+function Load
+    > Raw
+function Parse Raw
+    > Parsed
+function Summarize Raw
+    > Summary
+function Render Parsed
+    > Report
+function Publish Report
+
+Load
+Summarize
+Load
+Parse
+Render
+Publish
+"#;
+    let mut workspace = crate::analyzer::Workspace::new();
+    let uri = Url::parse("file:///critical.tect").unwrap();
+    workspace.analyze(uri, Some(input.to_string()));
+
+    let graph = Flow::new(true).simulate(&workspace.structure);
+    assert_eq!(
+        critical_path(&graph),
+        vec!["Load", "Parse", "Render", "Publish"]
+    );
+}
+
+/// Neighbors list the producers of each input and the consumers of each output.
+#[test]
+fn test_neighbors_of_function() {
+    let input = r#"
+constant Config
+variable Page
+variable Html
+error Missing
+
+function Load Config
+    > Page
+    | Missing
+function Render Page
+    > Html
+function Show Html
+function Spare Page
+
+Load
+Render
+Show
+"#;
+    let mut workspace = crate::analyzer::Workspace::new();
+    let uri = Url::parse("file:///neighbors.tect").unwrap();
+    workspace.analyze(uri, Some(input.to_string()));
+    let graph = Flow::new(true).simulate(&workspace.structure);
+
+    let load = neighbors(&graph, "Load").unwrap();
+    let names = |nodes: &[&crate::models::Node]| {
+        nodes
+            .iter()
+            .map(|n| n.function.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(load.inputs.keys().copied().collect::<Vec<_>>(), ["Config"]);
+    assert!(load.inputs["Config"][0].is_artificial_graph_start);
+    assert_eq!(names(&load.outputs["Page"]), ["Render"]);
+    assert!(load.outputs["Missing"][0].is_artificial_error_termination);
+
+    let render = neighbors(&graph, "Render").unwrap();
+    assert_eq!(names(&render.inputs["Page"]), ["Load"]);
+    assert_eq!(names(&render.outputs["Html"]), ["Show"]);
+    assert!(neighbors(&graph, "Spare").is_none());
+    assert!(neighbors(&graph, "FinalNode").is_none());
+}
@@ -1,5 +1,10 @@
 mod common;
+mod diff;
 mod engine;
+mod export;
 mod formatter;
 mod general;
+mod graph;
+mod lsp;
+mod metrics;
 mod parser;